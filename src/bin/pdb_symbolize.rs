@@ -0,0 +1,416 @@
+//! Symbolize the module offsets and addresses of logs and crash reports offline, by the pdbs in the symbol
+//! path. It does not depend on Windows, i.e. it runs on Linux against the pdb files.
+//!
+//! `pdb_symbolize [--symbol-path <path>] [--modules <file>] [<input file>]`
+//! - the input lines are read from the file or stdin. i.e. `ntdll!+0x1234`, `ntdll.dll+0x1234`, or the
+//!   address `0x7ffb01231234` in the modules of `--modules`
+//! - the modules file has a line per module: `<base> <size> <module path> [<pdb name> <GUID><age>]`. the pdb
+//!   is matched by the guid and age of the code view if they are given, and downloaded from the `srv*` entries
+//! - a json object is written to stdout for each input line
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::ExitCode,
+};
+use sys_monitor::{
+    demangle,
+    pdb_info::{self, parse_pdb, PdbInfo},
+    symbol_path::{PdbSignature, SymbolPath},
+    symbol_server,
+};
+use tracing_subscriber::filter::EnvFilter;
+
+const USAGE: &str = "Usage: pdb_symbolize [--symbol-path <path>] [--modules <file>] [<input file>]
+  --symbol-path  the directories of pdbs in the syntax of _NT_SYMBOL_PATH. default: $_NT_SYMBOL_PATH
+  --modules      the lines of `<base> <size> <module path> [<pdb name> <GUID><age>]` for the input
+                 addresses, and the pdbs by the guid and age of the modules
+  <input file>   the lines of `module!+offset`, `module+0xoffset` or `0xaddress`. default: stdin";
+
+#[derive(Debug, Serialize)]
+struct Symbolized {
+    input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<String>,
+    frames: Vec<Frame>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// the inlined functions are before the function that contains them
+#[derive(Debug, Serialize)]
+struct Frame {
+    function_offset: String,
+    function_offset_demangled: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Module {
+    base: u64,
+    size: u64,
+    name: String,
+    pdb_signature: Option<PdbSignature>,
+}
+
+struct Symbolizer {
+    symbol_path: SymbolPath,
+    modules: Vec<Module>,
+    // (the module file name in lowercase, the key of pdb signature) -> the parsed pdb or the error
+    pdbs: HashMap<(String, Option<String>), Result<PdbInfo, String>>,
+}
+
+impl Symbolizer {
+    fn symbolize(&mut self, input: &str) -> Symbolized {
+        let mut symbolized = Symbolized {
+            input: input.to_string(),
+            module: None,
+            offset: None,
+            frames: vec![],
+            error: None,
+        };
+        let (module, offset) = match parse_input(input, &self.modules) {
+            Ok(module_offset) => module_offset,
+            Err(e) => {
+                symbolized.error = Some(format!("{e:#}"));
+                return symbolized;
+            }
+        };
+        symbolized.module = Some(module.clone());
+        symbolized.offset = Some(format!("{offset:#x}"));
+        let pdb_info = match self.pdb_info(module.as_str()) {
+            Ok(pdb_info) => pdb_info,
+            Err(e) => {
+                symbolized.error = Some(e.clone());
+                return symbolized;
+            }
+        };
+        symbolized.frames = pdb_info
+            .get_location_info_by_offset(offset)
+            .into_iter()
+            .filter(|(function_offset, _)| !function_offset.is_empty())
+            .map(|(function_offset, line_offset)| {
+                let (file, line) = match line_offset.rsplit_once(": ") {
+                    Some((file, line)) => (Some(file.to_string()), line.parse().ok()),
+                    None => (None, None),
+                };
+                Frame {
+                    function_offset_demangled: demangle::demangle_location(&function_offset),
+                    function_offset,
+                    file,
+                    line,
+                }
+            })
+            .collect();
+        if symbolized.frames.is_empty() {
+            symbolized.error = Some(format!("No symbol at {offset:#x} in the pdb of {module}"));
+        }
+        symbolized
+    }
+
+    // the pdb is parsed once for each module
+    fn pdb_info(&mut self, module: &str) -> &Result<PdbInfo, String> {
+        let file_name = file_name(module).to_lowercase();
+        let pdb_signature = module_pdb_signature(&self.modules, file_name.as_str());
+        let key = (
+            file_name,
+            pdb_signature.map(|pdb_signature| pdb_signature.key.clone()),
+        );
+        self.pdbs.entry(key).or_insert_with_key(|(file_name, _)| {
+            load_pdb(&self.symbol_path, file_name.as_str(), pdb_signature)
+        })
+    }
+}
+
+// the signature of the module in `--modules` by the file name, with or without the extension
+fn module_pdb_signature<'a>(modules: &'a [Module], module_name: &str) -> Option<&'a PdbSignature> {
+    modules
+        .iter()
+        .filter(|module| {
+            let name = file_name(module.name.as_str());
+            name.eq_ignore_ascii_case(module_name)
+                || Path::new(name)
+                    .file_stem()
+                    .is_some_and(|stem| stem.eq_ignore_ascii_case(module_name))
+        })
+        .find_map(|module| module.pdb_signature.as_ref())
+}
+
+// the pdb is matched by the guid and age if the signature is known. otherwise the first pdb is used
+fn load_pdb(
+    symbol_path: &SymbolPath,
+    module: &str,
+    pdb_signature: Option<&PdbSignature>,
+) -> Result<PdbInfo, String> {
+    let pdb_paths = symbol_path.pdb_candidates(Path::new(module), pdb_signature);
+    let mut errors = match pdb_info::open_matched_pdb(&pdb_paths, None, pdb_signature) {
+        Ok((_, mut pdb)) => return parse_pdb(&mut pdb).map_err(|e| format!("{e:#}")),
+        Err(e) => vec![format!("{e:#}")],
+    };
+    // downloaded to the cache of `srv*` then it is found in the cache by the next run
+    let requests = pdb_signature
+        .map(|pdb_signature| symbol_server::download_requests(symbol_path, pdb_signature))
+        .unwrap_or_default();
+    for request in requests.iter() {
+        let r = symbol_server::download(request)
+            .and_then(|pdb_path| pdb_info::open_matched_pdb(&[pdb_path], None, pdb_signature));
+        match r {
+            Ok((_, mut pdb)) => return parse_pdb(&mut pdb).map_err(|e| format!("{e:#}")),
+            Err(e) => errors.push(format!("{}: {e:#}", request.url)),
+        }
+    }
+    Err(errors.join("; "))
+}
+
+/// (module, offset) of `module!+offset`, `module+0xoffset` or the address in the modules
+fn parse_input(input: &str, modules: &[Module]) -> Result<(String, u32)> {
+    let (module, offset) = if let Some((module, offset)) = input.split_once('!') {
+        (
+            module.to_string(),
+            parse_hex(offset.trim_start_matches('+'))?,
+        )
+    } else if let Some((module, offset)) = input.rsplit_once('+') {
+        (module.to_string(), parse_hex(offset)?)
+    } else {
+        let address = parse_hex(input)?;
+        let module = modules
+            .iter()
+            .find(|module| address >= module.base && address - module.base < module.size)
+            .ok_or_else(|| anyhow!("No module contains the address {address:#x}"))?;
+        (module.name.clone(), address - module.base)
+    };
+    let module = module.trim();
+    if module.is_empty() {
+        return Err(anyhow!("No module in {input}"));
+    }
+    let offset = u32::try_from(offset).map_err(|_| anyhow!("The offset is too large: {input}"))?;
+    Ok((module.to_string(), offset))
+}
+
+// the lines of `<base> <size> <module path> [<pdb name> <GUID><age>]`. the module path may contain spaces
+fn parse_modules(text: &str) -> Result<Vec<Module>> {
+    let mut modules = vec![];
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let (Some(base), Some(size), Some(name)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!(
+                "The module is not `<base> <size> <module path>`: {line}"
+            ));
+        };
+        let (name, pdb_signature) = match split_pdb_signature(name.trim()) {
+            Some((name, pdb_signature)) => (name, Some(pdb_signature)),
+            None => (name.trim(), None),
+        };
+        modules.push(Module {
+            base: parse_hex(base)?,
+            size: parse_hex(size)?,
+            name: name.to_string(),
+            pdb_signature,
+        });
+    }
+    Ok(modules)
+}
+
+// the trailing `<pdb name> <GUID><age>` of the module path. i.e. `ntkrnlmp.pdb` of the code view, and the 32
+// hex digits of guid with the hex digits of age as the directory name of symstore
+fn split_pdb_signature(name: &str) -> Option<(&str, PdbSignature)> {
+    let (rest, key) = name.rsplit_once(char::is_whitespace)?;
+    if key.len() <= 32 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let (module, pdb_name) = rest.trim_end().rsplit_once(char::is_whitespace)?;
+    let module = module.trim_end();
+    if module.is_empty() || !pdb_name.to_ascii_lowercase().ends_with(".pdb") {
+        return None;
+    }
+    Some((
+        module,
+        PdbSignature {
+            pdb_path: pdb_name.to_string(),
+            key: key.to_ascii_uppercase(),
+        },
+    ))
+}
+
+// the numbers are hex as the debuggers, with or without `0x`
+fn parse_hex(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+        .replace('`', "");
+    u64::from_str_radix(digits.as_str(), 16).map_err(|e| anyhow!("Invalid hex number {s}: {e}"))
+}
+
+// the module name of path. i.e. `ntdll.dll` of `C:\Windows\System32\ntdll.dll`
+fn file_name(module: &str) -> &str {
+    module.rsplit(['\\', '/']).next().unwrap_or(module)
+}
+
+fn run(args: Vec<String>) -> Result<()> {
+    let mut symbol_path = env::var("_NT_SYMBOL_PATH").unwrap_or_default();
+    let mut modules = vec![];
+    let mut input_path = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--symbol-path" => {
+                symbol_path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("No value of --symbol-path"))?
+            }
+            "--modules" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("No value of --modules"))?;
+                let text = fs::read_to_string(path.as_str())
+                    .map_err(|e| anyhow!("Failed to read {path}: {e}"))?;
+                modules = parse_modules(text.as_str())?;
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if arg.starts_with("--") => return Err(anyhow!("Unknown option {arg}")),
+            _ => input_path = Some(arg),
+        }
+    }
+    let symbol_path = SymbolPath::parse(symbol_path.as_str())?;
+    if symbol_path.entries.is_empty() {
+        return Err(anyhow!(
+            "No symbol path, set --symbol-path or _NT_SYMBOL_PATH"
+        ));
+    }
+
+    let input: Box<dyn BufRead> = match input_path {
+        Some(path) => Box::new(BufReader::new(
+            File::open(path.as_str()).map_err(|e| anyhow!("Failed to open {path}: {e}"))?,
+        )),
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+    let mut symbolizer = Symbolizer {
+        symbol_path,
+        modules,
+        pdbs: HashMap::new(),
+    };
+    let mut stdout = io::stdout().lock();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let symbolized = symbolizer.symbolize(line);
+        writeln!(stdout, "{}", serde_json::to_string(&symbolized)?)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+    match run(env::args().skip(1).collect()) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e:#}\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_inputs() {
+        let modules = parse_modules(
+            "0x7ffb01220000 0x1f8000 C:\\Windows\\System32\\ntdll.dll\n\n7ff6`00000000 1000 D:\\my app\\app.exe",
+        )
+        .unwrap();
+        assert_eq!(
+            modules[1],
+            Module {
+                base: 0x7ff600000000,
+                size: 0x1000,
+                name: "D:\\my app\\app.exe".to_string(),
+                pdb_signature: None,
+            }
+        );
+        assert!(parse_modules("0x1000 C:\\a.dll").is_err());
+
+        let parse = |input| parse_input(input, &modules).ok();
+        assert_eq!(parse("ntdll!+0x1234"), Some(("ntdll".to_string(), 0x1234)));
+        assert_eq!(parse("ntdll!1234"), Some(("ntdll".to_string(), 0x1234)));
+        assert_eq!(
+            parse("ntdll.dll+0x1a"),
+            Some(("ntdll.dll".to_string(), 0x1a))
+        );
+        assert_eq!(
+            parse("0x7ffb01231234"),
+            Some(("C:\\Windows\\System32\\ntdll.dll".to_string(), 0x11234))
+        );
+        assert_eq!(parse("0x7ffb00000000"), None);
+        assert_eq!(parse("+0x10"), None);
+        assert_eq!(parse("ntdll+0x1ffffffff"), None);
+        assert_eq!(file_name("C:\\Windows\\System32\\ntdll.dll"), "ntdll.dll");
+    }
+
+    #[test]
+    fn parse_pdb_signatures() {
+        let modules = parse_modules(
+            "fffff80000000000 1000000 C:\\Windows\\System32\\ntoskrnl.exe ntkrnlmp.pdb 6a399ae04bc64de9870b3657f8947e7e1a\n\
+             1000 1000 D:\\my app\\app.exe 1234",
+        )
+        .unwrap();
+        assert_eq!(modules[0].name, "C:\\Windows\\System32\\ntoskrnl.exe");
+        assert_eq!(
+            modules[0].pdb_signature,
+            Some(PdbSignature {
+                pdb_path: "ntkrnlmp.pdb".to_string(),
+                key: "6A399AE04BC64DE9870B3657F8947E7E1A".to_string(),
+            })
+        );
+        // the trailing word is a part of the path unless it is the pdb with the key
+        assert_eq!(modules[1].name, "D:\\my app\\app.exe 1234");
+        assert_eq!(modules[1].pdb_signature, None);
+        assert!(split_pdb_signature("ntkrnlmp.pdb 6A399AE04BC64DE9870B3657F8947E7E1A").is_none());
+        assert!(split_pdb_signature("a.exe a.dll 6A399AE04BC64DE9870B3657F8947E7E1A").is_none());
+
+        assert_eq!(
+            module_pdb_signature(&modules, "ntoskrnl").map(|s| s.key.as_str()),
+            Some("6A399AE04BC64DE9870B3657F8947E7E1A")
+        );
+        assert!(module_pdb_signature(&modules, "ntoskrnl.exe").is_some());
+        assert!(module_pdb_signature(&modules, "ntdll").is_none());
+    }
+
+    #[test]
+    fn symbolize_without_pdb() {
+        let mut symbolizer = Symbolizer {
+            symbol_path: SymbolPath::parse(env::temp_dir().to_string_lossy().as_ref()).unwrap(),
+            modules: vec![],
+            pdbs: HashMap::new(),
+        };
+        let symbolized = symbolizer.symbolize("sys_monitor_no_such_module.dll+0x10");
+        assert_eq!(symbolized.offset.as_deref(), Some("0x10"));
+        assert!(symbolized.frames.is_empty());
+        assert!(symbolized
+            .error
+            .is_some_and(|e| e.starts_with("No pdb in the")));
+        assert!(symbolizer.symbolize("x").error.is_some());
+    }
+}
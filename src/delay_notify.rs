@@ -4,63 +4,172 @@ use slint::{ComponentHandle, Model, Weak};
 use smol::{Task, Timer};
 use std::time::Duration;
 
+// too many removed ranges is slower than a reset
+const MAX_REMOVED_RANGES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Notify {
     Push(/*index*/ usize, /*count*/ usize),
-    Remove,
+    Remove(/*index*/ usize, /*count*/ usize),
+    Reset,
+}
+
+/// The receiver of the merged notifications. The notifications must be applied in order.
+pub trait NotifySink: Send + Sync {
+    fn notify(&self, notifies: Vec<Notify>);
+}
+
+/// Notify the ListModel of App in the event loop
+pub struct AppNotifySink(pub Weak<App>);
+
+impl NotifySink for AppNotifySink {
+    fn notify(&self, notifies: Vec<Notify>) {
+        self.0
+            .upgrade_in_event_loop(move |app_handle| {
+                let row_data = app_handle.global::<EventsViewData>().get_row_data();
+                let rows = row_data.as_any().downcast_ref::<ListModel>().unwrap();
+                rows.notify_all(notifies);
+            })
+            .unwrap();
+    }
+}
+
+/// Merge the pushed and removed rows to the fewest notifications
+#[derive(Default)]
+pub struct NotifyMerger {
+    // the removed ranges are in the order of happening. the index is base on the rows after removing the previous ranges
+    removed_ranges: Vec<(/*index*/ usize, /*count*/ usize)>,
+    // the pushed rows are always at the end. so they are notified after the removed ranges
+    push_index: usize,
+    push_count: usize,
+    is_reset: bool,
 }
 
-pub struct DelayNotify {
+impl NotifyMerger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_count(&self) -> usize {
+        self.push_count
+    }
+
+    pub fn push(&mut self, index: usize, count: usize) {
+        if self.is_reset {
+            return;
+        }
+        // when no item for waiting notify
+        if self.push_count == 0 {
+            self.push_index = index;
+            self.push_count = count;
+        } else if index == self.push_index + self.push_count {
+            self.push_count += count;
+        } else {
+            self.is_reset = true;
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if self.is_reset {
+            return;
+        }
+        if self.push_count > 0 {
+            if index >= self.push_index + self.push_count {
+                self.is_reset = true;
+                return;
+            }
+            // the row is not notified yet
+            if index >= self.push_index {
+                self.push_count -= 1;
+                return;
+            }
+            self.push_index -= 1;
+        }
+        // merge with the last range when the removed rows are adjacent
+        if let Some(last) = self.removed_ranges.last_mut() {
+            if index == last.0 {
+                last.1 += 1;
+                return;
+            }
+            if index + 1 == last.0 {
+                last.0 = index;
+                last.1 += 1;
+                return;
+            }
+        }
+        if self.removed_ranges.len() >= MAX_REMOVED_RANGES {
+            self.is_reset = true;
+            return;
+        }
+        self.removed_ranges.push((index, 1));
+    }
+
+    // the reset will refresh all rows
+    pub fn reset(&mut self) {
+        self.is_reset = true;
+    }
+
+    pub fn take_notifies(&mut self) -> Vec<Notify> {
+        let notifies = if self.is_reset {
+            vec![Notify::Reset]
+        } else {
+            let mut notifies: Vec<Notify> = self
+                .removed_ranges
+                .iter()
+                .map(|(index, count)| Notify::Remove(*index, *count))
+                .collect();
+            if self.push_count > 0 {
+                notifies.push(Notify::Push(self.push_index, self.push_count));
+            }
+            notifies
+        };
+        *self = Self::default();
+        notifies
+    }
+}
+
+pub struct DelayNotify<S: NotifySink + 'static> {
     status: FairMutex<DelayNotifyStatus>,
+    sink: S,
     max_count: usize,
     interval_ms: u64,
     timer_task: Option<Task<()>>,
 }
 
 struct DelayNotifyStatus {
-    push_index: usize,
-    push_count: usize,
+    merger: NotifyMerger,
     is_notified: bool,
-    is_removed: bool,
 }
 
-impl DelayNotify {
-    pub fn new(max_count: usize, interval_ms: u64) -> Self {
+impl<S: NotifySink + 'static> DelayNotify<S> {
+    pub fn new(sink: S, max_count: usize, interval_ms: u64) -> Self {
         DelayNotify {
             status: FairMutex::new(DelayNotifyStatus {
-                push_index: 0,
-                push_count: 0,
+                merger: NotifyMerger::new(),
                 is_notified: false,
-                is_removed: false,
             }),
+            sink,
             max_count,
             timer_task: None,
             interval_ms,
         }
     }
 
-    pub fn init(&mut self, app_weak: Weak<App>) {
-        let self_context = unsafe { &*(self as *const DelayNotify) };
+    pub fn init(&mut self) {
+        let self_context = unsafe { &*(self as *const DelayNotify<S>) };
         self.timer_task = Some(smol::spawn(async move {
             let period = Duration::from_millis(self_context.interval_ms);
-            let app_weak = app_weak.clone();
             loop {
                 {
                     let mut lock = self_context.status.lock();
-                    if lock.is_removed {
-                        lock.push_index = 0;
-                        lock.push_count = 0;
+                    if !lock.is_notified {
+                        let notifies = lock.merger.take_notifies();
                         lock.is_notified = true;
-                        lock.is_removed = false;
-                        Self::notify_to_app(app_weak.clone(), Notify::Remove);
-                    } else {
-                        if !lock.is_notified {
-                            let notify = Notify::Push(lock.push_index, lock.push_count);
-                            lock.push_count = 0;
-                            lock.is_notified = true;
-                            Self::notify_to_app(app_weak.clone(), notify);
-                        } else {
-                            lock.is_notified = false;
+                        if !notifies.is_empty() {
+                            self_context.sink.notify(notifies);
                         }
+                    } else {
+                        lock.is_notified = false;
                     }
                 }
                 Timer::after(period).await;
@@ -68,47 +177,123 @@ impl DelayNotify {
         }));
     }
 
-    pub fn notify(&self, app_weak: Weak<App>, notify: Notify) {
+    pub fn notify(&self, notify: Notify) {
         let mut status = self.status.lock();
         // merge notify
         match notify {
             Notify::Push(index, count) => {
-                assert!(count == 1);
-                // when no item for waiting notify
-                if status.push_count == 0 {
-                    status.push_index = index;
-                    status.push_count = count;
-                } else {
-                    // consider when removed but not notified
-                    if index == status.push_index + status.push_count {
-                        status.push_count += count;
-                    } else {
-                        // wait remove notify
-                    }
-                }
-                if status.push_count >= self.max_count && !status.is_removed {
-                    let notify = Notify::Push(status.push_index, status.push_count);
-                    status.push_count = 0;
+                status.merger.push(index, count);
+                if status.merger.push_count() >= self.max_count {
+                    let notifies = status.merger.take_notifies();
                     status.is_notified = true;
-                    Self::notify_to_app(app_weak, notify);
+                    self.sink.notify(notifies);
+                }
+            }
+            Notify::Remove(index, count) => {
+                for _ in 0..count {
+                    status.merger.remove(index);
                 }
             }
-            Notify::Remove => {
-                status.is_removed = true;
+            Notify::Reset => {
+                status.merger.reset();
             }
         }
     }
 
-    fn notify_to_app(app_weak: Weak<App>, notify: Notify) {
-        app_weak
-            .upgrade_in_event_loop(move |app_handle| {
-                let row_data = app_handle.global::<EventsViewData>().get_row_data();
-                let rows = row_data.as_any().downcast_ref::<ListModel>().unwrap();
-                match notify {
-                    Notify::Push(index, count) => rows.notify_push(index, count),
-                    Notify::Remove => rows.notify_reset(),
-                }
-            })
-            .unwrap();
+    #[cfg(test)]
+    fn flush(&self) {
+        let notifies = self.status.lock().merger.take_notifies();
+        if !notifies.is_empty() {
+            self.sink.notify(notifies);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DelayNotify, Notify, NotifySink};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct VecSink(Arc<Mutex<Vec<Notify>>>);
+
+    impl NotifySink for VecSink {
+        fn notify(&self, notifies: Vec<Notify>) {
+            self.0.lock().extend(notifies);
+        }
+    }
+
+    #[test]
+    fn merge_push_and_remove() {
+        let sink = VecSink::default();
+        let delay_notify = DelayNotify::new(sink.clone(), 100, 200);
+        // 10 rows had been notified
+        for index in 10..13 {
+            delay_notify.notify(Notify::Push(index, 1));
+        }
+        delay_notify.notify(Notify::Remove(4, 1));
+        delay_notify.notify(Notify::Remove(4, 1));
+        delay_notify.notify(Notify::Remove(3, 1));
+        // the pushed row that is not notified
+        delay_notify.notify(Notify::Remove(8, 1));
+        delay_notify.notify(Notify::Push(9, 1));
+        delay_notify.flush();
+
+        assert_eq!(
+            *sink.0.lock(),
+            vec![Notify::Remove(3, 3), Notify::Push(7, 3)]
+        );
+    }
+
+    #[test]
+    fn merge_unfreeze_batch() {
+        let sink = VecSink::default();
+        let delay_notify = DelayNotify::new(sink.clone(), 100, 200);
+        // 10 rows had been notified before freezing
+        delay_notify.notify(Notify::Push(10, 1));
+        delay_notify.notify(Notify::Push(11, 1));
+        // the rows removed when frozen are in the descending order, then the rows pushed when frozen
+        for index in [9, 5, 4] {
+            delay_notify.notify(Notify::Remove(index, 1));
+        }
+        delay_notify.notify(Notify::Push(9, 3));
+        delay_notify.flush();
+
+        assert_eq!(
+            *sink.0.lock(),
+            vec![
+                Notify::Remove(9, 1),
+                Notify::Remove(4, 2),
+                Notify::Push(7, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_when_too_many_ranges() {
+        let sink = VecSink::default();
+        let delay_notify = DelayNotify::new(sink.clone(), 100, 200);
+        for index in 0..=super::MAX_REMOVED_RANGES {
+            delay_notify.notify(Notify::Remove(index * 2, 1));
+        }
+        delay_notify.notify(Notify::Push(1000, 1));
+        delay_notify.flush();
+
+        assert_eq!(*sink.0.lock(), vec![Notify::Reset]);
+    }
+
+    #[test]
+    fn notify_when_max_count() {
+        let sink = VecSink::default();
+        let delay_notify = DelayNotify::new(sink.clone(), 2, 200);
+        delay_notify.notify(Notify::Remove(0, 1));
+        delay_notify.notify(Notify::Push(5, 1));
+        delay_notify.notify(Notify::Push(6, 1));
+
+        assert_eq!(
+            *sink.0.lock(),
+            vec![Notify::Remove(0, 1), Notify::Push(5, 2)]
+        );
     }
 }
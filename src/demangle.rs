@@ -0,0 +1,63 @@
+//! Demangle the symbol names of MSVC (`?Foo@Bar@@QEAAXH@Z`) and Rust (legacy `_ZN..E` and v0 `_R..`)
+use msvc_demangler::DemangleFlags;
+
+/// None if the name is not mangled or failed to demangle
+pub fn demangle(name: &str) -> Option<String> {
+    if name.starts_with('?') {
+        return msvc_demangler::demangle(name, DemangleFlags::llvm()).ok();
+    }
+    // the alternate format omits the hash of legacy names
+    rustc_demangle::try_demangle(name)
+        .ok()
+        .map(|demangled| format!("{demangled:#}"))
+}
+
+/// Demangle the name of function location, the raw location if it is not mangled.
+/// i.e. `_ZN4core3fmt5write17h2b4b9f1e8c9d0a1bE+0x1a` -> `core::fmt::write+0x1a`
+pub fn demangle_location(location: &str) -> String {
+    // the mangled name has no `+` and space. i.e. the suffix `+0x1a`, ` [inline]`
+    let end = location.find(['+', ' ']).unwrap_or(location.len());
+    match demangle(&location[..end]) {
+        Some(name) => format!("{name}{}", &location[end..]),
+        None => location.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangle_names() {
+        assert_eq!(
+            demangle("?Foo@Bar@@QEAAXH@Z").as_deref(),
+            Some("public: void __cdecl Bar::Foo(int)")
+        );
+        assert_eq!(
+            demangle("_ZN4core3fmt5write17h2b4b9f1e8c9d0a1bE").as_deref(),
+            Some("core::fmt::write")
+        );
+        assert_eq!(
+            demangle("_RNvCs1234_7mycrate3foo").as_deref(),
+            Some("mycrate::foo")
+        );
+        assert_eq!(demangle("CreateFileW"), None);
+    }
+
+    #[test]
+    fn demangle_locations() {
+        assert_eq!(
+            demangle_location("?Foo@Bar@@QEAAXH@Z+0x1a"),
+            "public: void __cdecl Bar::Foo(int)+0x1a"
+        );
+        assert_eq!(
+            demangle_location("_ZN4core3fmt5write17h2b4b9f1e8c9d0a1bE [inline]"),
+            "core::fmt::write [inline]"
+        );
+        assert_eq!(
+            demangle_location("Bar::operator+=+0x4"),
+            "Bar::operator+=+0x4"
+        );
+        assert_eq!(demangle_location(""), "");
+    }
+}
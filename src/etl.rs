@@ -0,0 +1,691 @@
+//! Reader of the etl file without OpenTraceW. The file is a sequence of WMI buffers,
+//! and every buffer is a sequence of events with the kernel headers.
+//! It does not depend on Windows, the guids are u128 as `GUID::to_u128` and the timestamps are FILETIME.
+//! reference: https://geoffchappell.com/studies/windows/km/ntoskrnl/api/etw/tracesup/wmi_buffer_header.htm
+use crate::pe_bytes;
+use anyhow::{anyhow, Result};
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+    path::Path,
+};
+use tracing::debug;
+
+const BUFFER_HEADER_SIZE: usize = 72;
+const DEFAULT_ALIGNMENT: usize = 8;
+
+const TRACE_HEADER_FLAG: u8 = 0x80;
+
+const TRACE_HEADER_TYPE_SYSTEM32: u8 = 1;
+const TRACE_HEADER_TYPE_SYSTEM64: u8 = 2;
+const TRACE_HEADER_TYPE_COMPACT32: u8 = 3;
+const TRACE_HEADER_TYPE_COMPACT64: u8 = 4;
+const TRACE_HEADER_TYPE_FULL_HEADER32: u8 = 10;
+const TRACE_HEADER_TYPE_PERFINFO32: u8 = 16;
+const TRACE_HEADER_TYPE_PERFINFO64: u8 = 17;
+const TRACE_HEADER_TYPE_EVENT_HEADER32: u8 = 18;
+const TRACE_HEADER_TYPE_EVENT_HEADER64: u8 = 19;
+const TRACE_HEADER_TYPE_FULL_HEADER64: u8 = 20;
+
+const SYSTEM_HEADER_SIZE: usize = 32;
+const COMPACT_HEADER_SIZE: usize = 24;
+const PERFINFO_HEADER_SIZE: usize = 16;
+const FULL_HEADER_SIZE: usize = 48;
+const EVENT_HEADER_SIZE: usize = 80;
+
+// the flags of EVENT_HEADER
+pub const EVENT_HEADER_FLAG_32_BIT_HEADER: u16 = 0x0020;
+pub const EVENT_HEADER_FLAG_64_BIT_HEADER: u16 = 0x0040;
+pub const EVENT_HEADER_FLAG_CLASSIC_HEADER: u16 = 0x0100;
+
+// the clock type in ReservedFlags of TRACE_LOGFILE_HEADER
+const CLOCK_TYPE_QPC: u32 = 1;
+const CLOCK_TYPE_SYSTEM_TIME: u32 = 2;
+const CLOCK_TYPE_CPU_CYCLE: u32 = 3;
+
+pub const EVENT_TRACE_GUID: u128 = 0x68fdd900_4a3e_11d1_84f4_0000f80464e3;
+pub const STACK_WALK_GUID: u128 = 0xdef2fe46_7bd6_4b80_bd94_f57fe20d0ce3;
+
+/// The group of HookId in the system headers to the guid of kernel events. the guids are of the Etw module of
+/// windows and `event_kernel`
+/// reference: https://geoffchappell.com/studies/windows/km/ntoskrnl/api/etw/callouts/hookid.htm
+pub const GROUP_GUID_MAP: &[(u8, u128)] = &[
+    (0x00, EVENT_TRACE_GUID),
+    (0x01, 0x3d6fa8d4_fe05_11d0_9dda_00c04fd7ba7c), // DiskIo
+    (0x02, 0x3d6fa8d3_fe05_11d0_9dda_00c04fd7ba7c), // PageFault
+    (0x03, 0x3d6fa8d0_fe05_11d0_9dda_00c04fd7ba7c), // Process
+    (0x04, 0x90cbdc39_4a3e_11d1_84f4_0000f80464e3), // FileIo
+    (0x05, 0x3d6fa8d1_fe05_11d0_9dda_00c04fd7ba7c), // Thread
+    (0x06, 0x9a280ac0_c8e0_11d1_84e2_00c04fb998a2), // TcpIp
+    (0x07, 0x3282fc76_feed_498e_8aa7_e70f459d430e), // Job
+    (0x08, 0xbf3a50c5_a9c9_4988_a005_2df0b7c80f80), // UdpIp
+    (0x09, 0xae53722e_c863_11d2_8659_00c04fa321a1), // Registry
+    (0x0a, 0x13976d09_a327_438c_950b_7f03192815c7), // DbgPrint
+    (0x0e, 0x0268a8b6_74fd_4302_9dd0_6e8f1795c0cf), // Pool
+    (0x0f, 0xce1dbfb4_137e_4da6_87b0_3f59aa102cbc), // PerfInfo
+    (0x10, 0x222962ab_6180_4b88_a825_346b75f2a24a), // Heap
+    (0x11, 0x89497f50_effe_4440_8cf2_ce6b1cdcaca7), // Object
+    (0x12, 0xe43445e0_0903_48c3_b878_ff0fccebdd04), // Power
+    (0x14, 0x2cb15d1d_5fc1_11d2_abe1_00a0c911f518), // ImageLoad
+    (0x16, 0x7687a439_f752_45b8_b741_321aec0f8df9), // Cc
+    (0x18, STACK_WALK_GUID),
+    (0x1a, 0x45d8cccd_539f_4b72_a8b7_5c683142609a), // ALPC
+    (0x1b, 0xd837ca92_12b9_44a5_ad6a_3a65b3578aa8), // SplitIo
+];
+
+pub fn guid_from_group(group: u8) -> Option<u128> {
+    GROUP_GUID_MAP
+        .iter()
+        .find(|(g, _)| *g == group)
+        .map(|(_, guid)| *guid)
+}
+
+/// The fields of TRACE_LOGFILE_HEADER that are needed to convert the timestamps
+#[derive(Debug, Clone)]
+pub struct LogfileHeader {
+    pub number_of_processors: u32,
+    pub pointer_size: u32,
+    pub events_lost: u32,
+    pub cpu_speed_mhz: u32,
+    // FILETIME
+    pub boot_time: i64,
+    pub perf_freq: i64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub clock_type: u32,
+}
+
+impl LogfileHeader {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let pointer_size = read_u32(data, 0x2c)?;
+        // the offsets after the LoggerName and LogFileName pointers depend on the pointer size
+        let offset = if pointer_size == 4 { 0xf0 } else { 0xf8 };
+        Ok(Self {
+            number_of_processors: read_u32(data, 0x0c)?,
+            pointer_size,
+            events_lost: read_u32(data, 0x30)?,
+            cpu_speed_mhz: read_u32(data, 0x34)?,
+            end_time: read_i64(data, 0x10)?,
+            boot_time: read_i64(data, offset)?,
+            perf_freq: read_i64(data, offset + 8)?,
+            start_time: read_i64(data, offset + 16)?,
+            clock_type: read_u32(data, offset + 24)?,
+        })
+    }
+
+    /// Convert the raw timestamp of event header to the FILETIME
+    pub fn timestamp(&self, raw: i64) -> i64 {
+        match self.clock_type {
+            CLOCK_TYPE_SYSTEM_TIME => raw,
+            CLOCK_TYPE_CPU_CYCLE if self.cpu_speed_mhz != 0 => {
+                self.counter_to_filetime(raw, self.cpu_speed_mhz as i64 * 1_000_000)
+            }
+            CLOCK_TYPE_QPC if self.perf_freq != 0 => self.counter_to_filetime(raw, self.perf_freq),
+            _ => raw,
+        }
+    }
+
+    // the same as `TimeStamp::from_qpc` of the monitor
+    fn counter_to_filetime(&self, count: i64, frequency: i64) -> i64 {
+        let duration = 10000000.0 / frequency as f64 * count as u64 as f64;
+        self.boot_time + duration as i64
+    }
+}
+
+/// The EVENT_DESCRIPTOR of the event header
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EventDescriptor {
+    pub id: u16,
+    pub version: u8,
+    pub channel: u8,
+    pub level: u8,
+    pub opcode: u8,
+    pub task: u16,
+    pub keyword: u64,
+}
+
+/// The StackWalk event. the event_timestamp is raw
+#[derive(Debug, Clone, PartialEq)]
+pub struct EtlStackWalk {
+    pub event_timestamp: i64,
+    pub stack_process: u32,
+    pub stack_thread: u32,
+    pub stacks: Vec<u64>,
+}
+
+/// A event read from the etl file. The classic kernel event has no descriptor but the opcode and version
+#[derive(Debug, Clone)]
+pub struct EtlEvent {
+    pub header_type: u8,
+    pub provider_id: u128,
+    pub descriptor: EventDescriptor,
+    // the flags of EVENT_HEADER. only for the EVENT_HEADER32/64
+    pub flags: u16,
+    pub event_property: u16,
+    pub thread_id: u32,
+    pub process_id: u32,
+    // the raw timestamp. converted by LogfileHeader::timestamp
+    pub timestamp: i64,
+    pub kernel_time: u32,
+    pub user_time: u32,
+    // only for the EVENT_HEADER32/64
+    pub activity_id: u128,
+    pub processor_number: u8,
+    pub pointer_size: u32,
+    pub user_data: Vec<u8>,
+}
+
+impl EtlEvent {
+    /// True if the event has a classic header, and the provider id is the guid of event class
+    pub fn is_classic(&self) -> bool {
+        !matches!(
+            self.header_type,
+            TRACE_HEADER_TYPE_EVENT_HEADER32 | TRACE_HEADER_TYPE_EVENT_HEADER64
+        )
+    }
+
+    /// The flags of EVENT_HEADER as the event is coming from ProcessTrace
+    pub fn header_flags(&self) -> u16 {
+        if !self.is_classic() {
+            return self.flags;
+        }
+        let pointer_flag = if self.pointer_size == 4 {
+            EVENT_HEADER_FLAG_32_BIT_HEADER
+        } else {
+            EVENT_HEADER_FLAG_64_BIT_HEADER
+        };
+        EVENT_HEADER_FLAG_CLASSIC_HEADER | pointer_flag
+    }
+
+    /// Parse the StackWalk event: EventTimeStamp, StackProcess, StackThread, Stack1..StackN.
+    /// The event_timestamp is raw.
+    pub fn stack_walk(&self) -> Result<EtlStackWalk> {
+        let event_timestamp = read_i64(&self.user_data, 0)?;
+        let stack_process = read_u32(&self.user_data, 8)?;
+        let stack_thread = read_u32(&self.user_data, 12)?;
+        let pointer_size = self.pointer_size as usize;
+        let stacks = self.user_data[16..]
+            .chunks_exact(pointer_size)
+            .map(|chunk| {
+                if pointer_size == 4 {
+                    read_u32(chunk, 0).unwrap_or_default() as u64
+                } else {
+                    read_u64(chunk, 0).unwrap_or_default()
+                }
+            })
+            .collect();
+        Ok(EtlStackWalk {
+            event_timestamp,
+            stack_process,
+            stack_thread,
+            stacks,
+        })
+    }
+}
+
+pub struct EtlReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    // the offset of next event in buffer
+    offset: usize,
+    // the end of events in buffer
+    end: usize,
+    alignment: usize,
+    processor_number: u8,
+    logfile_header: Option<LogfileHeader>,
+    buffer_count: usize,
+}
+
+impl EtlReader<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: Read> EtlReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            offset: 0,
+            end: 0,
+            alignment: DEFAULT_ALIGNMENT,
+            processor_number: 0,
+            logfile_header: None,
+            buffer_count: 0,
+        }
+    }
+
+    /// Some after the header event of the first buffer has been read
+    pub fn logfile_header(&self) -> Option<&LogfileHeader> {
+        self.logfile_header.as_ref()
+    }
+
+    pub fn buffer_count(&self) -> usize {
+        self.buffer_count
+    }
+
+    /// Returns None at the end of file
+    pub fn next_event(&mut self) -> Result<Option<EtlEvent>> {
+        loop {
+            if self.offset >= self.end {
+                if !self.read_buffer()? {
+                    return Ok(None);
+                }
+                continue;
+            }
+            match self.parse_event()? {
+                Some(event) => {
+                    if event.provider_id == EVENT_TRACE_GUID
+                        && event.descriptor.opcode == 0
+                        && self.logfile_header.is_none()
+                    {
+                        self.logfile_header = Some(LogfileHeader::parse(&event.user_data)?);
+                    }
+                    return Ok(Some(event));
+                }
+                None => continue,
+            }
+        }
+    }
+
+    // returns false at the end of file
+    fn read_buffer(&mut self) -> Result<bool> {
+        let mut header = [0u8; BUFFER_HEADER_SIZE];
+        match self.reader.read_exact(&mut header) {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        let buffer_size = read_u32(&header, 0x00)? as usize;
+        if buffer_size < BUFFER_HEADER_SIZE {
+            return Err(anyhow!(
+                "Invalid buffer size: {buffer_size} of the buffer: {}",
+                self.buffer_count
+            ));
+        }
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&header);
+        self.buffer.resize(buffer_size, 0);
+        self.reader
+            .read_exact(&mut self.buffer[BUFFER_HEADER_SIZE..])
+            .map_err(|e| anyhow!("Failed to read the buffer: {}: {e}", self.buffer_count))?;
+
+        let saved_offset = read_u32(&header, 0x04)? as usize;
+        let offset = read_u32(&header, 0x30)? as usize;
+        let is_valid = |end: usize| end > BUFFER_HEADER_SIZE && end <= buffer_size;
+        self.end = if is_valid(saved_offset) {
+            saved_offset
+        } else if is_valid(offset) {
+            offset
+        } else {
+            buffer_size
+        };
+        self.offset = BUFFER_HEADER_SIZE;
+        self.processor_number = header[0x28];
+        self.alignment = match header[0x29] as usize {
+            0 => DEFAULT_ALIGNMENT,
+            alignment => alignment,
+        };
+        self.buffer_count += 1;
+        Ok(true)
+    }
+
+    // returns None for the skipped event
+    fn parse_event(&mut self) -> Result<Option<EtlEvent>> {
+        let data = &self.buffer[self.offset..self.end];
+        if data.len() < 4 || read_u32(data, 0)? == u32::MAX || data[3] & TRACE_HEADER_FLAG == 0 {
+            // the rest of buffer is padding
+            self.offset = self.end;
+            return Ok(None);
+        }
+        let header_type = data[2];
+        let size = match header_type {
+            TRACE_HEADER_TYPE_SYSTEM32
+            | TRACE_HEADER_TYPE_SYSTEM64
+            | TRACE_HEADER_TYPE_COMPACT32
+            | TRACE_HEADER_TYPE_COMPACT64
+            | TRACE_HEADER_TYPE_PERFINFO32
+            | TRACE_HEADER_TYPE_PERFINFO64 => read_u16(data, 4)? as usize,
+            _ => read_u16(data, 0)? as usize,
+        };
+        if size == 0 || size > data.len() {
+            debug!(
+                "Invalid event size: {size} at: {} of the buffer: {}",
+                self.offset, self.buffer_count
+            );
+            self.offset = self.end;
+            return Ok(None);
+        }
+        let data = &data[..size];
+        self.offset += size.next_multiple_of(self.alignment);
+
+        let pointer_size = match header_type {
+            TRACE_HEADER_TYPE_SYSTEM32
+            | TRACE_HEADER_TYPE_COMPACT32
+            | TRACE_HEADER_TYPE_PERFINFO32
+            | TRACE_HEADER_TYPE_FULL_HEADER32
+            | TRACE_HEADER_TYPE_EVENT_HEADER32 => 4u32,
+            _ => 8u32,
+        };
+        let mut event = EtlEvent {
+            header_type,
+            provider_id: 0,
+            descriptor: EventDescriptor::default(),
+            flags: 0,
+            event_property: 0,
+            thread_id: u32::MAX,
+            process_id: u32::MAX,
+            timestamp: 0,
+            kernel_time: 0,
+            user_time: 0,
+            activity_id: 0,
+            processor_number: self.processor_number,
+            pointer_size,
+            user_data: vec![],
+        };
+        let header_size = match header_type {
+            TRACE_HEADER_TYPE_SYSTEM32
+            | TRACE_HEADER_TYPE_SYSTEM64
+            | TRACE_HEADER_TYPE_COMPACT32
+            | TRACE_HEADER_TYPE_COMPACT64
+            | TRACE_HEADER_TYPE_PERFINFO32
+            | TRACE_HEADER_TYPE_PERFINFO64 => {
+                let group = data[7];
+                let Some(guid) = guid_from_group(group) else {
+                    debug!("Unknown group: {group:#x} of the system header");
+                    return Ok(None);
+                };
+                event.provider_id = guid;
+                event.descriptor.version = data[0];
+                event.descriptor.opcode = data[6];
+                match header_type {
+                    TRACE_HEADER_TYPE_PERFINFO32 | TRACE_HEADER_TYPE_PERFINFO64 => {
+                        event.timestamp = read_i64(data, 8)?;
+                        PERFINFO_HEADER_SIZE
+                    }
+                    _ => {
+                        event.thread_id = read_u32(data, 8)?;
+                        event.process_id = read_u32(data, 12)?;
+                        event.timestamp = read_i64(data, 16)?;
+                        if header_type == TRACE_HEADER_TYPE_COMPACT32
+                            || header_type == TRACE_HEADER_TYPE_COMPACT64
+                        {
+                            COMPACT_HEADER_SIZE
+                        } else {
+                            event.kernel_time = read_u32(data, 24)?;
+                            event.user_time = read_u32(data, 28)?;
+                            SYSTEM_HEADER_SIZE
+                        }
+                    }
+                }
+            }
+            TRACE_HEADER_TYPE_FULL_HEADER32 | TRACE_HEADER_TYPE_FULL_HEADER64 => {
+                event.descriptor.opcode = data[4];
+                event.descriptor.level = data[5];
+                event.descriptor.version = read_u16(data, 6)? as u8;
+                event.thread_id = read_u32(data, 8)?;
+                event.process_id = read_u32(data, 12)?;
+                event.timestamp = read_i64(data, 16)?;
+                event.provider_id = read_guid(data, 24)?;
+                event.kernel_time = read_u32(data, 40)?;
+                event.user_time = read_u32(data, 44)?;
+                FULL_HEADER_SIZE
+            }
+            TRACE_HEADER_TYPE_EVENT_HEADER32 | TRACE_HEADER_TYPE_EVENT_HEADER64 => {
+                event.flags = read_u16(data, 4)?;
+                event.event_property = read_u16(data, 6)?;
+                event.thread_id = read_u32(data, 8)?;
+                event.process_id = read_u32(data, 12)?;
+                event.timestamp = read_i64(data, 16)?;
+                event.provider_id = read_guid(data, 24)?;
+                event.descriptor = EventDescriptor {
+                    id: read_u16(data, 40)?,
+                    version: data[42],
+                    channel: data[43],
+                    level: data[44],
+                    opcode: data[45],
+                    task: read_u16(data, 46)?,
+                    keyword: read_u64(data, 48)?,
+                };
+                event.kernel_time = read_u32(data, 56)?;
+                event.user_time = read_u32(data, 60)?;
+                event.activity_id = read_guid(data, 64)?;
+                EVENT_HEADER_SIZE
+            }
+            _ => {
+                debug!("Unsupported header type: {header_type}");
+                return Ok(None);
+            }
+        };
+        if header_size > data.len() {
+            return Err(anyhow!(
+                "Too small event size: {} for the header type: {header_type}",
+                data.len()
+            ));
+        }
+        event.user_data = data[header_size..].to_vec();
+        Ok(Some(event))
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    pe_bytes::read_bytes(data, offset)
+        .ok_or_else(|| anyhow!("Out of range: {offset}+{N} > {}", data.len()))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    Ok(u16::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_i64(data: &[u8], offset: usize) -> Result<i64> {
+    Ok(i64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+// the u128 as `GUID::to_u128`. data1, data2 and data3 are little endian, data4 is the bytes
+fn read_guid(data: &[u8], offset: usize) -> Result<u128> {
+    Ok((read_u32(data, offset)? as u128) << 96
+        | (read_u16(data, offset + 4)? as u128) << 80
+        | (read_u16(data, offset + 6)? as u128) << 64
+        | u64::from_be_bytes(read_bytes(data, offset + 8)?) as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const BUFFER_SIZE: usize = 1024;
+
+    fn buffer_new(events: &[Vec<u8>]) -> Vec<u8> {
+        let mut buffer = vec![0u8; BUFFER_HEADER_SIZE];
+        buffer[0x28] = 1; // processor number
+        buffer[0x29] = 8; // alignment
+        for event in events {
+            buffer.extend_from_slice(event);
+            buffer.resize(buffer.len().next_multiple_of(8), 0);
+        }
+        let saved_offset = buffer.len() as u32;
+        buffer[0x04..0x08].copy_from_slice(&saved_offset.to_le_bytes());
+        buffer[0x30..0x34].copy_from_slice(&saved_offset.to_le_bytes());
+        buffer.resize(BUFFER_SIZE, 0xff);
+        buffer[0x00..0x04].copy_from_slice(&(BUFFER_SIZE as u32).to_le_bytes());
+        buffer
+    }
+
+    fn system_event_new(
+        group: u8,
+        opcode: u8,
+        version: u8,
+        timestamp: i64,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut event = vec![version, 0, TRACE_HEADER_TYPE_SYSTEM64, 0xc0];
+        event.extend_from_slice(&((SYSTEM_HEADER_SIZE + data.len()) as u16).to_le_bytes());
+        event.extend_from_slice(&[opcode, group]);
+        event.extend_from_slice(&16u32.to_le_bytes()); // thread id
+        event.extend_from_slice(&4u32.to_le_bytes()); // process id
+        event.extend_from_slice(&timestamp.to_le_bytes());
+        event.extend_from_slice(&[0u8; 8]);
+        event.extend_from_slice(data);
+        event
+    }
+
+    fn logfile_header_new() -> Vec<u8> {
+        let mut data = vec![0u8; 0x118];
+        data[0x0c..0x10].copy_from_slice(&8u32.to_le_bytes());
+        data[0x2c..0x30].copy_from_slice(&8u32.to_le_bytes());
+        data[0x34..0x38].copy_from_slice(&3000u32.to_le_bytes());
+        data[0xf8..0x100].copy_from_slice(&133627865450000000i64.to_le_bytes());
+        data[0x100..0x108].copy_from_slice(&10_000_000i64.to_le_bytes());
+        data[0x110..0x114].copy_from_slice(&CLOCK_TYPE_QPC.to_le_bytes());
+        data
+    }
+
+    fn stack_walk_data_new() -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&1234i64.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&0xfffff80000001000u64.to_le_bytes());
+        data.extend_from_slice(&0x00007ff800002000u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn read_events() {
+        let file = [
+            buffer_new(&[
+                system_event_new(0x00, 0, 2, 0, &logfile_header_new()),
+                system_event_new(0x05, 1, 3, 1234, &[0x10, 0, 0, 0]),
+            ]),
+            buffer_new(&[system_event_new(0x18, 32, 2, 1235, &stack_walk_data_new())]),
+        ]
+        .concat();
+        let mut reader = EtlReader::new(Cursor::new(file));
+
+        let header_event = reader.next_event().unwrap().unwrap();
+        assert_eq!(header_event.provider_id, EVENT_TRACE_GUID);
+        let logfile_header = reader.logfile_header().unwrap().clone();
+        assert_eq!(logfile_header.number_of_processors, 8);
+        assert_eq!(logfile_header.pointer_size, 8);
+        assert_eq!(logfile_header.timestamp(1234), 133627865450000000 + 1234);
+
+        let thread_event = reader.next_event().unwrap().unwrap();
+        assert_eq!(thread_event.provider_id, guid_from_group(0x05).unwrap());
+        assert_eq!(thread_event.descriptor.opcode, 1);
+        assert_eq!(thread_event.descriptor.version, 3);
+        assert_eq!(thread_event.thread_id, 16);
+        assert_eq!(thread_event.process_id, 4);
+        assert_eq!(thread_event.timestamp, 1234);
+        assert_eq!(thread_event.processor_number, 1);
+        assert_eq!(thread_event.user_data, vec![0x10, 0, 0, 0]);
+        assert!(thread_event.is_classic());
+        assert_eq!(
+            thread_event.header_flags(),
+            EVENT_HEADER_FLAG_CLASSIC_HEADER | EVENT_HEADER_FLAG_64_BIT_HEADER
+        );
+
+        let stack_event = reader.next_event().unwrap().unwrap();
+        assert_eq!(stack_event.provider_id, STACK_WALK_GUID);
+        let sw = stack_event.stack_walk().unwrap();
+        assert_eq!(sw.event_timestamp, 1234);
+        assert_eq!(sw.stack_process, 4);
+        assert_eq!(sw.stack_thread, 16);
+        assert_eq!(sw.stacks, vec![0xfffff80000001000, 0x00007ff800002000]);
+
+        assert!(reader.next_event().unwrap().is_none());
+        assert_eq!(reader.buffer_count(), 2);
+    }
+
+    #[test]
+    fn invalid_buffer() {
+        let mut buffer = buffer_new(&[]);
+        buffer[0x00..0x04].copy_from_slice(&16u32.to_le_bytes());
+        let mut reader = EtlReader::new(Cursor::new(buffer));
+        assert!(reader.next_event().is_err());
+
+        // truncated buffer
+        let buffer = buffer_new(&[system_event_new(0x05, 1, 3, 1234, &[])]);
+        let mut reader = EtlReader::new(Cursor::new(&buffer[..BUFFER_SIZE / 2]));
+        assert!(reader.next_event().is_err());
+    }
+
+    #[test]
+    fn read_fixture() {
+        // two buffers of 4096 bytes of a 64 bit kernel logger with the QPC clock
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/kernel.etl");
+        let mut reader = EtlReader::open(&path).unwrap();
+        let mut events = vec![];
+        while let Some(event) = reader.next_event().unwrap() {
+            events.push(event);
+        }
+        assert_eq!(reader.buffer_count(), 2);
+        let logfile_header = reader.logfile_header().unwrap();
+        assert_eq!(logfile_header.number_of_processors, 2);
+        assert_eq!(logfile_header.perf_freq, 10_000_000);
+        assert_eq!(
+            logfile_header.start_time,
+            logfile_header.boot_time + 10_000_000
+        );
+
+        let kinds: Vec<(u8, u128, u8)> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.header_type,
+                    event.provider_id,
+                    event.descriptor.opcode,
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (TRACE_HEADER_TYPE_SYSTEM64, EVENT_TRACE_GUID, 0),
+                (
+                    TRACE_HEADER_TYPE_SYSTEM64,
+                    guid_from_group(0x05).unwrap(),
+                    3
+                ),
+                (
+                    TRACE_HEADER_TYPE_EVENT_HEADER64,
+                    0xedd08927_9cc4_4e65_b970_c2560fb5c289,
+                    0
+                ),
+                (TRACE_HEADER_TYPE_SYSTEM64, STACK_WALK_GUID, 32),
+                (
+                    TRACE_HEADER_TYPE_PERFINFO64,
+                    guid_from_group(0x0f).unwrap(),
+                    46
+                ),
+            ]
+        );
+        // the qpc of 1 second after boot
+        assert_eq!(
+            logfile_header.timestamp(events[1].timestamp),
+            logfile_header.boot_time + 10_000_000
+        );
+        let file_event = &events[2];
+        assert!(!file_event.is_classic());
+        assert_eq!(file_event.descriptor.id, 12);
+        assert_eq!(file_event.descriptor.keyword, 0x8000000000000010);
+        assert_eq!(file_event.processor_number, 1);
+        assert_eq!(file_event.user_data.len(), 24 + 18);
+
+        let sw = events[3].stack_walk().unwrap();
+        assert_eq!(sw.stack_thread, 0x1a2c);
+        assert_eq!(sw.stacks, vec![0xfffff80312345678, 0x00007ffb01231234]);
+        assert_eq!(events[4].thread_id, u32::MAX);
+    }
+}
@@ -6,7 +6,9 @@ use once_cell::sync::OnceCell;
 use parking_lot::FairMutex;
 use std::{
     cell::SyncUnsafeCell,
-    ptr,
+    cmp::Reverse,
+    collections::HashMap,
+    mem, ptr,
     sync::{
         atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
@@ -63,6 +65,69 @@ impl<T: Clone + Send + Sync> NodeArc<T> {
         self.index = if node.is_some() { index } else { 0 };
         self.node = node;
     }
+
+    // cursor_next: the cursor after the removed node
+    fn on_removed(&mut self, node_removed: &Node<T>, cursor_next: Option<Arc<Node<T>>>) {
+        if let Some(cursor_last_read) = &self.node {
+            if ptr::eq(node_removed, cursor_last_read.as_ref()) {
+                let index = self.index;
+                self.set(cursor_next, index);
+            } else {
+                if node_removed.serial_number.get().unwrap()
+                    <= cursor_last_read.serial_number.get().unwrap()
+                {
+                    if self.index != 0 {
+                        self.index -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the cursors cached for get_by_index and traversal_from
+struct NodeArcs<T: Clone + Send + Sync> {
+    read: NodeArc<T>,
+    find: NodeArc<T>,
+}
+
+impl<T: Clone + Send + Sync> NodeArcs<T> {
+    fn new() -> Self {
+        Self {
+            read: NodeArc::new(),
+            find: NodeArc::new(),
+        }
+    }
+}
+
+// the rows changed when the list is frozen
+struct FrozenBuffer<T: Clone + Send + Sync> {
+    pushed: Vec<Arc<Node<T>>>,
+    removed: Vec<Arc<Node<T>>>,
+    // the pushed rows that are removed before merging, by the address of node. they are skipped when merging
+    dropped: HashMap<usize, Arc<Node<T>>>,
+}
+
+impl<T: Clone + Send + Sync> FrozenBuffer<T> {
+    fn new() -> Self {
+        Self {
+            pushed: Vec::new(),
+            removed: Vec::new(),
+            dropped: HashMap::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pushed.is_empty() && self.removed.is_empty() && self.dropped.is_empty()
+    }
+}
+
+pub enum Removed {
+    Index(usize),
+    // the index is not found in the max_distance
+    NoIndex,
+    // the list is frozen. the row is removed when unfreezing
+    Deferred,
 }
 
 pub struct EventList<T: Clone + Send + Sync> {
@@ -70,8 +135,10 @@ pub struct EventList<T: Clone + Send + Sync> {
     list: Box<SyncUnsafeCell<LinkedList<NodeAdapter<T>>>>,
     list_len: AtomicUsize,
     serial_number: AtomicU64, //todo: integer overflow
-    list_except_last_lock: FairMutex<NodeArc<T>>,
+    list_except_last_lock: FairMutex<NodeArcs<T>>,
     list_last_lock: FairMutex<()>,
+    frozen: FairMutex<Option<FrozenBuffer<T>>>, // always lock before list_except_last_lock and list_last_lock
+    merge_lock: FairMutex<()>, // always lock before frozen. unfreeze and clear are not run together
 }
 
 // when modifying the model, we call the corresponding function in
@@ -81,14 +148,18 @@ impl<T: Clone + Send + Sync> EventList<T> {
         let list = Box::new(SyncUnsafeCell::new(LinkedList::<NodeAdapter<T>>::default()));
         let list_len = AtomicUsize::new(0);
         let serial_number = AtomicU64::new(0);
-        let list_except_last_lock = FairMutex::new(NodeArc::new());
+        let list_except_last_lock = FairMutex::new(NodeArcs::new());
         let list_last_lock = FairMutex::new(());
+        let frozen = FairMutex::new(None);
+        let merge_lock = FairMutex::new(());
         Self {
             list,
             list_len,
             serial_number,
             list_except_last_lock,
             list_last_lock,
+            frozen,
+            merge_lock,
         }
     }
 
@@ -98,25 +169,33 @@ impl<T: Clone + Send + Sync> EventList<T> {
 
     pub fn get_by_index(&self, index_to: usize) -> Option<Arc<Node<T>>> {
         let mut list_except_last_lock = self.list_except_last_lock.lock();
+        self.get_by_index_with_cache(&mut list_except_last_lock.read, index_to)
+    }
+
+    // the cache is used to find nearby index quickly. must be called with list_except_last_lock
+    fn get_by_index_with_cache(
+        &self,
+        cache: &mut NodeArc<T>,
+        index_to: usize,
+    ) -> Option<Arc<Node<T>>> {
         let mut list_len = self.list_len.load(Ordering::Acquire);
         if index_to >= list_len {
             return None;
         }
 
         // i.e. the cursor is null at start
-        let (mut cursor, cursor_index) =
-            if let Some(cursor) = list_except_last_lock.get_cursor(self.get_list()) {
-                (cursor, list_except_last_lock.index)
-            } else {
-                let cursor = self.get_list().front();
-                if cursor.is_null() {
-                    return None;
-                }
-                (cursor, 0usize)
-            };
+        let (mut cursor, cursor_index) = if let Some(cursor) = cache.get_cursor(self.get_list()) {
+            (cursor, cache.index)
+        } else {
+            let cursor = self.get_list().front();
+            if cursor.is_null() {
+                return None;
+            }
+            (cursor, 0usize)
+        };
 
         if cursor_index == index_to {
-            list_except_last_lock.set(cursor.clone_pointer(), index_to);
+            cache.set(cursor.clone_pointer(), index_to);
             return cursor.clone_pointer();
         } else {
             if cursor_index < index_to {
@@ -139,7 +218,7 @@ impl<T: Clone + Send + Sync> EventList<T> {
                     move_next_to_uncheck(&mut cursor, 0, index_to);
                 }
             }
-            list_except_last_lock.set(cursor.clone_pointer(), index_to);
+            cache.set(cursor.clone_pointer(), index_to);
             return cursor.clone_pointer();
         }
 
@@ -172,24 +251,160 @@ impl<T: Clone + Send + Sync> EventList<T> {
         }
     }
 
-    pub fn traversal(&self, cb: impl Fn(&T) -> Result<bool>) -> Result<Vec<i32>> {
-        let mut _list_except_last_lock = self.list_except_last_lock.lock();
+    /// Traverse at most `max_count` items begin at `index_from`(included) toward the direction.
+    /// The lock is only held during the call, so a long traversal can be split to many calls without blocking the reader.
+    /// Returns the index to continue with. None when reach the end or the `cb` returns true.
+    pub fn traversal_from(
+        &self,
+        index_from: usize,
+        is_forward: bool,
+        max_count: usize,
+        mut cb: impl FnMut(/*index*/ usize, &T) -> Result</*is_stop*/ bool>,
+    ) -> Result<Option<usize>> {
+        let mut list_except_last_lock = self.list_except_last_lock.lock();
         let list_len = self.list_len.load(Ordering::Acquire);
-        let list = unsafe { &*self.list.get() };
-        let mut vec = vec![];
-        for (index, item) in list.iter().enumerate() {
-            let is_find = cb(&item.value)?;
-            if is_find {
-                vec.push(index as i32);
+        if index_from >= list_len {
+            return Ok(None);
+        }
+        let node = match self.get_by_index_with_cache(&mut list_except_last_lock.find, index_from) {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        let mut cursor = unsafe { self.get_list().cursor_from_ptr(node.as_ref()) };
+        let mut index = index_from;
+        for _ in 0..max_count {
+            let item = match cursor.get() {
+                Some(item) => item,
+                None => return Ok(None),
+            };
+            if cb(index, &item.value)? {
+                list_except_last_lock
+                    .find
+                    .set(cursor.clone_pointer(), index);
+                return Ok(None);
+            }
+            if is_forward {
+                // the item after list_len may be pushing
+                if index + 1 >= list_len {
+                    return Ok(None);
+                }
+                cursor.move_next();
+                index += 1;
+            } else {
+                if index == 0 {
+                    return Ok(None);
+                }
+                cursor.move_prev();
+                index -= 1;
+            }
+        }
+        list_except_last_lock
+            .find
+            .set(cursor.clone_pointer(), index);
+        Ok(Some(index))
+    }
+
+    /// Returns the index of the pushed row. None when the list is frozen
+    pub fn push(&self, value: Arc<Node<T>>) -> Option<usize> {
+        let mut frozen = self.frozen.lock();
+        if let Some(frozen_buffer) = frozen.as_mut() {
+            frozen_buffer.pushed.push(value);
+            return None;
+        }
+        Some(self.push_unfrozen(value))
+    }
+
+    /// Remove the row by a arc
+    /// The index of the removed row is found when it is in `max_distance` from the cached cursor or the back.
+    pub fn remove(&self, node_arc: Arc<Node<T>>, max_distance: usize) -> Removed {
+        let mut frozen = self.frozen.lock();
+        if let Some(frozen_buffer) = frozen.as_mut() {
+            // no serial number when it is not pushed to list
+            if node_arc.serial_number.get().is_none() {
+                frozen_buffer
+                    .dropped
+                    .insert(Arc::as_ptr(&node_arc) as usize, node_arc);
+            } else {
+                frozen_buffer.removed.push(node_arc);
             }
-            if index as usize >= list_len {
-                break;
+            return Removed::Deferred;
+        }
+        match self.remove_unfrozen(node_arc, max_distance) {
+            Some(index) => Removed::Index(index),
+            None => Removed::NoIndex,
+        }
+    }
+
+    /// Keep the rows unchanged until unfreezing. The pushed and removed rows are buffered.
+    pub fn freeze(&self) {
+        let mut frozen = self.frozen.lock();
+        if frozen.is_none() {
+            *frozen = Some(FrozenBuffer::new());
+        }
+    }
+
+    /// Apply the buffered rows. `cb_removed` is called with the index of every removed row in order, then `cb_pushed`
+    /// with the index and count of the pushed rows. They are called before any row is pushed again, so the
+    /// notifications are in order with the pushing.
+    /// The buffer is merged without the frozen lock, the rows changed meanwhile are buffered again and merged in a
+    /// short final step with the lock. So the pushing of the trace is not blocked by a long merge
+    pub fn unfreeze(
+        &self,
+        mut cb_removed: impl FnMut(/*index*/ usize),
+        mut cb_pushed: impl FnMut(/*index*/ usize, /*count*/ usize),
+    ) {
+        let _merge_lock = self.merge_lock.lock();
+        let frozen_buffer = {
+            let mut frozen = self.frozen.lock();
+            match frozen.as_mut() {
+                Some(frozen_buffer) => mem::replace(frozen_buffer, FrozenBuffer::new()),
+                None => return,
             }
+        };
+        self.merge(frozen_buffer, &mut cb_removed, &mut cb_pushed);
+
+        let mut frozen = self.frozen.lock();
+        if let Some(frozen_buffer) = frozen.take().filter(|buffer| !buffer.is_empty()) {
+            self.merge(frozen_buffer, &mut cb_removed, &mut cb_pushed);
         }
-        Ok(vec)
     }
 
-    pub fn push(&self, value: Arc<Node<T>>) -> usize {
+    // the list is still frozen or with the frozen lock, so no row is pushed meanwhile
+    fn merge(
+        &self,
+        frozen_buffer: FrozenBuffer<T>,
+        cb_removed: &mut impl FnMut(usize),
+        cb_pushed: &mut impl FnMut(usize, usize),
+    ) {
+        let FrozenBuffer {
+            pushed,
+            mut removed,
+            dropped,
+        } = frozen_buffer;
+        let pushed: Vec<_> = pushed
+            .into_iter()
+            .filter(|node| !dropped.contains_key(&(Arc::as_ptr(node) as usize)))
+            .collect();
+        // dropped when the previous merge is pushing it
+        removed.extend(
+            dropped
+                .into_values()
+                .filter(|node| node.serial_number.get().is_some()),
+        );
+        self.remove_all_unfrozen(removed, cb_removed);
+        let push_index = self.len();
+        let push_count = pushed.len();
+        for node_arc in pushed {
+            self.push_unfrozen(node_arc);
+        }
+        cb_pushed(push_index, push_count);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.lock().is_some()
+    }
+
+    fn push_unfrozen(&self, value: Arc<Node<T>>) -> usize {
         let mut _list_last_lock = self.list_last_lock.lock();
         let serail_number = self.serial_number.fetch_add(1, Ordering::Release);
         value.serial_number.set(serail_number).unwrap();
@@ -198,32 +413,79 @@ impl<T: Clone + Send + Sync> EventList<T> {
         index
     }
 
-    /// Remove the row by a arc
-    pub fn remove(&self, node_arc: Arc<Node<T>>) {
+    fn remove_unfrozen(&self, node_arc: Arc<Node<T>>, max_distance: usize) -> Option<usize> {
         let mut list_except_last_lock = self.list_except_last_lock.lock(); // lock before remove
+        let index = self.index_of(&list_except_last_lock.read, node_arc.as_ref(), max_distance);
         let mut cursor = self.get_cursor_mut_from_node(node_arc.as_ref()); // the node_arc may be modify by other
                                                                            // the remove and push is called in same thread
         let node_arc_removed = cursor.remove();
         if let Some(node_arc_removed) = &node_arc_removed {
             self.list_len.fetch_sub(1, Ordering::Release);
-            if let Some(cursor_last_read) = &list_except_last_lock.node {
-                if ptr::eq(node_arc_removed.as_ref(), cursor_last_read.as_ref()) {
-                    let index = list_except_last_lock.index;
-                    list_except_last_lock.set(cursor.as_cursor().clone_pointer(), index);
-                } else {
-                    if node_arc_removed.serial_number.get().unwrap()
-                        <= cursor_last_read.serial_number.get().unwrap()
-                    {
-                        if list_except_last_lock.index != 0 {
-                            list_except_last_lock.index -= 1;
-                        }
-                    }
+            let cursor_next = cursor.as_cursor().clone_pointer();
+            list_except_last_lock
+                .read
+                .on_removed(node_arc_removed.as_ref(), cursor_next.clone());
+            list_except_last_lock
+                .find
+                .on_removed(node_arc_removed.as_ref(), cursor_next);
+            index
+        } else {
+            None
+        }
+    }
+
+    // remove the rows in one walk from the back. the rows are removed in the descending order, so the index of the
+    // rows not walked yet are not changed. must be called when no row is pushed meanwhile, see `merge`
+    fn remove_all_unfrozen(
+        &self,
+        mut nodes: Vec<Arc<Node<T>>>,
+        cb_removed: &mut impl FnMut(usize),
+    ) {
+        nodes.sort_unstable_by_key(|node| Reverse(node.serial_number.get().copied()));
+        let mut list_except_last_lock = self.list_except_last_lock.lock();
+        let list_len = self.list_len.load(Ordering::Acquire);
+        let mut cursor = self.get_list_mut().back_mut();
+        let mut index = list_len.wrapping_sub(1);
+        for node in nodes {
+            let Some(serial_number) = node.serial_number.get() else {
+                continue;
+            };
+            // the serial numbers are ascending in the list. the node is not in the list when passed
+            while let Some(item) = cursor.get() {
+                if ptr::eq(item, node.as_ref()) || item.serial_number.get() < Some(serial_number) {
+                    break;
                 }
+                cursor.move_prev();
+                index = index.wrapping_sub(1);
+            }
+            if !cursor
+                .get()
+                .is_some_and(|item| ptr::eq(item, node.as_ref()))
+            {
+                continue;
             }
+            // the cursor is moved to the next after removing
+            let node_arc_removed = cursor.remove().unwrap();
+            self.list_len.fetch_sub(1, Ordering::Release);
+            let cursor_next = cursor.as_cursor().clone_pointer();
+            list_except_last_lock
+                .read
+                .on_removed(node_arc_removed.as_ref(), cursor_next.clone());
+            list_except_last_lock
+                .find
+                .on_removed(node_arc_removed.as_ref(), cursor_next);
+            cb_removed(index);
+            cursor.move_prev();
+            index = index.wrapping_sub(1);
         }
     }
 
     pub fn clear(&self) {
+        let _merge_lock = self.merge_lock.lock();
+        let mut frozen = self.frozen.lock();
+        if let Some(frozen_buffer) = frozen.as_mut() {
+            *frozen_buffer = FrozenBuffer::new();
+        }
         let mut list_except_last_lock = self.list_except_last_lock.lock(); // always lock before list_last_lock
         let _list_last_lock = self.list_last_lock.lock();
 
@@ -231,7 +493,54 @@ impl<T: Clone + Send + Sync> EventList<T> {
         list.clear();
         self.list_len.store(0, Ordering::Relaxed);
         self.serial_number.store(0, Ordering::Relaxed);
-        *list_except_last_lock = NodeArc::new();
+        *list_except_last_lock = NodeArcs::new();
+    }
+
+    // walk from the nearer one(by serial number) of the cache and the back. must be called with list_except_last_lock
+    fn index_of(&self, cache: &NodeArc<T>, node: &Node<T>, max_distance: usize) -> Option<usize> {
+        let serial_number = *node.serial_number.get()?;
+        let (back, back_index) = {
+            let _list_last_lock = self.list_last_lock.lock();
+            let list_len = self.list_len.load(Ordering::Acquire);
+            if list_len == 0 {
+                return None;
+            }
+            (self.get_list().back(), list_len - 1)
+        };
+        let back_serial_number = *back.get()?.serial_number.get()?;
+
+        // the distance of serial number is not less than the distance of index
+        let mut start = (back, back_index, back_serial_number);
+        if let Some(cursor) = cache.get_cursor(self.get_list()) {
+            if let Some(cache_serial_number) =
+                cursor.get().and_then(|item| item.serial_number.get())
+            {
+                if cache_serial_number.abs_diff(serial_number)
+                    < back_serial_number.abs_diff(serial_number)
+                {
+                    start = (cursor, cache.index, *cache_serial_number);
+                }
+            }
+        }
+        let (mut cursor, mut index, cursor_serial_number) = start;
+        let is_forward = serial_number > cursor_serial_number;
+        for _ in 0..=max_distance {
+            let item = cursor.get()?;
+            if ptr::eq(item, node) {
+                return Some(index);
+            }
+            if is_forward {
+                cursor.move_next();
+                index += 1;
+            } else {
+                if index == 0 {
+                    return None;
+                }
+                cursor.move_prev();
+                index -= 1;
+            }
+        }
+        None
     }
 
     fn get_list(&self) -> &LinkedList<NodeAdapter<T>> {
@@ -246,3 +555,190 @@ impl<T: Clone + Send + Sync> EventList<T> {
         unsafe { self.get_list_mut().cursor_mut_from_ptr(node) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{EventList, Node, Removed};
+    use std::sync::Arc;
+
+    #[test]
+    fn traversal_from() {
+        let list = EventList::<u32>::new();
+        for i in 0..10u32 {
+            list.push(Arc::new(Node::new(i)));
+        }
+
+        let mut found = None;
+        let r = list
+            .traversal_from(2, true, 3, |index, value| {
+                if *value == 7 {
+                    found = Some(index);
+                    return Ok(true);
+                }
+                Ok(false)
+            })
+            .unwrap();
+        assert_eq!(r, Some(5));
+        assert!(found.is_none());
+
+        let r = list
+            .traversal_from(5, true, 3, |index, value| {
+                if *value == 7 {
+                    found = Some(index);
+                    return Ok(true);
+                }
+                Ok(false)
+            })
+            .unwrap();
+        assert_eq!(r, None);
+        assert_eq!(found, Some(7));
+
+        let mut visited = vec![];
+        let r = list
+            .traversal_from(3, false, 10, |_index, value| {
+                visited.push(*value);
+                Ok(false)
+            })
+            .unwrap();
+        assert_eq!(r, None);
+        assert_eq!(visited, vec![3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn remove() {
+        let list = EventList::<u32>::new();
+        let mut nodes = vec![];
+        for i in 0..10u32 {
+            let node = Arc::new(Node::new(i));
+            list.push(node.clone());
+            nodes.push(node);
+        }
+
+        assert!(matches!(
+            list.remove(nodes[8].clone(), 100),
+            Removed::Index(8)
+        ));
+        assert_eq!(list.get_by_index(2).unwrap().value, 2);
+        assert!(matches!(
+            list.remove(nodes[3].clone(), 100),
+            Removed::Index(3)
+        ));
+        assert!(matches!(list.remove(nodes[0].clone(), 1), Removed::NoIndex));
+        assert_eq!(list.len(), 7);
+        assert_eq!(list.get_by_index(0).unwrap().value, 1);
+    }
+
+    #[test]
+    fn freeze() {
+        let list = EventList::<u32>::new();
+        let mut nodes = vec![];
+        for i in 0..5u32 {
+            let node = Arc::new(Node::new(i));
+            list.push(node.clone());
+            nodes.push(node);
+        }
+
+        list.freeze();
+        for i in 5..8u32 {
+            let node = Arc::new(Node::new(i));
+            assert_eq!(list.push(node.clone()), None);
+            nodes.push(node);
+        }
+        assert!(matches!(
+            list.remove(nodes[1].clone(), 100),
+            Removed::Deferred
+        ));
+        // the buffered row is dropped without notifying
+        assert!(matches!(
+            list.remove(nodes[6].clone(), 100),
+            Removed::Deferred
+        ));
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.get_by_index(1).unwrap().value, 1);
+
+        let mut removed_indexes = vec![];
+        let mut pushed = None;
+        list.unfreeze(
+            |index| removed_indexes.push(index),
+            |index, count| pushed = Some((index, count)),
+        );
+        assert_eq!(removed_indexes, vec![1]);
+        assert_eq!(pushed, Some((4, 2)));
+        assert!(!list.is_frozen());
+        let values: Vec<u32> = (0..list.len())
+            .map(|index| list.get_by_index(index).unwrap().value)
+            .collect();
+        assert_eq!(values, vec![0, 2, 3, 4, 5, 7]);
+    }
+
+    #[test]
+    fn unfreeze_removes_in_one_walk() {
+        let list = EventList::<u32>::new();
+        let mut nodes = vec![];
+        for i in 0..10u32 {
+            let node = Arc::new(Node::new(i));
+            list.push(node.clone());
+            nodes.push(node);
+        }
+        // the cached cursor is after the removed rows
+        assert_eq!(list.get_by_index(7).unwrap().value, 7);
+
+        list.freeze();
+        for i in [0, 9, 4, 5, 2] {
+            assert!(matches!(
+                list.remove(nodes[i].clone(), 100),
+                Removed::Deferred
+            ));
+        }
+        let mut removed_indexes = vec![];
+        list.unfreeze(|index| removed_indexes.push(index), |_, _| {});
+        assert_eq!(removed_indexes, vec![9, 5, 4, 2, 0]);
+        let values: Vec<u32> = (0..list.len())
+            .map(|index| list.get_by_index(index).unwrap().value)
+            .collect();
+        assert_eq!(values, vec![1, 3, 6, 7, 8]);
+        // removed again is ignored
+        list.freeze();
+        assert!(matches!(
+            list.remove(nodes[4].clone(), 100),
+            Removed::Deferred
+        ));
+        list.unfreeze(
+            |_| panic!("the row is removed"),
+            |_, count| assert_eq!(count, 0),
+        );
+        assert_eq!(list.len(), 5);
+    }
+
+    #[test]
+    fn unfreeze_buffers_while_merging() {
+        let list = EventList::<u32>::new();
+        let nodes: Vec<_> = (0..3u32).map(|i| Arc::new(Node::new(i))).collect();
+        list.push(nodes[0].clone());
+        list.freeze();
+        list.push(nodes[1].clone());
+        list.push(nodes[2].clone());
+
+        // the rows changed by the trace during the merge are applied in the final step
+        let mut pushed = vec![];
+        list.unfreeze(
+            |_| {},
+            |index, count| {
+                if pushed.is_empty() {
+                    assert_eq!(list.push(Arc::new(Node::new(3))), None);
+                    assert!(matches!(
+                        list.remove(nodes[1].clone(), 100),
+                        Removed::Deferred
+                    ));
+                }
+                pushed.push((index, count));
+            },
+        );
+        assert_eq!(pushed, vec![(1, 2), (2, 1)]);
+        assert!(!list.is_frozen());
+        let values: Vec<u32> = (0..list.len())
+            .map(|index| list.get_by_index(index).unwrap().value)
+            .collect();
+        assert_eq!(values, vec![0, 2, 3]);
+    }
+}
@@ -1,16 +1,26 @@
+use crate::delay_notify::{DelayNotify, Notify, NotifySink};
 use crate::event_list::EventList;
 use crate::event_list::Node;
 use crate::event_record_model::EventRecordModel;
 use crate::filter::ExpressionForOne;
 use anyhow::Result;
 use slint::{Model, ModelNotify, ModelRc, ModelTracker, StandardListViewItem};
-use std::sync::Arc;
+use smol::Task;
+use std::{cell::RefCell, sync::Arc};
+
+// the count of items is traversed before yielding to others
+const FIND_STEP: usize = 4096;
+// the max count of rows walked for the index of a removed row. a reset is notified when exceeding
+pub const REMOVE_INDEX_MAX_DISTANCE: usize = 100_000;
 
 pub struct ListModel {
     // the backing data, access by cursor
     list: Arc<EventList<EventRecordModel>>,
     // the ModelNotify will allow to notify the UI that the model changes
     notify: ModelNotify,
+    // the running find. a new find cancels the old by dropping
+    find_task: RefCell<Option<Task<()>>>,
+    find_marks_task: RefCell<Option<Task<()>>>,
 }
 
 impl Model for ListModel {
@@ -48,6 +58,8 @@ impl ListModel {
         Self {
             list,
             notify: Default::default(),
+            find_task: RefCell::new(None),
+            find_marks_task: RefCell::new(None),
         }
     }
 
@@ -61,10 +73,50 @@ impl ListModel {
         self.notify.row_added(index, count);
     }
 
+    pub fn notify_remove(&self, index: usize, count: usize) {
+        self.notify.row_removed(index, count);
+    }
+
     pub fn notify_reset(&self) {
         self.notify.reset();
     }
 
+    pub fn notify_all(&self, notifies: Vec<Notify>) {
+        for notify in notifies {
+            match notify {
+                Notify::Push(index, count) => self.notify_push(index, count),
+                Notify::Remove(index, count) => self.notify_remove(index, count),
+                Notify::Reset => self.notify_reset(),
+            }
+        }
+    }
+
+    /// Keep the rows of the view unchanged. The events are still collected in background
+    pub fn freeze(&self) {
+        self.list.freeze();
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.list.is_frozen()
+    }
+
+    /// Merge the rows collected when frozen in background. The removed rows are merged in one walk, and notified
+    /// through the `delay_notify` in order with the rows pushed by the trace
+    pub fn unfreeze<S: NotifySink>(&self, delay_notify: Arc<DelayNotify<S>>) {
+        let list = self.list.clone();
+        smol::unblock(move || {
+            list.unfreeze(
+                |index| delay_notify.notify(Notify::Remove(index, 1)),
+                |index, count| {
+                    if count > 0 {
+                        delay_notify.notify(Notify::Push(index, count));
+                    }
+                },
+            );
+        })
+        .detach();
+    }
+
     pub fn row_data_detail(&self, row: usize) -> Option<Arc<Node<EventRecordModel>>> {
         if row >= self.list.len() {
             return None;
@@ -72,13 +124,159 @@ impl ListModel {
         self.list.get_by_index(row)
     }
 
-    pub fn row_find(&self, filter_expr: &ExpressionForOne) -> Result<Vec<i32>> {
-        self.list.traversal(|item| {
-            ExpressionForOne::evaluate(
-                filter_expr,
-                |path, value| item.find_by_path_value(path, value),
-                |value| item.find_by_value(value),
-            )
-        })
+    /// Find the next matched row after `index_from`(excluded) toward the direction, and wrap around at the end.
+    /// The `fn_completion` is called on a background thread with the found index.
+    pub fn row_find_next(
+        &self,
+        filter_expr: ExpressionForOne,
+        index_from: Option<usize>,
+        is_forward: bool,
+        fn_completion: impl FnOnce(Result<Option<usize>>) + Send + 'static,
+    ) {
+        self.row_find_next_by(
+            move |item| {
+                filter_expr.evaluate(
+                    |path, value| item.find_by_path_value(path, value),
+                    |value| item.find_by_value(value),
+                )
+            },
+            index_from,
+            is_forward,
+            fn_completion,
+        );
+    }
+
+    /// Flip the bookmark of the row. Returns the new state
+    pub fn row_toggle_bookmark(&self, row: usize) -> Option<bool> {
+        let node = self.row_data_detail(row)?;
+        let bookmarked = !node.value.is_bookmarked();
+        node.value.set_bookmarked(bookmarked);
+        self.notify.row_changed(row);
+        Some(bookmarked)
+    }
+
+    /// Find the next bookmarked row like `row_find_next`
+    pub fn row_find_bookmark(
+        &self,
+        index_from: Option<usize>,
+        is_forward: bool,
+        fn_completion: impl FnOnce(Result<Option<usize>>) + Send + 'static,
+    ) {
+        self.row_find_next_by(
+            |item| Ok(item.is_bookmarked()),
+            index_from,
+            is_forward,
+            fn_completion,
+        );
+    }
+
+    fn row_find_next_by(
+        &self,
+        mut is_matched: impl FnMut(&EventRecordModel) -> Result<bool> + Send + 'static,
+        index_from: Option<usize>,
+        is_forward: bool,
+        fn_completion: impl FnOnce(Result<Option<usize>>) + Send + 'static,
+    ) {
+        let list = self.list.clone();
+        let task = smol::spawn(async move {
+            let list_len = list.len();
+            if list_len == 0 {
+                fn_completion(Ok(None));
+                return;
+            }
+            // the ranges: the first is from index_from to end, the second is from other end to index_from
+            let (start_first, start_second) = match index_from {
+                None => (if is_forward { 0 } else { list_len - 1 }, None),
+                Some(index) => {
+                    let index = index.min(list_len - 1);
+                    if is_forward {
+                        (index + 1, Some(0))
+                    } else {
+                        (index.wrapping_sub(1), Some(list_len - 1))
+                    }
+                }
+            };
+            let mut found = None;
+            for start in [Some(start_first), start_second] {
+                let Some(mut index) = start else {
+                    break;
+                };
+                loop {
+                    let r = list.traversal_from(index, is_forward, FIND_STEP, |index, item| {
+                        let is_matched = is_matched(item)?;
+                        if is_matched {
+                            found = Some(index);
+                        }
+                        Ok(is_matched)
+                    });
+                    match r {
+                        Err(e) => {
+                            fn_completion(Err(e));
+                            return;
+                        }
+                        Ok(Some(index_next)) => index = index_next,
+                        Ok(None) => break,
+                    }
+                    smol::future::yield_now().await;
+                }
+                if found.is_some() {
+                    break;
+                }
+            }
+            fn_completion(Ok(found));
+        });
+        *self.find_task.borrow_mut() = Some(task);
+    }
+
+    /// Count the matched rows in every bucket of `bucket_count` buckets for marking at scroll bar.
+    /// The `fn_completion` is called on a background thread with the density(0.0 - 1.0) of every bucket.
+    pub fn row_find_marks(
+        &self,
+        filter_expr: ExpressionForOne,
+        bucket_count: usize,
+        fn_completion: impl FnOnce(Result<Vec<f32>>) + Send + 'static,
+    ) {
+        let list = self.list.clone();
+        let task = smol::spawn(async move {
+            let list_len = list.len();
+            if list_len == 0 || bucket_count == 0 {
+                fn_completion(Ok(vec![]));
+                return;
+            }
+            let mut counts = vec![0u32; bucket_count];
+            let mut index = 0usize;
+            loop {
+                let r = list.traversal_from(index, true, FIND_STEP, |index, item| {
+                    // the rows pushed after starting is ignored
+                    if index >= list_len {
+                        return Ok(true);
+                    }
+                    let is_matched = filter_expr.evaluate(
+                        |path, value| item.find_by_path_value(path, value),
+                        |value| item.find_by_value(value),
+                    )?;
+                    if is_matched {
+                        counts[index * bucket_count / list_len] += 1;
+                    }
+                    Ok(false)
+                });
+                match r {
+                    Err(e) => {
+                        fn_completion(Err(e));
+                        return;
+                    }
+                    Ok(Some(index_next)) => index = index_next,
+                    Ok(None) => break,
+                }
+                smol::future::yield_now().await;
+            }
+            let bucket_size = (list_len as f32 / bucket_count as f32).max(1.0);
+            let density = counts
+                .into_iter()
+                .map(|count| (count as f32 / bucket_size).min(1.0))
+                .collect();
+            fn_completion(Ok(density));
+        });
+        *self.find_marks_task.borrow_mut() = Some(task);
     }
 }
@@ -1,18 +1,25 @@
 use crate::{
+    demangle,
     event_trace::{process_modules, EventRecordDecoded, PropertyDecoded, StackWalk},
     filter::{Path, Value},
-    pdb::get_location_info,
+    symbolizer::{self, FrameRequest, Locations},
+    third_extend::Guid,
     StackWalkInfo,
 };
 use anyhow::{anyhow, Error, Result};
+use serde::Serialize;
 use slint::{Model, ModelRc, ModelTracker, SharedString, StandardListViewItem, VecModel};
 use std::{
-    path,
+    path::PathBuf,
     str::FromStr,
-    sync::{Arc, OnceLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
 };
 use strum::{AsRefStr, VariantArray};
-use tracing::{error, info};
+use tracing::error;
+use windows::core::GUID;
 
 #[derive(Clone)]
 pub struct EventRecordModel {
@@ -20,6 +27,8 @@ pub struct EventRecordModel {
     pub process_path: String,
     stack_walk: OnceLock<Arc<StackWalk>>,
     stack_walk_2: OnceLock<Arc<StackWalk>>, // occasionally there is second stack on other process
+    // shared by the clones of row, so the bookmark set on the view is saved with the session
+    bookmarked: Arc<AtomicBool>,
 }
 
 impl EventRecordModel {
@@ -29,9 +38,18 @@ impl EventRecordModel {
             process_path,
             stack_walk: OnceLock::new(),
             stack_walk_2: OnceLock::new(),
+            bookmarked: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    pub fn is_bookmarked(&self) -> bool {
+        self.bookmarked.load(Ordering::Relaxed)
+    }
+
+    pub fn set_bookmarked(&self, bookmarked: bool) {
+        self.bookmarked.store(bookmarked, Ordering::Relaxed);
+    }
+
     pub fn data_detail(&self) -> Option<SharedString> {
         Some(SharedString::from(format!(
             "{}\n{}",
@@ -71,91 +89,81 @@ impl EventRecordModel {
         }
     }
 
-    pub fn stack_walk(
+    pub fn stack_walk_raw(
         &self,
     ) -> (
-        /*stacks*/ StackWalkInfo,
-        /*stacks_2*/ StackWalkInfo,
+        /*stacks*/ Option<&Arc<StackWalk>>,
+        /*stacks_2*/ Option<&Arc<StackWalk>>,
     ) {
-        return (
-            get_stack_walk_info(self.stack_walk.get()),
-            get_stack_walk_info(self.stack_walk_2.get()),
-        );
+        (self.stack_walk.get(), self.stack_walk_2.get())
+    }
 
-        fn get_stack_walk_info(stack_op: Option<&Arc<StackWalk>>) -> StackWalkInfo {
-            if let Some(stack_arc) = stack_op {
-                let vec = VecModel::default();
-                for item in stack_arc.stacks.iter() {
-                    let model_rc = if let Some(relative) = item.1.relative {
-                        if let Some(module_info) =
-                            process_modules::get_module_info_by_id(relative.0)
-                        {
-                            let file_name = module_info.get_module_name();
-                            let (function_offset, line_offset) = get_location_info(
-                                path::Path::new(module_info.file_name.as_str()),
-                                module_info.time_data_stamp,
-                                relative.1,
-                            )
-                            .unwrap_or_else(|e| {
-                                info!("{e:#}");
-                                (String::new(), String::new())
-                            });
-
-                            ModelRc::from([
-                                StandardListViewItem::from(SharedString::from(&item.0)),
-                                StandardListViewItem::from(SharedString::from(format!(
-                                    "{:#x}",
-                                    item.1.raw
-                                ))),
-                                StandardListViewItem::from(SharedString::from(format!(
-                                    "{file_name}+{:#x}",
-                                    relative.1
-                                ))),
-                                StandardListViewItem::from(SharedString::from(function_offset)),
-                                StandardListViewItem::from(SharedString::from(line_offset)),
-                            ])
-                        } else {
-                            ModelRc::from([
-                                StandardListViewItem::from(SharedString::from(&item.0)),
-                                StandardListViewItem::from(SharedString::from(format!(
-                                    "{:#x}",
-                                    item.1.raw
-                                ))),
-                                StandardListViewItem::from(SharedString::from(format!(
-                                    "{:#x}",
-                                    relative.0
-                                ))),
-                                StandardListViewItem::from(SharedString::from(format!(
-                                    "{:#x}",
-                                    relative.1
-                                ))),
-                                StandardListViewItem::from(SharedString::new()),
-                            ])
-                        }
-                    } else {
-                        ModelRc::from([
-                            StandardListViewItem::from(SharedString::from(&item.0)),
-                            StandardListViewItem::from(SharedString::from(format!(
-                                "{:#x}",
-                                item.1.raw
-                            ))),
-                            StandardListViewItem::from(SharedString::new()),
-                            StandardListViewItem::from(SharedString::new()),
-                            StandardListViewItem::from(SharedString::new()),
-                        ])
-                    };
-                    vec.push(model_rc);
-                }
-                StackWalkInfo {
-                    event_timestamp: SharedString::from(stack_arc.event_timestamp.to_string()),
-                    process_id: SharedString::from(format!("{}", stack_arc.stack_process as i32)),
-                    thread_id: SharedString::from(format!("{}", stack_arc.stack_thread as i32)),
-                    stacks: ModelRc::new(vec),
-                }
-            } else {
-                StackWalkInfo::default()
-            }
+    /// Translate the addresses of stacks to the code locations in the current thread
+    pub fn stack_walk_symbolized(
+        &self,
+    ) -> (
+        /*stacks*/ Option<StackWalkSymbolized>,
+        /*stacks_2*/ Option<StackWalkSymbolized>,
+    ) {
+        let symbolize = |sw: &Arc<StackWalk>| {
+            StackWalkUnsymbolized::new(sw).symbolized(|request| Some(request.locations()))
+        };
+        (
+            self.stack_walk.get().map(symbolize),
+            self.stack_walk_2.get().map(symbolize),
+        )
+    }
+
+    /// The stacks that the frames of unparsed pdbs are placeholders. The frames are symbolized on the workers
+    /// and on_done is called on a worker with the symbolized stacks, it is not called if no placeholder
+    pub fn stack_walk_symbolize_async(
+        &self,
+        on_done: impl FnOnce(
+                /*stacks*/ Option<StackWalkSymbolized>,
+                /*stacks_2*/ Option<StackWalkSymbolized>,
+            ) + Send
+            + 'static,
+    ) -> (
+        /*stacks*/ Option<StackWalkSymbolized>,
+        /*stacks_2*/ Option<StackWalkSymbolized>,
+    ) {
+        let stack_walk = self
+            .stack_walk
+            .get()
+            .map(|sw| StackWalkUnsymbolized::new(sw));
+        let stack_walk_2 = self
+            .stack_walk_2
+            .get()
+            .map(|sw| StackWalkUnsymbolized::new(sw));
+        let mut is_placeholder = false;
+        let mut symbolize_loaded = |stack: &StackWalkUnsymbolized| {
+            stack.clone().symbolized(|request| {
+                let locations = request.locations_loaded();
+                is_placeholder |= locations.is_none();
+                locations
+            })
+        };
+        let stacks_loaded = (
+            stack_walk.as_ref().map(&mut symbolize_loaded),
+            stack_walk_2.as_ref().map(&mut symbolize_loaded),
+        );
+        if !is_placeholder {
+            return stacks_loaded;
         }
+
+        // the stacks are symbolized in a batch
+        let requests: Vec<FrameRequest> = stack_walk
+            .iter()
+            .chain(stack_walk_2.iter())
+            .flat_map(|stack| stack.requests().cloned())
+            .collect();
+        symbolizer::symbolize(requests, move |locations| {
+            let mut locations = locations.into_iter();
+            let stack_walk = stack_walk.map(|stack| stack.symbolized(|_| locations.next()));
+            let stack_walk_2 = stack_walk_2.map(|stack| stack.symbolized(|_| locations.next()));
+            on_done(stack_walk, stack_walk_2);
+        });
+        stacks_loaded
     }
 
     pub fn find_by_path_value(&self, path: &Path, value: &Value) -> Result<bool> {
@@ -216,29 +224,89 @@ impl EventRecordModel {
                 }
                 return Err(anyhow!("invalid value type"));
             }
+            Columns::Cpu | Columns::KernelTime | Columns::UserTime => {
+                if let Value::I64(num) = value {
+                    let meta = &self.array.header_meta;
+                    let column_num = match path.key {
+                        Columns::Cpu => meta.processor_number as i64,
+                        Columns::KernelTime => meta.kernel_time as i64,
+                        _ => meta.user_time as i64,
+                    };
+                    return Ok(*num == column_num);
+                }
+                return Err(anyhow!("invalid value type"));
+            }
+            Columns::HeaderFlags | Columns::EventProperty => {
+                if let Value::I64(num) = value {
+                    let meta = &self.array.header_meta;
+                    let column_num = if path.key == Columns::HeaderFlags {
+                        meta.flags
+                    } else {
+                        meta.event_property
+                    };
+                    return Ok(*num == column_num as i64);
+                }
+                return Err(anyhow!("invalid value type"));
+            }
+            Columns::ActivityId | Columns::RelatedActivityId => {
+                if let Value::Str(string) = value {
+                    let string = string.trim_matches(|c| c == '{' || c == '}');
+                    return Ok(self.column_text(&path.key).eq_ignore_ascii_case(string));
+                }
+                return Err(anyhow!("invalid value type"));
+            }
+            Columns::Extended => {
+                let Some(ref field) = path.field else {
+                    return Err(anyhow!("Not assign field for extended"));
+                };
+                let Some(item) = self.extended_item(field) else {
+                    return Ok(false);
+                };
+                match value {
+                    Value::Str(value_str) => {
+                        let item_str = item.as_str().map(|s| s.to_string());
+                        Ok(item_str
+                            .unwrap_or_else(|| item.to_string())
+                            .eq_ignore_ascii_case(value_str))
+                    }
+                    Value::I64(v) => Ok(item.as_i64() == Some(*v)),
+                    Value::Num(v) => Ok(item.as_f64() == Some(*v)),
+                    _ => Err(anyhow!(
+                        "The finding extended.{field}'s value's type is not supported"
+                    )),
+                }
+            }
             Columns::Properties => {
                 if let Some(ref field) = path.field {
-                    if let Value::Str(ref value_str) = value {
-                        if let PropertyDecoded::Struct(ref properties) = self.array.properties {
-                            if let PropertyDecoded::String(ref property_field_str) =
-                                properties[field]
-                            {
+                    let PropertyDecoded::Struct(ref properties) = self.array.properties else {
+                        return Err(anyhow!(
+                            "The properties of {}-{} is not a struct!",
+                            self.array.get_event_display_name(),
+                            self.array.opcode_name
+                        ));
+                    };
+                    let Some(property) = properties.get(field) else {
+                        return Ok(false);
+                    };
+                    match value {
+                        Value::Str(value_str) => {
+                            if let Some(property_field_str) = property.text() {
                                 return Ok(value_str.to_ascii_lowercase()
                                     == property_field_str.to_ascii_lowercase());
                             } else {
                                 return Err(anyhow!("The properties's {field} type is not string"));
                             }
-                        } else {
+                        }
+                        // the integer is compared by value, so 500 matches 0x1F4
+                        Value::I64(v) => return Ok(property.as_i64() == Some(*v)),
+                        Value::Num(v) => {
+                            return Ok(property.as_i64().is_some_and(|num| num as f64 == *v))
+                        }
+                        _ => {
                             return Err(anyhow!(
-                                "The properties of {}-{} is not a struct!",
-                                self.array.get_event_display_name(),
-                                self.array.opcode_name
+                                "The finding properties.{field}'s value's type is not supported"
                             ));
                         }
-                    } else {
-                        return Err(anyhow!(
-                            "The finding properties.{field}'s value's type is not Value::Str"
-                        ));
                     }
                 } else {
                     return Err(anyhow!("Not assign field for properties"));
@@ -274,7 +342,7 @@ impl EventRecordModel {
                         {
                             return Ok(true);
                         }
-                        if let PropertyDecoded::String(ref value_str) = value {
+                        if let Some(value_str) = value.text() {
                             if value_str
                                 .to_ascii_lowercase()
                                 .contains(v.to_ascii_lowercase().as_str())
@@ -298,7 +366,7 @@ impl EventRecordModel {
                         if key.to_ascii_lowercase().contains(v.to_string().as_str()) {
                             return Ok(true);
                         }
-                        if let PropertyDecoded::String(ref value_str) = value {
+                        if let Some(value_str) = value.text() {
                             if value_str
                                 .to_ascii_lowercase()
                                 .contains(v.to_string().as_str())
@@ -319,7 +387,7 @@ impl EventRecordModel {
                         if key.to_ascii_lowercase().contains(v.to_string().as_str()) {
                             return Ok(true);
                         }
-                        if let PropertyDecoded::String(ref value_str) = value {
+                        if let Some(value_str) = value.text() {
                             if value_str
                                 .to_ascii_lowercase()
                                 .contains(v.to_string().as_str())
@@ -357,17 +425,37 @@ impl EventRecordModel {
                 Columns::OpcodeName => {
                     s.push_str(self.array.opcode_name.as_str());
                 }
+                Columns::Extended => {
+                    let Some(ref field) = path.field else {
+                        return Err(anyhow!("Not assign field for extended"));
+                    };
+                    if let Some(item) = self.extended_item(field) {
+                        match item.as_str() {
+                            Some(item_str) => s.push_str(item_str),
+                            None => s.push_str(item.to_string().as_str()),
+                        }
+                    }
+                }
+                Columns::Cpu
+                | Columns::KernelTime
+                | Columns::UserTime
+                | Columns::ActivityId
+                | Columns::RelatedActivityId
+                | Columns::HeaderFlags
+                | Columns::EventProperty => {
+                    s.push_str(self.column_text(&path.key).as_str());
+                }
                 Columns::Properties => {
                     if let Some(ref field) = path.field {
                         if let PropertyDecoded::Struct(ref properties) = self.array.properties {
-                            if let PropertyDecoded::String(ref property_field_str) =
-                                properties[field]
+                            if let Some(property_field_str) =
+                                properties.get(field).and_then(|property| property.text())
                             {
-                                s.push_str(property_field_str);
+                                s.push_str(&property_field_str);
                             } else {
                                 return Err(anyhow!(
                                     "The properties's {field} type is not string, {:?}",
-                                    properties[field]
+                                    properties.get(field)
                                 ));
                             }
                         } else {
@@ -386,9 +474,65 @@ impl EventRecordModel {
         Ok(s)
     }
 
+    // the item of extended data by the field name. i.e. user_sid, terminal_session_id
+    fn extended_item(&self, field: &str) -> Option<serde_json::Value> {
+        self.array.extended.item(field)
+    }
+
     pub fn get_process_name(&self) -> &str {
         process_modules::get_file_name_from_path(self.process_path.as_str())
     }
+
+    /// The text displayed in the column
+    pub fn column_text(&self, column: &Columns) -> String {
+        match column {
+            Columns::Datetime => self.array.timestamp.to_datetime_detail(),
+            Columns::ProcessName => self.get_process_name().to_string(),
+            Columns::ProcessId => (self.array.process_id as i32).to_string(),
+            Columns::ThreadId => (self.array.thread_id as i32).to_string(),
+            Columns::EventName => self.array.get_event_display_name().to_string(),
+            Columns::OpcodeName => self.array.opcode_name.to_string(),
+            Columns::Cpu => self.array.header_meta.processor_number.to_string(),
+            Columns::KernelTime => self.array.header_meta.kernel_time.to_string(),
+            Columns::UserTime => self.array.header_meta.user_time.to_string(),
+            Columns::ActivityId => guid_text(Some(&self.array.header_meta.activity_id)),
+            Columns::RelatedActivityId => {
+                guid_text(self.array.extended.related_activity_id.as_ref())
+            }
+            Columns::HeaderFlags => format!("0x{:X}", self.array.header_meta.flags),
+            Columns::EventProperty => format!("0x{:X}", self.array.header_meta.event_property),
+            Columns::Extended => {
+                if self.array.extended.is_empty() {
+                    String::new()
+                } else {
+                    serde_json::to_string(&self.array.extended).unwrap_or_default()
+                }
+            }
+            Columns::Properties => {
+                serde_json::to_string(&self.array.properties).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Returns None if the properties has not the field. The value is json if it is not a string
+    pub fn property_text(&self, field: &str) -> Option<String> {
+        let PropertyDecoded::Struct(ref properties) = self.array.properties else {
+            return None;
+        };
+        let property = properties.get(field)?;
+        match property.text() {
+            Some(s) => Some(s.into_owned()),
+            None => Some(serde_json::to_string(property).unwrap_or_default()),
+        }
+    }
+}
+
+// empty for the GUID_NULL
+fn guid_text(guid: Option<&Guid>) -> String {
+    match guid {
+        Some(guid) if guid.0 != GUID::zeroed() => format!("{:?}", guid.0),
+        _ => String::new(),
+    }
 }
 
 impl Model for EventRecordModel {
@@ -402,29 +546,12 @@ impl Model for EventRecordModel {
         if row >= <Columns as VariantArray>::VARIANTS.len() {
             return None;
         }
-        match <Columns as VariantArray>::VARIANTS[row] {
-            Columns::Datetime => Some(StandardListViewItem::from(SharedString::from(
-                self.array.timestamp.to_datetime_detail(),
-            ))),
-            Columns::ProcessName => Some(StandardListViewItem::from(SharedString::from(
-                self.get_process_name(),
-            ))),
-            Columns::ProcessId => Some(StandardListViewItem::from(SharedString::from(
-                (self.array.process_id as i32).to_string(),
-            ))),
-            Columns::ThreadId => Some(StandardListViewItem::from(SharedString::from(
-                (self.array.thread_id as i32).to_string(),
-            ))),
-            Columns::EventName => Some(StandardListViewItem::from(SharedString::from(
-                self.array.get_event_display_name().to_string(),
-            ))),
-            Columns::OpcodeName => Some(StandardListViewItem::from(SharedString::from(
-                self.array.opcode_name.to_string(),
-            ))),
-            Columns::Properties => Some(StandardListViewItem::from(SharedString::from(
-                serde_json::to_string(&self.array.properties).unwrap_or_default(),
-            ))),
+        let mut text = self.column_text(&<Columns as VariantArray>::VARIANTS[row]);
+        // the bookmark is marked at the first column
+        if row == 0 && self.is_bookmarked() {
+            text.insert_str(0, "\u{2605} ");
         }
+        Some(StandardListViewItem::from(SharedString::from(text)))
     }
 
     fn set_row_data(&self, #[allow(unused)] row: usize, #[allow(unused)] data: Self::Data) {
@@ -442,6 +569,152 @@ impl Model for EventRecordModel {
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct StackWalkSymbolized {
+    pub event_timestamp: i64,
+    pub process_id: i32,
+    pub thread_id: i32,
+    pub frames: Vec<StackFrameSymbolized>,
+}
+
+// the fields are same as the columns of stack view
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StackFrameSymbolized {
+    pub name: String,
+    pub address: String,
+    pub module_offset: String,
+    pub function_offset: String,
+    // the function_offset with the demangled name
+    pub function_offset_demangled: String,
+    pub line_offset: String,
+}
+
+const FRAME_PLACEHOLDER: &str = "<symbolizing>";
+
+// the frames before symbolizing. the request is None if the frame is not in a known module
+#[derive(Clone)]
+struct StackWalkUnsymbolized {
+    event_timestamp: i64,
+    process_id: i32,
+    thread_id: i32,
+    frames: Vec<(StackFrameSymbolized, Option<FrameRequest>)>,
+}
+
+impl StackWalkUnsymbolized {
+    fn new(stack_walk: &StackWalk) -> Self {
+        let frames = stack_walk
+            .stacks
+            .iter()
+            .map(|item| {
+                let mut frame = StackFrameSymbolized {
+                    name: item.0.clone(),
+                    address: format!("{:#x}", item.1.raw),
+                    ..Default::default()
+                };
+                let Some(relative) = item.1.relative else {
+                    return (frame, None);
+                };
+                let Some(module_info) = process_modules::get_module_info_by_id(relative.0) else {
+                    frame.module_offset = format!("{:#x}", relative.0);
+                    frame.function_offset = format!("{:#x}", relative.1);
+                    frame.function_offset_demangled = frame.function_offset.clone();
+                    return (frame, None);
+                };
+                let file_name = module_info.get_module_name();
+                frame.module_offset = format!("{file_name}+{:#x}", relative.1);
+                let request = FrameRequest {
+                    module_path: PathBuf::from(module_info.file_name.as_str()),
+                    time_date_stamp: module_info.time_data_stamp,
+                    code_view: module_info.code_view.clone(),
+                    offset: relative.1,
+                };
+                (frame, Some(request))
+            })
+            .collect();
+        Self {
+            event_timestamp: stack_walk.event_timestamp,
+            process_id: stack_walk.stack_process as i32,
+            thread_id: stack_walk.stack_thread as i32,
+            frames,
+        }
+    }
+
+    fn requests(&self) -> impl Iterator<Item = &FrameRequest> {
+        self.frames
+            .iter()
+            .filter_map(|(_, request)| request.as_ref())
+    }
+
+    /// get_locations: called for the requests in order. the frame is a placeholder if it returns None
+    fn symbolized(
+        self,
+        mut get_locations: impl FnMut(&FrameRequest) -> Option<Locations>,
+    ) -> StackWalkSymbolized {
+        // a return address is expanded to the frames of inlined functions
+        let frames = self
+            .frames
+            .into_iter()
+            .flat_map(|(frame, request)| {
+                let Some(request) = request else {
+                    return vec![frame];
+                };
+                let Some(locations) = get_locations(&request) else {
+                    return vec![StackFrameSymbolized {
+                        function_offset: FRAME_PLACEHOLDER.to_string(),
+                        function_offset_demangled: FRAME_PLACEHOLDER.to_string(),
+                        ..frame
+                    }];
+                };
+                locations
+                    .into_iter()
+                    .map(|(function_offset, line_offset)| StackFrameSymbolized {
+                        function_offset_demangled: demangle::demangle_location(
+                            function_offset.as_str(),
+                        ),
+                        function_offset,
+                        line_offset,
+                        ..frame.clone()
+                    })
+                    .collect()
+            })
+            .collect();
+        StackWalkSymbolized {
+            event_timestamp: self.event_timestamp,
+            process_id: self.process_id,
+            thread_id: self.thread_id,
+            frames,
+        }
+    }
+}
+
+/// The stacks for the stack view. demangled: show the demangled or raw function names
+pub fn stack_walk_info(stack_walk: Option<StackWalkSymbolized>, demangled: bool) -> StackWalkInfo {
+    let Some(stack) = stack_walk else {
+        return StackWalkInfo::default();
+    };
+    let vec = VecModel::default();
+    for frame in stack.frames {
+        let function_offset = if demangled {
+            frame.function_offset_demangled
+        } else {
+            frame.function_offset
+        };
+        vec.push(ModelRc::from([
+            StandardListViewItem::from(SharedString::from(frame.name)),
+            StandardListViewItem::from(SharedString::from(frame.address)),
+            StandardListViewItem::from(SharedString::from(frame.module_offset)),
+            StandardListViewItem::from(SharedString::from(function_offset)),
+            StandardListViewItem::from(SharedString::from(frame.line_offset)),
+        ]));
+    }
+    StackWalkInfo {
+        event_timestamp: SharedString::from(stack.event_timestamp.to_string()),
+        process_id: SharedString::from(format!("{}", stack.process_id)),
+        thread_id: SharedString::from(format!("{}", stack.thread_id)),
+        stacks: ModelRc::new(vec),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, VariantArray, AsRefStr)]
 #[strum(serialize_all = "snake_case")]
 pub enum Columns {
@@ -449,8 +722,16 @@ pub enum Columns {
     ProcessName,
     ProcessId,
     ThreadId,
+    Cpu,
     EventName,
     OpcodeName,
+    KernelTime,
+    UserTime,
+    ActivityId,
+    RelatedActivityId,
+    HeaderFlags,
+    EventProperty,
+    Extended,
     Properties,
 }
 
@@ -478,6 +759,8 @@ mod tests {
         assert_eq!(Columns::ThreadId.as_ref(), "thread_id");
         assert_eq!(Columns::EventName.as_ref(), "event_name");
         assert_eq!(Columns::OpcodeName.as_ref(), "opcode_name");
+        assert_eq!(Columns::Cpu.as_ref(), "cpu");
+        assert_eq!(Columns::RelatedActivityId.as_ref(), "related_activity_id");
         assert_eq!(Columns::Properties.as_ref(), "properties");
     }
 }
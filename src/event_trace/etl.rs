@@ -0,0 +1,127 @@
+//! The events of the etl reader of the lib as the records of ProcessTrace. the reader is in the lib so it is
+//! tested without Windows
+use super::{StackAddress, StackWalk};
+pub use crate::etl::{EtlEvent, EtlReader, LogfileHeader};
+use anyhow::Result;
+use std::{ffi, mem};
+use windows::{core::GUID, Win32::System::Diagnostics::Etw::*};
+
+/// The EVENT_RECORD as it is passed by ProcessTrace. The user data is borrowed from the event
+pub fn event_record(event: &EtlEvent, logfile_header: &LogfileHeader) -> EVENT_RECORD {
+    let mut event_record = EVENT_RECORD::default();
+    let header = &mut event_record.EventHeader;
+    header.Size = mem::size_of::<EVENT_HEADER>() as u16;
+    header.Flags = event.header_flags();
+    header.EventProperty = event.event_property;
+    header.ThreadId = event.thread_id;
+    header.ProcessId = event.process_id;
+    header.TimeStamp = logfile_header.timestamp(event.timestamp);
+    header.ProviderId = GUID::from_u128(event.provider_id);
+    header.EventDescriptor = EVENT_DESCRIPTOR {
+        Id: event.descriptor.id,
+        Version: event.descriptor.version,
+        Channel: event.descriptor.channel,
+        Level: event.descriptor.level,
+        Opcode: event.descriptor.opcode,
+        Task: event.descriptor.task,
+        Keyword: event.descriptor.keyword,
+    };
+    header.ActivityId = GUID::from_u128(event.activity_id);
+    header.Anonymous = EVENT_HEADER_0 {
+        Anonymous: EVENT_HEADER_0_0 {
+            KernelTime: event.kernel_time,
+            UserTime: event.user_time,
+        },
+    };
+    event_record.BufferContext.Anonymous = ETW_BUFFER_CONTEXT_0 {
+        Anonymous: ETW_BUFFER_CONTEXT_0_0 {
+            ProcessorNumber: event.processor_number,
+            Alignment: 0,
+        },
+    };
+    event_record.UserDataLength = event.user_data.len() as u16;
+    event_record.UserData = event.user_data.as_ptr() as *mut ffi::c_void;
+    event_record
+}
+
+/// The StackWalk of the event, the event_timestamp is converted to the FILETIME
+pub fn stack_walk(event: &EtlEvent, logfile_header: &LogfileHeader) -> Result<StackWalk> {
+    let sw = event.stack_walk()?;
+    Ok(StackWalk {
+        event_timestamp: logfile_header.timestamp(sw.event_timestamp),
+        stack_process: sw.stack_process,
+        stack_thread: sw.stack_thread,
+        stacks: sw
+            .stacks
+            .into_iter()
+            .enumerate()
+            .map(|(i, raw)| {
+                (
+                    format!("Stack{}", i + 1),
+                    StackAddress {
+                        raw,
+                        relative: None,
+                    },
+                )
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::etl::{self as lib_etl, EVENT_HEADER_FLAG_64_BIT_HEADER};
+    use crate::event_trace::event_kernel;
+
+    #[test]
+    fn group_guids() {
+        for (group, guid) in [
+            (0x00, EventTraceGuid),
+            (0x03, ProcessGuid),
+            (0x05, ThreadGuid),
+            (0x07, event_kernel::JOB_GUID),
+            (0x0f, PerfInfoGuid),
+            (0x12, event_kernel::POWER_GUID),
+            (0x14, ImageLoadGuid),
+            (0x18, event_kernel::STACK_WALK_GUID),
+            (0x1b, SplitIoGuid),
+        ] {
+            assert_eq!(
+                lib_etl::guid_from_group(group).map(GUID::from_u128),
+                Some(guid)
+            );
+        }
+    }
+
+    #[test]
+    fn convert_event() {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/kernel.etl");
+        let mut reader = EtlReader::open(&path).unwrap();
+        let mut records = vec![];
+        while let Some(event) = reader.next_event().unwrap() {
+            let logfile_header = reader.logfile_header().unwrap();
+            let event_record = event_record(&event, logfile_header);
+            let header = event_record.EventHeader;
+            records.push((
+                header.ProviderId,
+                header.EventDescriptor.Opcode,
+                header.Flags,
+            ));
+            if header.ProviderId == event_kernel::STACK_WALK_GUID {
+                let sw = stack_walk(&event, logfile_header).unwrap();
+                assert_eq!(sw.event_timestamp, logfile_header.boot_time + 10_000_100);
+                assert_eq!(sw.stacks[1].0, "Stack2");
+                assert_eq!(sw.stacks[1].1.raw, 0x00007ffb01231234);
+            }
+        }
+        assert_eq!(records[1].0, ThreadGuid);
+        assert_eq!(records[1].1, 3);
+        assert_eq!(
+            records[2].0,
+            GUID::from_u128(0xedd08927_9cc4_4e65_b970_c2560fb5c289)
+        );
+        assert_eq!(records[2].2, EVENT_HEADER_FLAG_64_BIT_HEADER);
+    }
+}
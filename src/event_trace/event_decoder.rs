@@ -1,13 +1,28 @@
+use super::{
+    event_extended::ExtendedData,
+    event_mof,
+    event_payload::{PropertyRange, RawPayload},
+};
 use crate::third_extend::strings::*;
 use crate::third_extend::Guid;
 use crate::utils::TimeStamp;
 use anyhow::{anyhow, Result};
 use linked_hash_map::LinkedHashMap;
-use serde::{ser::SerializeStruct, Serialize, Serializer};
-use std::{convert::TryFrom, mem, slice};
+use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    slice,
+};
 use tracing::{debug, warn};
 use widestring::*;
-use windows::{core::PWSTR, Win32::Foundation::*, Win32::System::Diagnostics::Etw::*};
+use windows::{
+    core::{GUID, PWSTR},
+    Win32::Foundation::*,
+    Win32::System::Diagnostics::Etw::*,
+};
 
 pub struct Decoder<'a> {
     event_record: &'a EVENT_RECORD,
@@ -18,6 +33,7 @@ pub struct Decoder<'a> {
     user_data: &'a [u8],
     int_values: Vec<u16>,
     pointer_size: u32,
+    property_ranges: Vec<PropertyRange>,
 }
 
 impl<'a> Decoder<'a> {
@@ -82,13 +98,7 @@ impl<'a> Decoder<'a> {
             )
         };
         let int_values = vec![0u16; event_info.PropertyCount as usize];
-        let pointer_size = if (header.Flags as u32 & EVENT_HEADER_FLAG_32_BIT_HEADER) != 0 {
-            4u32
-        } else if (header.Flags as u32 & EVENT_HEADER_FLAG_64_BIT_HEADER) != 0 {
-            8u32
-        } else {
-            mem::size_of::<*const u8>() as u32
-        };
+        let pointer_size = pointer_size_from_flags(header.Flags);
         Ok(Self {
             event_record,
             _event_info_vec: event_info_vec,
@@ -98,6 +108,7 @@ impl<'a> Decoder<'a> {
             user_data,
             int_values,
             pointer_size,
+            property_ranges: vec![],
         })
     }
     pub fn decode(&mut self) -> Result<EventRecordDecoded> {
@@ -225,6 +236,9 @@ impl<'a> Decoder<'a> {
             process_id: header.ProcessId,
             thread_id: header.ThreadId,
             timestamp: TimeStamp(header.TimeStamp),
+            header_meta: EventHeaderMeta::from_event_record(self.event_record),
+            extended: ExtendedData::from_event_record(self.event_record),
+            raw: RawPayload::new(self.user_data, mem::take(&mut self.property_ranges)),
             properties,
         })
     }
@@ -345,14 +359,19 @@ impl<'a> Decoder<'a> {
                 let num_of_struct_members =
                     unsafe { property_info.Anonymous1.structType.NumOfStructMembers };
                 let struct_index_end = struct_start_index as u32 + num_of_struct_members as u32;
+                let property_begin = *user_data_index;
                 let r = self.decode_properties(
                     struct_start_index,
                     struct_index_end as u16,
                     user_data_index,
                 )?;
+                self.push_property_range(&property_name, property_begin, *user_data_index);
                 properties_object.insert(property_name, PropertyDecoded::Struct(r));
             } else {
+                let property_begin = *user_data_index;
                 let mut properties_array = Vec::<String>::new();
+                // only for the property that is not array and not mapped
+                let mut property_typed_op: Option<PropertyDecoded> = None;
                 // Treat non-array properties as arrays with one element.
                 let mut array_index = 0;
                 while array_index < array_count {
@@ -415,6 +434,8 @@ impl<'a> Decoder<'a> {
                     }
 
                     let mut prop_buffer = Vec::<u16>::new();
+                    // the range of user data formatted by TdhFormatProperty
+                    let mut consumed = 0..0;
 
                     if 0 == prop_length && in_type == TDH_INTYPE_NULL.0 as u16 {
                         // TdhFormatProperty doesn't handle INTYPE_NULL.
@@ -455,6 +476,8 @@ impl<'a> Decoder<'a> {
                             };
                             if status == ERROR_SUCCESS.0 {
                                 unsafe { prop_buffer.set_len((buffer_size / 2) as usize) };
+                                consumed = *user_data_index as usize
+                                    ..(*user_data_index + userdataconsumed) as usize;
                                 *user_data_index += userdataconsumed;
                                 break;
                             }
@@ -464,12 +487,15 @@ impl<'a> Decoder<'a> {
                             }
                             properties_array.push(format!(""));
                             if is_array {
-                                properties_object
-                                    .insert(property_name, PropertyDecoded::Array(properties_array));
+                                properties_object.insert(
+                                    property_name,
+                                    PropertyDecoded::Array(properties_array),
+                                );
                             } else {
                                 debug_assert!(properties_array.len() <= 1);
                                 if let Some(item) = properties_array.pop() {
-                                    properties_object.insert(property_name, PropertyDecoded::String(item));
+                                    properties_object
+                                        .insert(property_name, PropertyDecoded::String(item));
                                 }
                             }
                             return Err(PropertiesError{msg: format!("Failed to TdhFormatProperty: {status} pointer_size: {} in_type: {in_type} out_type: {out_type} prop_length: {prop_length} userdata len: {}  buffersize: {buffer_size} thread_id: {} timestamp: {}", 
@@ -481,17 +507,28 @@ impl<'a> Decoder<'a> {
                         .unwrap_or_default()
                         .to_string()
                         .unwrap_or_default();
+                    if !is_array && map_info.is_none() && !consumed.is_empty() {
+                        property_typed_op = property_typed(
+                            in_type,
+                            out_type,
+                            self.pointer_size,
+                            &self.user_data[consumed],
+                            s.as_str(),
+                        );
+                    }
                     properties_array.push(s);
 
                     array_index += 1;
                 }
+                self.push_property_range(&property_name, property_begin, *user_data_index);
                 if is_array {
                     properties_object
                         .insert(property_name, PropertyDecoded::Array(properties_array));
                 } else {
                     debug_assert!(properties_array.len() <= 1);
                     if let Some(item) = properties_array.pop() {
-                        properties_object.insert(property_name, PropertyDecoded::String(item));
+                        let property = property_typed_op.unwrap_or(PropertyDecoded::String(item));
+                        properties_object.insert(property_name, property);
                     }
                 }
             }
@@ -499,6 +536,125 @@ impl<'a> Decoder<'a> {
         }
         Ok(properties_object)
     }
+
+    fn push_property_range(&mut self, name: &str, begin: u16, end: u16) {
+        self.property_ranges.push(PropertyRange {
+            name: name.to_string(),
+            offset: begin as usize,
+            length: end.saturating_sub(begin) as usize,
+        });
+    }
+}
+
+/// The typed value of a property that is formatted by TdhFormatProperty.
+/// None if the type is not supported or the text of typed value is different from the formatted text
+fn property_typed(
+    in_type: u16,
+    out_type: u16,
+    pointer_size: u32,
+    data: &[u8],
+    text: &str,
+) -> Option<PropertyDecoded> {
+    fn bytes<const N: usize>(data: &[u8]) -> Option<[u8; N]> {
+        data.get(..N)?.try_into().ok()
+    }
+    let in_type = in_type as i32;
+    let out_type = out_type as i32;
+    let is_hex = [
+        TDH_OUTTYPE_HEXINT8,
+        TDH_OUTTYPE_HEXINT16,
+        TDH_OUTTYPE_HEXINT32,
+        TDH_OUTTYPE_HEXINT64,
+    ]
+    .iter()
+    .any(|t| t.0 == out_type);
+    let is_decimal = [
+        TDH_OUTTYPE_NULL,
+        TDH_OUTTYPE_BYTE,
+        TDH_OUTTYPE_UNSIGNEDBYTE,
+        TDH_OUTTYPE_SHORT,
+        TDH_OUTTYPE_UNSIGNEDSHORT,
+        TDH_OUTTYPE_INT,
+        TDH_OUTTYPE_UNSIGNEDINT,
+        TDH_OUTTYPE_LONG,
+        TDH_OUTTYPE_UNSIGNEDLONG,
+    ]
+    .iter()
+    .any(|t| t.0 == out_type);
+    // the other out types, i.e. string, errorcode, are kept as text
+    let int_format = if is_hex {
+        Some(IntFormat::Hex)
+    } else if is_decimal {
+        Some(IntFormat::Decimal)
+    } else {
+        None
+    };
+
+    let typed = if in_type == TDH_INTYPE_INT8.0 {
+        PropertyDecoded::Signed(i8::from_le_bytes(bytes(data)?) as i64, int_format?)
+    } else if in_type == TDH_INTYPE_INT16.0 {
+        PropertyDecoded::Signed(i16::from_le_bytes(bytes(data)?) as i64, int_format?)
+    } else if in_type == TDH_INTYPE_INT32.0 {
+        PropertyDecoded::Signed(i32::from_le_bytes(bytes(data)?) as i64, int_format?)
+    } else if in_type == TDH_INTYPE_INT64.0 {
+        PropertyDecoded::Signed(i64::from_le_bytes(bytes(data)?), int_format?)
+    } else if in_type == TDH_INTYPE_UINT8.0 {
+        PropertyDecoded::Unsigned(u8::from_le_bytes(bytes(data)?) as u64, int_format?)
+    } else if in_type == TDH_INTYPE_UINT16.0 {
+        if out_type == TDH_OUTTYPE_PORT.0 {
+            PropertyDecoded::Unsigned(u16::from_be_bytes(bytes(data)?) as u64, IntFormat::Decimal)
+        } else {
+            PropertyDecoded::Unsigned(u16::from_le_bytes(bytes(data)?) as u64, int_format?)
+        }
+    } else if in_type == TDH_INTYPE_UINT32.0 {
+        if out_type == TDH_OUTTYPE_IPV4.0 {
+            PropertyDecoded::IpAddr(IpAddr::V4(Ipv4Addr::from(bytes::<4>(data)?)))
+        } else {
+            PropertyDecoded::Unsigned(u32::from_le_bytes(bytes(data)?) as u64, int_format?)
+        }
+    } else if in_type == TDH_INTYPE_UINT64.0 {
+        PropertyDecoded::Unsigned(u64::from_le_bytes(bytes(data)?), int_format?)
+    } else if in_type == TDH_INTYPE_HEXINT32.0 {
+        PropertyDecoded::Unsigned(u32::from_le_bytes(bytes(data)?) as u64, IntFormat::Hex)
+    } else if in_type == TDH_INTYPE_HEXINT64.0 {
+        PropertyDecoded::Unsigned(u64::from_le_bytes(bytes(data)?), IntFormat::Hex)
+    } else if in_type == TDH_INTYPE_POINTER.0 {
+        if pointer_size == 4 {
+            PropertyDecoded::Pointer(u32::from_le_bytes(bytes(data)?) as u64)
+        } else {
+            PropertyDecoded::Pointer(u64::from_le_bytes(bytes(data)?))
+        }
+    } else if in_type == TDH_INTYPE_BOOLEAN.0 {
+        PropertyDecoded::Boolean(u32::from_le_bytes(bytes(data)?) != 0)
+    } else if in_type == TDH_INTYPE_GUID.0 {
+        let guid = bytes::<16>(data)?;
+        PropertyDecoded::Guid(Guid(GUID::from_values(
+            u32::from_le_bytes(bytes(&guid[0..])?),
+            u16::from_le_bytes(bytes(&guid[4..])?),
+            u16::from_le_bytes(bytes(&guid[6..])?),
+            bytes(&guid[8..])?,
+        )))
+    } else if in_type == TDH_INTYPE_SID.0 || in_type == TDH_INTYPE_WBEMSID.0 {
+        PropertyDecoded::Sid(text.to_string())
+    } else if in_type == TDH_INTYPE_FILETIME.0 {
+        // the text of tdh depends on the locale, so it is kept as is
+        PropertyDecoded::FileTime(
+            TimeStamp(i64::from_le_bytes(bytes(data)?)),
+            text.to_string(),
+        )
+    } else if in_type == TDH_INTYPE_BINARY.0 {
+        if out_type == TDH_OUTTYPE_IPV6.0 {
+            PropertyDecoded::IpAddr(IpAddr::V6(Ipv6Addr::from(bytes::<16>(data)?)))
+        } else {
+            PropertyDecoded::Binary(data.to_vec())
+        }
+    } else {
+        return None;
+    };
+    if typed.text()?.as_ref() != text {
+        return None;
+    }
+    Some(typed)
 }
 
 #[inline]
@@ -506,6 +662,66 @@ pub fn is_string_event(flag: u16) -> bool {
     (flag & EVENT_HEADER_FLAG_STRING_ONLY as u16) != 0
 }
 
+fn pointer_size_from_flags(flags: u16) -> u32 {
+    if (flags as u32 & EVENT_HEADER_FLAG_32_BIT_HEADER) != 0 {
+        4u32
+    } else if (flags as u32 & EVENT_HEADER_FLAG_64_BIT_HEADER) != 0 {
+        8u32
+    } else {
+        mem::size_of::<*const u8>() as u32
+    }
+}
+
+/// Decode the classic kernel event by the layouts of event_mof without tdh.
+/// None if the event is not covered by the layouts.
+pub fn decode_mof_event(event_record: &EVENT_RECORD) -> Option<Result<EventRecordDecoded>> {
+    let header = &event_record.EventHeader;
+    if (header.Flags as u32 & EVENT_HEADER_FLAG_CLASSIC_HEADER) == 0 {
+        return None;
+    }
+    let user_data = unsafe {
+        slice::from_raw_parts(
+            event_record.UserData as *const u8,
+            event_record.UserDataLength as usize,
+        )
+    };
+    let mut property_ranges = vec![];
+    let (event_name, opcode_name, r) = event_mof::decode(
+        &header.ProviderId,
+        header.EventDescriptor.Opcode,
+        header.EventDescriptor.Version,
+        pointer_size_from_flags(header.Flags),
+        user_data,
+        &mut property_ranges,
+    )?;
+    let properties = match r {
+        Ok(map) => PropertyDecoded::Struct(map),
+        Err(e) => return Some(Err(anyhow!(e))),
+    };
+    Some(Ok(EventRecordDecoded {
+        provider_id: Guid(header.ProviderId),
+        event_guid: Guid(header.ProviderId),
+        event_descriptor: EventDescriptor(header.EventDescriptor),
+        decoding_source: DecodingSource::DecodingSourceWbem,
+        provider_name: event_mof::PROVIDER_NAME.to_string(),
+        level_name: "".to_string(),
+        channel_name: "".to_string(),
+        keywords_name: "".to_string(),
+        event_display_name: None,
+        event_name: event_name.to_string(),
+        opcode_name: opcode_name.to_string(),
+        event_message: "".to_string(),
+        provider_message: "".to_string(),
+        process_id: header.ProcessId,
+        thread_id: header.ThreadId,
+        timestamp: TimeStamp(header.TimeStamp),
+        header_meta: EventHeaderMeta::from_event_record(event_record),
+        extended: ExtendedData::from_event_record(event_record),
+        raw: RawPayload::new(user_data, property_ranges),
+        properties,
+    }))
+}
+
 pub fn decode_kernel_event(
     event_record: &EVENT_RECORD,
     event_name: &str,
@@ -525,6 +741,7 @@ pub fn decode_kernel_event(
         )
     };
     let properties = PropertyDecoded::String(hex::encode(user_data));
+    let raw = RawPayload::new(user_data, vec![]);
     EventRecordDecoded {
         provider_id,
         event_guid,
@@ -542,11 +759,14 @@ pub fn decode_kernel_event(
         process_id: event_record.EventHeader.ProcessId,
         thread_id: event_record.EventHeader.ThreadId,
         timestamp: TimeStamp(event_record.EventHeader.TimeStamp),
+        header_meta: EventHeaderMeta::from_event_record(event_record),
+        extended: ExtendedData::from_event_record(event_record),
+        raw,
         properties,
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventRecordDecoded {
     pub provider_id: Guid,
     pub event_guid: Guid,
@@ -566,6 +786,13 @@ pub struct EventRecordDecoded {
     #[serde(with = "serde_custom_u32")]
     pub thread_id: u32,
     pub timestamp: TimeStamp,
+    #[serde(default)]
+    pub header_meta: EventHeaderMeta,
+    #[serde(default, skip_serializing_if = "ExtendedData::is_empty")]
+    pub extended: ExtendedData,
+    // only for the hex view. it is not in the exports, sessions and recordings
+    #[serde(skip)]
+    pub raw: RawPayload,
     pub properties: PropertyDecoded,
 }
 
@@ -583,12 +810,175 @@ impl EventRecordDecoded {
     }
 }
 
-#[derive(Debug, Serialize)]
-#[serde(untagged)]
+/// The fields of EVENT_HEADER and ETW_BUFFER_CONTEXT that are not in the EventRecordDecoded
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EventHeaderMeta {
+    pub processor_number: u16,
+    pub kernel_time: u32,
+    pub user_time: u32,
+    pub activity_id: Guid,
+    pub flags: u16,
+    pub event_property: u16,
+}
+
+impl EventHeaderMeta {
+    pub fn from_event_record(event_record: &EVENT_RECORD) -> Self {
+        let header = &event_record.EventHeader;
+        // the ProcessorTime is in the union instead of KernelTime and UserTime
+        let no_cpu_time = (header.Flags
+            & (EVENT_HEADER_FLAG_PRIVATE_SESSION | EVENT_HEADER_FLAG_NO_CPUTIME) as u16)
+            != 0;
+        let (kernel_time, user_time) = if no_cpu_time {
+            (0, 0)
+        } else {
+            unsafe {
+                (
+                    header.Anonymous.Anonymous.KernelTime,
+                    header.Anonymous.Anonymous.UserTime,
+                )
+            }
+        };
+        Self {
+            processor_number: unsafe {
+                event_record
+                    .BufferContext
+                    .Anonymous
+                    .Anonymous
+                    .ProcessorNumber as u16
+            },
+            kernel_time,
+            user_time,
+            activity_id: Guid(header.ActivityId),
+            flags: header.Flags,
+            event_property: header.EventProperty,
+        }
+    }
+}
+
+/// The typed variants are serialized as the text of TdhFormatProperty, so the json is same as the text only.
+/// The json is deserialized to String, Array and Struct.
+#[derive(Debug, Clone)]
 pub enum PropertyDecoded {
     String(String),
     Array(Vec<String>),
     Struct(LinkedHashMap<String, PropertyDecoded>),
+    Signed(i64, IntFormat),
+    Unsigned(u64, IntFormat),
+    Pointer(u64),
+    Boolean(bool),
+    Guid(Guid),
+    Sid(String),
+    IpAddr(IpAddr),
+    // the text of TdhFormatProperty
+    FileTime(TimeStamp, String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntFormat {
+    Decimal,
+    Hex,
+}
+
+impl PropertyDecoded {
+    /// The text as TdhFormatProperty. None for Array and Struct
+    pub fn text(&self) -> Option<Cow<'_, str>> {
+        let s = match self {
+            Self::String(s) | Self::Sid(s) | Self::FileTime(_, s) => {
+                return Some(Cow::Borrowed(s.as_str()))
+            }
+            Self::Array(_) | Self::Struct(_) => return None,
+            Self::Signed(num, IntFormat::Decimal) => num.to_string(),
+            Self::Signed(num, IntFormat::Hex) => format!("0x{num:X}"),
+            Self::Unsigned(num, IntFormat::Decimal) => num.to_string(),
+            Self::Unsigned(num, IntFormat::Hex) | Self::Pointer(num) => format!("0x{num:X}"),
+            Self::Boolean(b) => b.to_string(),
+            Self::Guid(guid) => format!("{{{:?}}}", guid.0),
+            Self::IpAddr(ip) => ip.to_string(),
+            Self::Binary(bytes) => format!("0x{}", hex::encode_upper(bytes)),
+        };
+        Some(Cow::Owned(s))
+    }
+
+    /// The name of variant. i.e. for the schema of events
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "String",
+            Self::Array(_) => "Array",
+            Self::Struct(_) => "Struct",
+            Self::Signed(..) => "Signed",
+            Self::Unsigned(..) => "Unsigned",
+            Self::Pointer(_) => "Pointer",
+            Self::Boolean(_) => "Boolean",
+            Self::Guid(_) => "Guid",
+            Self::Sid(_) => "Sid",
+            Self::IpAddr(_) => "IpAddr",
+            Self::FileTime(..) => "FileTime",
+            Self::Binary(_) => "Binary",
+        }
+    }
+
+    /// The unsigned value of integer and pointer. The text is parsed as decimal or hex with 0x
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Unsigned(num, _) | Self::Pointer(num) => Some(*num),
+            Self::Signed(num, _) => Some(*num as u64),
+            Self::Boolean(b) => Some(*b as u64),
+            Self::String(s) => {
+                if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                    u64::from_str_radix(hex, 16).ok()
+                } else {
+                    s.parse::<u64>()
+                        .ok()
+                        .or_else(|| s.parse::<i64>().ok().map(|num| num as u64))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The signed value of integer and FILETIME. The unsigned is reinterpreted as signed
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Signed(num, _) => Some(*num),
+            Self::FileTime(ts, _) => Some(ts.0),
+            Self::String(s) if !s.starts_with("0x") && !s.starts_with("0X") => s.parse().ok(),
+            _ => self.as_u64().map(|num| num as i64),
+        }
+    }
+}
+
+impl Serialize for PropertyDecoded {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Array(array) => array.serialize(serializer),
+            Self::Struct(map) => map.serialize(serializer),
+            _ => serializer.serialize_str(self.text().unwrap_or_default().as_ref()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PropertyDecoded {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Text {
+            String(String),
+            Array(Vec<String>),
+            Struct(LinkedHashMap<String, PropertyDecoded>),
+        }
+        Ok(match Text::deserialize(deserializer)? {
+            Text::String(s) => Self::String(s),
+            Text::Array(array) => Self::Array(array),
+            Text::Struct(map) => Self::Struct(map),
+        })
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -598,7 +988,7 @@ pub struct PropertiesError {
     pub properties: LinkedHashMap<String, PropertyDecoded>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EventDescriptor(pub EVENT_DESCRIPTOR);
 
 impl Serialize for EventDescriptor {
@@ -618,7 +1008,36 @@ impl Serialize for EventDescriptor {
     }
 }
 
-#[derive(Debug, Serialize)]
+impl<'de> Deserialize<'de> for EventDescriptor {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Fields {
+            id: u16,
+            version: u8,
+            channel: u8,
+            level: u8,
+            opcode: u8,
+            task: u16,
+            keyword: u64,
+        }
+        let fields = Fields::deserialize(deserializer)?;
+        Ok(EventDescriptor(EVENT_DESCRIPTOR {
+            Id: fields.id,
+            Version: fields.version,
+            Channel: fields.channel,
+            Level: fields.level,
+            Opcode: fields.opcode,
+            Task: fields.task,
+            Keyword: fields.keyword,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(unused)]
 #[repr(u8)]
 pub enum DecodingSource {
@@ -667,4 +1086,191 @@ mod serde_custom_u32 {
     {
         serializer.serialize_i32(*date as i32)
     }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> core::result::Result<u32, D::Error>
+    where
+        D: super::Deserializer<'de>,
+    {
+        let num = <i32 as super::Deserialize>::deserialize(deserializer)?;
+        Ok(num as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn property_text() {
+        assert_eq!(
+            PropertyDecoded::Unsigned(500, IntFormat::Hex)
+                .text()
+                .unwrap(),
+            "0x1F4"
+        );
+        assert_eq!(
+            PropertyDecoded::Signed(-1, IntFormat::Decimal)
+                .text()
+                .unwrap(),
+            "-1"
+        );
+        assert_eq!(
+            PropertyDecoded::Pointer(0xfffff80000000000).text().unwrap(),
+            "0xFFFFF80000000000"
+        );
+        assert_eq!(
+            PropertyDecoded::IpAddr(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+                .text()
+                .unwrap(),
+            "127.0.0.1"
+        );
+        assert_eq!(
+            PropertyDecoded::Binary(vec![0xab, 0x01]).text().unwrap(),
+            "0xAB01"
+        );
+        assert!(PropertyDecoded::Array(vec![]).text().is_none());
+    }
+
+    #[test]
+    fn property_as_integer() {
+        assert_eq!(PropertyDecoded::String("0x1F4".into()).as_u64(), Some(500));
+        assert_eq!(PropertyDecoded::String("-2".into()).as_i64(), Some(-2));
+        assert_eq!(
+            PropertyDecoded::Unsigned(500, IntFormat::Hex).as_i64(),
+            Some(500)
+        );
+        assert_eq!(PropertyDecoded::Sid("S-1-5-18".into()).as_u64(), None);
+    }
+
+    #[test]
+    fn property_typed_as_tdh_text() {
+        let data = 133627865451234567i64.to_le_bytes();
+        // the text of tdh depends on the locale
+        let text = "\u{200e}2024\u{200e}-\u{200e}06\u{200e}-\u{200e}14T08:15:45.123456700Z";
+        let typed = property_typed(
+            TDH_INTYPE_FILETIME.0 as u16,
+            TDH_OUTTYPE_DATETIME.0 as u16,
+            8,
+            &data,
+            text,
+        )
+        .unwrap();
+        assert_eq!(typed.as_i64(), Some(133627865451234567));
+        assert_eq!(
+            serde_json::to_string(&typed).unwrap(),
+            serde_json::to_string(text).unwrap()
+        );
+        // the text is different from tdh
+        assert!(property_typed(
+            TDH_INTYPE_UINT32.0 as u16,
+            TDH_OUTTYPE_UNSIGNEDINT.0 as u16,
+            8,
+            &500u32.to_le_bytes(),
+            "0x1F4",
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn property_serde() {
+        let mut map = LinkedHashMap::new();
+        map.insert(
+            "ProcessId".to_string(),
+            PropertyDecoded::Unsigned(500, IntFormat::Hex),
+        );
+        map.insert(
+            "Name".to_string(),
+            PropertyDecoded::String("a.exe".to_string()),
+        );
+        let json = serde_json::to_string(&PropertyDecoded::Struct(map)).unwrap();
+        assert_eq!(json, r#"{"ProcessId":"0x1F4","Name":"a.exe"}"#);
+
+        let PropertyDecoded::Struct(map) = serde_json::from_str(&json).unwrap() else {
+            panic!("not a struct");
+        };
+        assert!(matches!(&map["ProcessId"], PropertyDecoded::String(s) if s == "0x1F4"));
+    }
+
+    fn classic_event_record(guid: GUID, opcode: u8, version: u8, user_data: &[u8]) -> EVENT_RECORD {
+        let mut event_record = EVENT_RECORD::default();
+        let header = &mut event_record.EventHeader;
+        header.Size = mem::size_of::<EVENT_HEADER>() as u16;
+        header.Flags = (EVENT_HEADER_FLAG_CLASSIC_HEADER | EVENT_HEADER_FLAG_64_BIT_HEADER) as u16;
+        header.ProcessId = 4;
+        header.ThreadId = 16;
+        header.TimeStamp = 133627865451234567;
+        header.ProviderId = guid;
+        header.EventDescriptor.Opcode = opcode;
+        header.EventDescriptor.Version = version;
+        event_record.UserDataLength = user_data.len() as u16;
+        event_record.UserData = user_data.as_ptr() as *mut std::ffi::c_void;
+        event_record
+    }
+
+    fn wide(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(|c| c.to_le_bytes())
+            .collect()
+    }
+
+    // the covered events are decoded by the layouts same as the tdh of current machine
+    #[test]
+    fn mof_same_as_tdh() {
+        let mut process_start = vec![];
+        process_start.extend_from_slice(&0xffffc68a5a1e8080u64.to_le_bytes());
+        process_start.extend_from_slice(&0x1f4u32.to_le_bytes());
+        process_start.extend_from_slice(&0x4u32.to_le_bytes());
+        process_start.extend_from_slice(&1u32.to_le_bytes());
+        process_start.extend_from_slice(&259i32.to_le_bytes());
+        process_start.extend_from_slice(&0x1aa000u64.to_le_bytes());
+        process_start.extend_from_slice(&0u32.to_le_bytes());
+        process_start.extend_from_slice(&0xffffc68a5a1e8100u64.to_le_bytes());
+        process_start.extend_from_slice(&0u64.to_le_bytes());
+        process_start.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 5]);
+        process_start.extend_from_slice(&18u32.to_le_bytes());
+        process_start.extend_from_slice(b"notepad.exe\0");
+        process_start.extend_from_slice(&wide("notepad.exe a.txt"));
+        process_start.extend_from_slice(&wide(""));
+        process_start.extend_from_slice(&wide(""));
+
+        let mut registry_open = vec![];
+        registry_open.extend_from_slice(&(-1i64).to_le_bytes());
+        registry_open.extend_from_slice(&0u32.to_le_bytes());
+        registry_open.extend_from_slice(&0u32.to_le_bytes());
+        registry_open.extend_from_slice(&0xffffa20f3c1d0000u64.to_le_bytes());
+        registry_open.extend_from_slice(&wide("\\Registry\\Machine\\SOFTWARE"));
+
+        let mut udp_send = vec![];
+        udp_send.extend_from_slice(&4u32.to_le_bytes());
+        udp_send.extend_from_slice(&100u32.to_le_bytes());
+        udp_send.extend_from_slice(&[10, 0, 0, 1]);
+        udp_send.extend_from_slice(&[192, 168, 1, 2]);
+        udp_send.extend_from_slice(&53u16.to_be_bytes());
+        udp_send.extend_from_slice(&50000u16.to_be_bytes());
+        udp_send.extend_from_slice(&0u32.to_le_bytes());
+        udp_send.extend_from_slice(&0u64.to_le_bytes());
+
+        let mut file_name = vec![];
+        file_name.extend_from_slice(&0xffffc68a5a1e9000u64.to_le_bytes());
+        file_name.extend_from_slice(&wide("\\Device\\HarddiskVolume3\\a.txt"));
+
+        for (guid, opcode, version, user_data) in [
+            (ProcessGuid, 1, 4, process_start),
+            (RegistryGuid, 11, 2, registry_open),
+            (UdpIpGuid, 10, 2, udp_send),
+            (FileIoGuid, 0, 2, file_name),
+        ] {
+            let event_record = classic_event_record(guid, opcode, version, &user_data);
+            let tdh = Decoder::new(&event_record)
+                .and_then(|mut decoder| decoder.decode())
+                .unwrap();
+            let mof = decode_mof_event(&event_record).unwrap().unwrap();
+            assert_eq!(
+                serde_json::to_value(&mof).unwrap(),
+                serde_json::to_value(&tdh).unwrap(),
+                "{guid:?} {opcode}"
+            );
+        }
+    }
 }
@@ -0,0 +1,510 @@
+//! The event definitions of user. It is a toml or json file that can rename or add the minors, add the majors
+//! and declare the layout of properties. It is merged with the EVENTS_DESC at startup.
+//! The flag of a major is the value of `Major`. i.e. 0x80100000 of the group mask FltIo
+//! i.e.
+//! ```toml
+//! [[minors]]
+//! major = "PerfInfo"
+//! op_code = 66
+//! name = "ThreadedDpc"
+//!
+//! [[minors]]
+//! major = "PerfInfo"
+//! op_code = 130
+//! name = "Unknown130"
+//! versions = [2]
+//! properties = [{ name = "Routine", type = "Pointer" }]
+//!
+//! [[majors]]
+//! name = "FileIo"
+//! display_name = "FileIo Flt Io"
+//! guid = "90CBDC39-4A3E-11D1-84F4-0000F80464E3"
+//! flag = 2148532224
+//! minors = [{ op_code = 105, name = "Unknown105" }]
+//! ```
+use super::{
+    event_decoder::EventRecordDecoded,
+    event_kernel::{self, EventsDescribe, MajorDescribe, MinorDescribe},
+    event_mof::{self, MofClass, MofProperty, MofType},
+};
+use crate::third_extend::Guid;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path, sync::OnceLock};
+use tracing::{info, warn};
+use windows::core::GUID;
+
+// the first existing file is loaded
+pub const DEFINITIONS_FILES: &[&str] = &["./events.toml", "./events.json"];
+
+// the merged events and classes borrow the names from the file. they are kept until exit
+static FILE: OnceLock<DefinitionFile> = OnceLock::new();
+static MERGED: OnceLock<Merged> = OnceLock::new();
+static EVENTS: OnceLock<Vec<EventsDescribe>> = OnceLock::new();
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DefinitionFile {
+    #[serde(default)]
+    pub minors: Vec<MinorDefinition>,
+    #[serde(default)]
+    pub majors: Vec<MajorDefinition>,
+}
+
+/// Rename the minor of a existing major, or add it if the op_code is not existing
+#[derive(Debug, Deserialize)]
+pub struct MinorDefinition {
+    // the display name or the name of major
+    pub major: String,
+    #[serde(flatten)]
+    pub minor: MinorLayout,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MajorDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    pub guid: Guid,
+    pub flag: u32,
+    #[serde(default)]
+    pub minors: Vec<MinorLayout>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MinorLayout {
+    // the classic events have a u8 op code. the greater is reported as a error
+    pub op_code: u32,
+    pub name: String,
+    // the versions of the layout. empty for all versions
+    #[serde(default)]
+    pub versions: Vec<u8>,
+    // empty for using the tdh
+    #[serde(default)]
+    pub properties: Vec<PropertyDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PropertyDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub mof_type: MofType,
+}
+
+/// The definitions merged to the built-in events
+pub struct Merged {
+    majors: Vec<MergedMajor>,
+    layouts: Vec<Layout>,
+    // the errors of validation, the conflicting definitions are ignored
+    pub errors: Vec<String>,
+}
+
+struct MergedMajor {
+    configurable: bool,
+    name: &'static str,
+    display_name: Option<&'static str>,
+    flag: u32,
+    minors: Vec<MinorDescribe>,
+    guid: GUID,
+}
+
+// the owner of the slices of a user MofClass
+struct Layout {
+    guid: GUID,
+    name: &'static str,
+    opcodes: [u8; 1],
+    opcode_names: [&'static str; 1],
+    versions: &'static [u8],
+    properties: Vec<MofProperty>,
+}
+
+/// The events of built-in and user definitions. It is EVENTS_DESC if no user definitions
+pub fn events_desc() -> &'static [EventsDescribe] {
+    EVENTS
+        .get()
+        .map(Vec::as_slice)
+        .unwrap_or(event_kernel::EVENTS_DESC)
+}
+
+/// Name the decoded event by the events. The tdh and the built-in classes decode the renamed minors
+/// with the old names
+pub fn name_decoded(
+    events: &[EventsDescribe],
+    (major_index, minor_index): (usize, usize),
+    event_record_decoded: &mut EventRecordDecoded,
+) {
+    let desc = &events[major_index];
+    if let Some(display_name) = desc.major.display_name {
+        event_record_decoded.set_event_display_name(display_name);
+    }
+    event_record_decoded.opcode_name = desc.minors[minor_index].name.to_string();
+}
+
+/// Load the definition file if it exists. Must be called before the events_desc is used.
+/// Returns the errors of validation, the conflicting definitions are ignored
+pub fn init(path: &Path) -> Result<Vec<String>> {
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+    if MERGED.get().is_some() {
+        return Err(anyhow!("The event definitions has been initialized"));
+    }
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let file = parse(path, content.as_str())
+        .map_err(|e| anyhow!("Failed to parse {}: {e}", path.display()))?;
+    let file = FILE.get_or_init(|| file);
+    let merged = MERGED.get_or_init(|| merge(event_kernel::EVENTS_DESC, file));
+    for e in merged.errors.iter() {
+        warn!("{}: {e}", path.display());
+    }
+    let events = EVENTS.get_or_init(|| merged.events());
+    let classes = merged.classes();
+    info!(
+        "loaded the event definitions: {}. {} majors, {} layouts",
+        path.display(),
+        events.len(),
+        classes.len()
+    );
+    event_mof::set_user_classes(classes);
+    Ok(merged.errors.clone())
+}
+
+/// Parse the toml file by the extension, otherwise the json
+pub fn parse(path: &Path, content: &str) -> Result<DefinitionFile> {
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    if is_toml {
+        Ok(toml::from_str(content)?)
+    } else {
+        Ok(serde_json::from_str(content)?)
+    }
+}
+
+/// Merge the definitions to the built-in events. The names are borrowed from the file
+pub fn merge(builtin: &'static [EventsDescribe], file: &'static DefinitionFile) -> Merged {
+    let mut errors = vec![];
+    let mut layouts = vec![];
+    let mut majors: Vec<MergedMajor> = builtin
+        .iter()
+        .map(|desc| MergedMajor {
+            configurable: desc.configurable,
+            name: desc.major.name,
+            display_name: desc.major.display_name,
+            flag: desc.major.flag,
+            minors: desc
+                .minors
+                .iter()
+                .map(|minor| MinorDescribe {
+                    name: minor.name,
+                    op_code: minor.op_code,
+                })
+                .collect(),
+            guid: desc.guid,
+        })
+        .collect();
+    // the owner of (guid, op_code). for checking the conflict
+    let mut opcode_map: HashMap<(GUID, u32), String> = HashMap::new();
+    for major in majors.iter() {
+        for minor in major.minors.iter() {
+            opcode_map
+                .entry((major.guid, minor.op_code))
+                .or_insert_with(|| format!("{}/{}", major.display(), minor.name));
+        }
+    }
+
+    for definition in file.minors.iter() {
+        let major_name = definition.major.to_ascii_lowercase();
+        let found = majors
+            .iter()
+            .position(|major| {
+                major
+                    .display_name
+                    .is_some_and(|name| name.to_ascii_lowercase() == major_name)
+            })
+            .or_else(|| {
+                majors
+                    .iter()
+                    .position(|major| major.name.to_ascii_lowercase() == major_name)
+            });
+        let Some(index) = found else {
+            errors.push(format!(
+                "No the major: {} for the minor: {}",
+                definition.major, definition.minor.name
+            ));
+            continue;
+        };
+        let major = &mut majors[index];
+        let layout = &definition.minor;
+        let owner = format!("{}/{}", major.display(), layout.name);
+        let Ok(op_code) = u8::try_from(layout.op_code) else {
+            errors.push(out_of_range(layout.op_code, &owner));
+            continue;
+        };
+        if let Some(minor) = major
+            .minors
+            .iter_mut()
+            .find(|minor| minor.op_code == layout.op_code)
+        {
+            minor.name = layout.name.as_str();
+        } else {
+            if let Some(other) = opcode_map.get(&(major.guid, layout.op_code)) {
+                errors.push(conflicting(layout.op_code, &owner, other));
+                continue;
+            }
+            opcode_map.insert((major.guid, layout.op_code), owner);
+            major.minors.push(MinorDescribe {
+                name: layout.name.as_str(),
+                op_code: layout.op_code,
+            });
+        }
+        layouts.extend(Layout::new(major.guid, major.name, op_code, layout));
+    }
+
+    for definition in file.majors.iter() {
+        let guid = definition.guid.0;
+        let name = definition.name.as_str();
+        let display_name = definition.display_name.as_deref();
+        let display = display_name.unwrap_or(name);
+        if majors
+            .iter()
+            .any(|major| major.display().eq_ignore_ascii_case(display))
+        {
+            errors.push(format!("The major: {display} is existing"));
+            continue;
+        }
+        let mut minors = vec![];
+        for layout in definition.minors.iter() {
+            let owner = format!("{display}/{}", layout.name);
+            let Ok(op_code) = u8::try_from(layout.op_code) else {
+                errors.push(out_of_range(layout.op_code, &owner));
+                continue;
+            };
+            if let Some(other) = opcode_map.get(&(guid, layout.op_code)) {
+                errors.push(conflicting(layout.op_code, &owner, other));
+                continue;
+            }
+            opcode_map.insert((guid, layout.op_code), owner);
+            minors.push(MinorDescribe {
+                name: layout.name.as_str(),
+                op_code: layout.op_code,
+            });
+            layouts.extend(Layout::new(guid, name, op_code, layout));
+        }
+        majors.push(MergedMajor {
+            configurable: true,
+            name,
+            display_name,
+            flag: definition.flag,
+            minors,
+            guid,
+        });
+    }
+    Merged {
+        majors,
+        layouts,
+        errors,
+    }
+}
+
+impl Merged {
+    pub fn events(&'static self) -> Vec<EventsDescribe> {
+        self.majors
+            .iter()
+            .map(|major| EventsDescribe {
+                configurable: major.configurable,
+                major: MajorDescribe {
+                    name: major.name,
+                    display_name: major.display_name,
+                    flag: major.flag,
+                },
+                minors: major.minors.as_slice(),
+                guid: major.guid,
+            })
+            .collect()
+    }
+
+    pub fn classes(&'static self) -> Vec<MofClass> {
+        self.layouts
+            .iter()
+            .map(|layout| MofClass {
+                guid: layout.guid,
+                name: layout.name,
+                opcodes: &layout.opcodes,
+                opcode_names: &layout.opcode_names,
+                versions: layout.versions,
+                properties: layout.properties.as_slice(),
+            })
+            .collect()
+    }
+}
+
+impl MergedMajor {
+    fn display(&self) -> &'static str {
+        self.display_name.unwrap_or(self.name)
+    }
+}
+
+impl Layout {
+    // none if the minor is decoded by tdh
+    fn new(
+        guid: GUID,
+        name: &'static str,
+        op_code: u8,
+        layout: &'static MinorLayout,
+    ) -> Option<Self> {
+        if layout.properties.is_empty() {
+            return None;
+        }
+        Some(Self {
+            guid,
+            name,
+            opcodes: [op_code],
+            opcode_names: [layout.name.as_str()],
+            versions: layout.versions.as_slice(),
+            properties: layout
+                .properties
+                .iter()
+                .map(|property| MofProperty {
+                    name: property.name.as_str(),
+                    mof_type: property.mof_type,
+                })
+                .collect(),
+        })
+    }
+}
+
+fn out_of_range(op_code: u32, owner: &str) -> String {
+    format!("The op_code: {op_code} of {owner} is greater than 255")
+}
+
+fn conflicting(op_code: u32, owner: &str, other: &str) -> String {
+    format!("The op_code: {op_code} of {owner} is conflicting with {other}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_trace::{event_config::Config, event_decoder};
+    use windows::Win32::System::Diagnostics::Etw::{ProcessGuid, EVENT_RECORD};
+
+    #[test]
+    fn merge_definitions() {
+        static FILE: OnceLock<DefinitionFile> = OnceLock::new();
+        static MERGED: OnceLock<Merged> = OnceLock::new();
+        let file = FILE.get_or_init(|| {
+            parse(
+                Path::new("events.json"),
+                r#"{
+                "minors": [
+                    { "major": "Process", "op_code": 39, "name": "Zombie" },
+                    { "major": "Process Counters", "op_code": 34, "name": "Added",
+                      "properties": [{ "name": "ProcessId", "type": "HexU32" }] },
+                    { "major": "Process Wake", "op_code": 1, "name": "Conflict" },
+                    { "major": "NotExisting", "op_code": 1, "name": "X" },
+                    { "major": "Process", "op_code": 300, "name": "OutOfRange" }
+                ],
+                "majors": [
+                    { "name": "Custom", "guid": "{6A399AE0-4BC6-4DE9-870B-3657F8947E7E}", "flag": 1,
+                      "minors": [{ "op_code": 1, "name": "A" }, { "op_code": 1, "name": "B" },
+                                 { "op_code": 256, "name": "C" }] }
+                ]
+            }"#,
+            )
+            .unwrap()
+        });
+        let merged = MERGED.get_or_init(|| merge(event_kernel::EVENTS_DESC, file));
+        let events = merged.events();
+        let classes = merged.classes();
+        assert_eq!(events.len(), event_kernel::EVENTS_DESC.len() + 1);
+        let process = &events[0];
+        assert!(process
+            .minors
+            .iter()
+            .any(|minor| minor.op_code == 39 && minor.name == "Zombie"));
+        assert!(!process.minors.iter().any(|minor| minor.op_code == 300));
+        let counters = &events[1];
+        assert_eq!(counters.minors.last().unwrap().name, "Added");
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].opcodes, &[34]);
+        assert_eq!(classes[0].opcode_names, &["Added"]);
+        assert!(classes[0].versions.is_empty());
+        let custom = events.last().unwrap();
+        assert_eq!(custom.major.name, "Custom");
+        assert_eq!(custom.minors.len(), 1);
+        // Process Wake/Conflict with Process/Start, NotExisting, Process/OutOfRange, Custom/B and Custom/C
+        assert_eq!(merged.errors.len(), 5, "{:?}", merged.errors);
+        assert!(merged.errors[2].contains("Process/OutOfRange"));
+    }
+
+    #[test]
+    fn parse_toml() {
+        let file = parse(
+            Path::new("events.toml"),
+            r#"
+            [[minors]]
+            major = "PerfInfo"
+            op_code = 130
+            name = "Unknown130"
+            versions = [2]
+            properties = [{ name = "Routine", type = "Pointer" }]
+
+            [[majors]]
+            name = "FileIo"
+            display_name = "FileIo Flt Io"
+            guid = "90CBDC39-4A3E-11D1-84F4-0000F80464E3"
+            flag = 2148532224
+            minors = [{ op_code = 105, name = "Unknown105" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.minors.len(), 1);
+        assert_eq!(file.minors[0].minor.versions, vec![2]);
+        assert_eq!(
+            file.minors[0].minor.properties[0].mof_type,
+            MofType::Pointer
+        );
+        assert_eq!(
+            file.majors[0].display_name.as_deref(),
+            Some("FileIo Flt Io")
+        );
+        assert_eq!(file.majors[0].flag, 0x80100000);
+        assert_eq!(file.majors[0].minors[0].op_code, 105);
+    }
+
+    #[test]
+    fn name_renamed_minor() {
+        static FILE: OnceLock<DefinitionFile> = OnceLock::new();
+        static MERGED: OnceLock<Merged> = OnceLock::new();
+        static EVENTS: OnceLock<Vec<EventsDescribe>> = OnceLock::new();
+        let file = FILE.get_or_init(|| {
+            parse(
+                Path::new("events.toml"),
+                r#"
+                [[minors]]
+                major = "Process"
+                op_code = 2
+                name = "Exit"
+                "#,
+            )
+            .unwrap()
+        });
+        let merged = MERGED.get_or_init(|| merge(event_kernel::EVENTS_DESC, file));
+        assert!(merged.errors.is_empty(), "{:?}", merged.errors);
+        // no layout for the property-less minor, it is decoded by the tdh with the old name
+        assert!(merged.classes().is_empty());
+        let events = EVENTS.get_or_init(|| merged.events());
+        let config = Config::new(events);
+        let event_indexes = config.events_opcode_map[&(ProcessGuid, 2)];
+
+        let user_data = [0u8; 4];
+        let event_record = EVENT_RECORD {
+            UserData: user_data.as_ptr() as *mut _,
+            ..Default::default()
+        };
+        let mut event = event_decoder::decode_kernel_event(&event_record, "Process", "End");
+        name_decoded(events, event_indexes, &mut event);
+        assert_eq!(event.opcode_name, "Exit");
+        assert_eq!(event.get_event_display_name(), "Process");
+    }
+}
@@ -0,0 +1,250 @@
+//! The extended data items of EVENT_RECORD. i.e. the user sid, the terminal session id, the stack trace.
+use super::{event_mof, StackAddress, StackWalk};
+use crate::{pe_bytes::read_bytes, third_extend::Guid};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::slice;
+use windows::{core::GUID, Win32::System::Diagnostics::Etw::*};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExtendedData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub related_activity_id: Option<Guid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_sid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_session_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_start_key: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_trace: Option<StackTrace>,
+    // the items that are not decoded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub others: Vec<ExtendedItem>,
+}
+
+/// The EVENT_HEADER_EXT_TYPE_STACK_TRACE32/64
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackTrace {
+    pub match_id: u64,
+    #[serde(with = "serde_addresses")]
+    pub addresses: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtendedItem {
+    pub ext_type: u16,
+    // hex string
+    pub data: String,
+}
+
+impl ExtendedData {
+    pub fn from_event_record(event_record: &EVENT_RECORD) -> Self {
+        let mut extended = Self::default();
+        if event_record.ExtendedData.is_null() {
+            return extended;
+        }
+        let items = unsafe {
+            slice::from_raw_parts(
+                event_record.ExtendedData,
+                event_record.ExtendedDataCount as usize,
+            )
+        };
+        for item in items {
+            if item.DataPtr == 0 {
+                continue;
+            }
+            let data =
+                unsafe { slice::from_raw_parts(item.DataPtr as *const u8, item.DataSize as usize) };
+            extended.push(item.ExtType, data);
+        }
+        extended
+    }
+
+    fn push(&mut self, ext_type: u16, data: &[u8]) {
+        let is_decoded = match ext_type as u32 {
+            EVENT_HEADER_EXT_TYPE_RELATED_ACTIVITYID => {
+                self.related_activity_id = read_guid(data).map(Guid);
+                self.related_activity_id.is_some()
+            }
+            EVENT_HEADER_EXT_TYPE_SID => {
+                self.user_sid = event_mof::sid_text(data).map(|(s, _)| s);
+                self.user_sid.is_some()
+            }
+            EVENT_HEADER_EXT_TYPE_TS_ID => {
+                self.terminal_session_id = read_bytes(data, 0).map(u32::from_le_bytes);
+                self.terminal_session_id.is_some()
+            }
+            EVENT_HEADER_EXT_TYPE_PROCESS_START_KEY => {
+                self.process_start_key = read_bytes(data, 0).map(u64::from_le_bytes);
+                self.process_start_key.is_some()
+            }
+            EVENT_HEADER_EXT_TYPE_STACK_TRACE32 => {
+                self.stack_trace = read_bytes(data, 0).map(|match_id| StackTrace {
+                    match_id: u64::from_le_bytes(match_id),
+                    addresses: data[8..]
+                        .chunks_exact(4)
+                        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as u64)
+                        .collect(),
+                });
+                self.stack_trace.is_some()
+            }
+            EVENT_HEADER_EXT_TYPE_STACK_TRACE64 => {
+                self.stack_trace = read_bytes(data, 0).map(|match_id| StackTrace {
+                    match_id: u64::from_le_bytes(match_id),
+                    addresses: data[8..]
+                        .chunks_exact(8)
+                        .filter_map(|c| c.try_into().ok().map(u64::from_le_bytes))
+                        .collect(),
+                });
+                self.stack_trace.is_some()
+            }
+            _ => false,
+        };
+        if !is_decoded {
+            self.others.push(ExtendedItem {
+                ext_type,
+                data: hex::encode(data),
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.related_activity_id.is_none()
+            && self.user_sid.is_none()
+            && self.terminal_session_id.is_none()
+            && self.process_start_key.is_none()
+            && self.stack_trace.is_none()
+            && self.others.is_empty()
+    }
+
+    /// The item by the field name of json. i.e. user_sid, terminal_session_id. Only the item is converted
+    pub fn item(&self, field: &str) -> Option<serde_json::Value> {
+        let r = match field {
+            "related_activity_id" => serde_json::to_value(self.related_activity_id.as_ref()?),
+            "user_sid" => serde_json::to_value(self.user_sid.as_ref()?),
+            "terminal_session_id" => serde_json::to_value(self.terminal_session_id?),
+            "process_start_key" => serde_json::to_value(self.process_start_key?),
+            "stack_trace" => serde_json::to_value(self.stack_trace.as_ref()?),
+            "others" if !self.others.is_empty() => serde_json::to_value(&self.others),
+            _ => return None,
+        };
+        r.ok()
+    }
+
+    /// The stack trace as the stack walk of the event. the addresses need to convert to module offset
+    pub fn stack_walk(&self, process_id: u32, thread_id: u32, timestamp: i64) -> Option<StackWalk> {
+        let stack_trace = self.stack_trace.as_ref()?;
+        Some(StackWalk {
+            event_timestamp: timestamp,
+            stack_process: process_id,
+            stack_thread: thread_id,
+            stacks: stack_trace
+                .addresses
+                .iter()
+                .enumerate()
+                .map(|(i, address)| {
+                    (
+                        format!("Stack{}", i + 1),
+                        StackAddress {
+                            raw: *address,
+                            relative: None,
+                        },
+                    )
+                })
+                .collect(),
+        })
+    }
+}
+
+fn read_guid(data: &[u8]) -> Option<GUID> {
+    Some(GUID::from_values(
+        u32::from_le_bytes(read_bytes(data, 0)?),
+        u16::from_le_bytes(read_bytes(data, 4)?),
+        u16::from_le_bytes(read_bytes(data, 6)?),
+        read_bytes(data, 8)?,
+    ))
+}
+
+mod serde_addresses {
+    use super::*;
+
+    pub fn serialize<S>(addresses: &[u64], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(addresses.iter().map(|address| format!("{address:#x}")))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| {
+                u64::from_str_radix(s.trim_start_matches("0x"), 16)
+                    .map_err(|e| serde::de::Error::custom(format!("invalid address: {s} {e}")))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_items() {
+        let mut extended = ExtendedData::default();
+        // S-1-5-18
+        let sid = [1u8, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0];
+        extended.push(EVENT_HEADER_EXT_TYPE_SID as u16, &sid);
+        extended.push(EVENT_HEADER_EXT_TYPE_TS_ID as u16, &1u32.to_le_bytes());
+        let mut stack = 7u64.to_le_bytes().to_vec();
+        stack.extend_from_slice(&0x7ff6_0000_1000u64.to_le_bytes());
+        stack.extend_from_slice(&0xfffff800_00002000u64.to_le_bytes());
+        extended.push(EVENT_HEADER_EXT_TYPE_STACK_TRACE64 as u16, &stack);
+        extended.push(0xff, &[0xab, 0xcd]);
+
+        assert_eq!(extended.user_sid.as_deref(), Some("S-1-5-18"));
+        assert_eq!(extended.terminal_session_id, Some(1));
+        assert_eq!(extended.others.len(), 1);
+        assert_eq!(extended.others[0].data, "abcd");
+
+        let sw = extended.stack_walk(4, 8, 100).unwrap();
+        assert_eq!(sw.stack_process, 4);
+        assert_eq!(sw.stacks.len(), 2);
+        assert_eq!(sw.stacks[1].0, "Stack2");
+        assert_eq!(sw.stacks[1].1.raw, 0xfffff800_00002000);
+    }
+
+    #[test]
+    fn item_by_field() {
+        let mut extended = ExtendedData::default();
+        assert!(extended.item("user_sid").is_none());
+        extended.user_sid = Some("S-1-5-18".to_string());
+        extended.terminal_session_id = Some(1);
+        assert_eq!(extended.item("user_sid").unwrap(), "S-1-5-18");
+        assert_eq!(extended.item("terminal_session_id").unwrap(), 1);
+        assert!(extended.item("process_start_key").is_none());
+        assert!(extended.item("others").is_none());
+        assert!(extended.item("unknown").is_none());
+    }
+
+    #[test]
+    fn serde() {
+        let mut extended = ExtendedData::default();
+        assert_eq!(serde_json::to_string(&extended).unwrap(), "{}");
+        extended.stack_trace = Some(StackTrace {
+            match_id: 0,
+            addresses: vec![0x1000],
+        });
+        let s = serde_json::to_string(&extended).unwrap();
+        assert_eq!(
+            s,
+            r#"{"stack_trace":{"match_id":0,"addresses":["0x1000"]}}"#
+        );
+        let extended: ExtendedData = serde_json::from_str(&s).unwrap();
+        assert_eq!(extended.stack_trace.unwrap().addresses, vec![0x1000]);
+    }
+}
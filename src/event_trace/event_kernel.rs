@@ -2263,15 +2263,16 @@ pub mod event_property {
     use crate::event_trace::event_decoder;
     use anyhow::{anyhow, Result};
     use ascii::AsciiChar;
+    use serde::{Deserialize, Serialize};
     use tracing::error;
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct StackAddress {
         pub raw: u64,
         pub relative: Option<(/*module_id*/ u32, /*offset*/ u32)>,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct StackWalk {
         pub event_timestamp: i64,
         pub stack_process: u32,
@@ -2427,8 +2428,8 @@ pub mod event_property {
                 let mut file_name = map
                     .get("FileName")
                     .map(|property| {
-                        if let event_decoder::PropertyDecoded::String(s) = property {
-                            s.clone()
+                        if let Some(s) = property.text() {
+                            s.into_owned()
                         } else {
                             String::new()
                         }
@@ -2551,8 +2552,8 @@ pub mod event_property {
                 let user_sid = map
                     .get("UserSID")
                     .map(|property| {
-                        if let event_decoder::PropertyDecoded::String(s) = property {
-                            s.clone()
+                        if let Some(s) = property.text() {
+                            s.into_owned()
                         } else {
                             error!("The property's type is not string! {property:?}");
                             String::new()
@@ -2562,8 +2563,8 @@ pub mod event_property {
                 let image_file_name = map
                     .get("ImageFileName")
                     .map(|property| {
-                        if let event_decoder::PropertyDecoded::String(s) = property {
-                            s.clone()
+                        if let Some(s) = property.text() {
+                            s.into_owned()
                         } else {
                             error!("The property's type is not string! {property:?}");
                             String::new()
@@ -2573,8 +2574,8 @@ pub mod event_property {
                 let command_line = map
                     .get("CommandLine")
                     .map(|property| {
-                        if let event_decoder::PropertyDecoded::String(s) = property {
-                            s.clone()
+                        if let Some(s) = property.text() {
+                            s.into_owned()
                         } else {
                             error!("The property's type is not string! {property:?}");
                             String::new()
@@ -2584,8 +2585,8 @@ pub mod event_property {
                 let package_full_name = map
                     .get("PackageFullName")
                     .map(|property| {
-                        if let event_decoder::PropertyDecoded::String(s) = property {
-                            s.clone()
+                        if let Some(s) = property.text() {
+                            s.into_owned()
                         } else {
                             error!("The property's type is not string! {property:?}");
                             String::new()
@@ -2595,8 +2596,8 @@ pub mod event_property {
                 let application_id = map
                     .get("ApplicationID")
                     .map(|property| {
-                        if let event_decoder::PropertyDecoded::String(s) = property {
-                            s.clone()
+                        if let Some(s) = property.text() {
+                            s.into_owned()
                         } else {
                             error!("The property's type is not string! {property:?}");
                             String::new()
@@ -2662,6 +2663,8 @@ pub mod event_property {
                     }
                 }
             }
+        } else if let Some(num) = property.as_u64() {
+            Ok(num)
         } else {
             Err(anyhow!("The property's type is not string!"))
         }
@@ -2686,6 +2689,8 @@ pub mod event_property {
                     }
                 }
             }
+        } else if let Some(num) = property.as_u64() {
+            u32::try_from(num).map_err(|e| anyhow!("Failed to convert: {num}: {e}"))
         } else {
             Err(anyhow!("The property's type is not string! {property:?}"))
         }
@@ -2704,6 +2709,8 @@ pub mod event_property {
                     }
                 }
             }
+        } else if let Some(num) = property.as_i64() {
+            i32::try_from(num).map_err(|e| anyhow!("Failed to convert: {num}: {e}"))
         } else {
             Err(anyhow!("The property's type is not string! {property:?}"))
         }
@@ -0,0 +1,1104 @@
+//! The layouts of the classic kernel events that are defined by the mof classes of MSNT_SystemTrace.
+//! Decode the user data without tdh. The text of values are same as TdhFormatProperty.
+//! reference: https://learn.microsoft.com/en-us/windows/win32/etw/msnt-systemtrace
+use super::{
+    event_decoder::{IntFormat, PropertyDecoded},
+    event_kernel,
+    event_payload::PropertyRange,
+};
+use crate::third_extend::Guid;
+use linked_hash_map::LinkedHashMap;
+use serde::Deserialize;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::OnceLock,
+};
+use tracing::error;
+use windows::{core::GUID, Win32::System::Diagnostics::Etw::*};
+
+/// The in type with the out type of a mof property
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum MofType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    // uint32 with format("x")
+    HexU32,
+    I64,
+    U64,
+    Pointer,
+    // null terminated
+    StringA,
+    // null terminated
+    StringW,
+    // TOKEN_USER followed by SID
+    Sid,
+    Guid,
+    IPv4,
+    IPv6,
+    // uint16 in network byte order
+    Port,
+}
+
+#[derive(Debug)]
+pub struct MofProperty {
+    pub name: &'static str,
+    pub mof_type: MofType,
+}
+
+// the provider name of the classic kernel events in tdh
+pub const PROVIDER_NAME: &str = "MSNT_SystemTrace";
+
+#[derive(Debug)]
+pub struct MofClass {
+    pub guid: GUID,
+    // the task name of tdh
+    pub name: &'static str,
+    pub opcodes: &'static [u8],
+    // the EventTypeName of the opcodes in the same order. tdh reads the opcode name from it
+    pub opcode_names: &'static [&'static str],
+    pub versions: &'static [u8],
+    pub properties: &'static [MofProperty],
+}
+
+macro_rules! props {
+    ($($name:literal: $mof_type:ident),* $(,)?) => {
+        &[$(MofProperty { name: $name, mof_type: MofType::$mof_type }),*]
+    };
+}
+
+const PROCESS_V3: &[MofProperty] = props![
+    "UniqueProcessKey": Pointer,
+    "ProcessId": HexU32,
+    "ParentId": HexU32,
+    "SessionId": U32,
+    "ExitStatus": I32,
+    "DirectoryTableBase": Pointer,
+    "UserSID": Sid,
+    "ImageFileName": StringA,
+    "CommandLine": StringW,
+];
+
+const PROCESS_V4: &[MofProperty] = props![
+    "UniqueProcessKey": Pointer,
+    "ProcessId": HexU32,
+    "ParentId": HexU32,
+    "SessionId": U32,
+    "ExitStatus": I32,
+    "DirectoryTableBase": Pointer,
+    "Flags": U32,
+    "UserSID": Sid,
+    "ImageFileName": StringA,
+    "CommandLine": StringW,
+    "PackageFullName": StringW,
+    "ApplicationID": StringW,
+];
+
+const THREAD_V3: &[MofProperty] = props![
+    "ProcessId": HexU32,
+    "TThreadId": HexU32,
+    "StackBase": Pointer,
+    "StackLimit": Pointer,
+    "UserStackBase": Pointer,
+    "UserStackLimit": Pointer,
+    "Affinity": Pointer,
+    "Win32StartAddr": Pointer,
+    "TebBase": Pointer,
+    "SubProcessTag": HexU32,
+    "BasePriority": U8,
+    "PagePriority": U8,
+    "IoPriority": U8,
+    "ThreadFlags": U8,
+];
+
+const THREAD_V4: &[MofProperty] = props![
+    "ProcessId": HexU32,
+    "TThreadId": HexU32,
+    "StackBase": Pointer,
+    "StackLimit": Pointer,
+    "UserStackBase": Pointer,
+    "UserStackLimit": Pointer,
+    "Affinity": Pointer,
+    "Win32StartAddr": Pointer,
+    "TebBase": Pointer,
+    "SubProcessTag": HexU32,
+    "BasePriority": U8,
+    "PagePriority": U8,
+    "IoPriority": U8,
+    "ThreadFlags": U8,
+    "ThreadName": StringW,
+];
+
+const IMAGE_V2: &[MofProperty] = props![
+    "ImageBase": Pointer,
+    "ImageSize": Pointer,
+    "ProcessId": HexU32,
+    "ImageChecksum": U32,
+    "TimeDateStamp": U32,
+    "Reserved0": U32,
+    "DefaultBase": Pointer,
+    "Reserved1": U32,
+    "Reserved2": U32,
+    "Reserved3": U32,
+    "Reserved4": U32,
+    "FileName": StringW,
+];
+
+const IMAGE_V3: &[MofProperty] = props![
+    "ImageBase": Pointer,
+    "ImageSize": Pointer,
+    "ProcessId": HexU32,
+    "ImageChecksum": U32,
+    "TimeDateStamp": U32,
+    "SignatureLevel": U8,
+    "SignatureType": U8,
+    "Reserved0": U16,
+    "DefaultBase": Pointer,
+    "Reserved1": U32,
+    "Reserved2": U32,
+    "Reserved3": U32,
+    "Reserved4": U32,
+    "FileName": StringW,
+];
+
+const CLASSES: &[MofClass] = &[
+    MofClass {
+        guid: ProcessGuid,
+        name: "Process",
+        opcodes: &[1, 2, 3, 4, 39],
+        opcode_names: &["Start", "End", "DCStart", "DCEnd", "Defunct"],
+        versions: &[3],
+        properties: PROCESS_V3,
+    },
+    MofClass {
+        guid: ProcessGuid,
+        name: "Process",
+        opcodes: &[1, 2, 3, 4, 39],
+        opcode_names: &["Start", "End", "DCStart", "DCEnd", "Defunct"],
+        versions: &[4],
+        properties: PROCESS_V4,
+    },
+    MofClass {
+        guid: ThreadGuid,
+        name: "Thread",
+        opcodes: &[1, 2, 3, 4],
+        opcode_names: &["Start", "End", "DCStart", "DCEnd"],
+        versions: &[3],
+        properties: THREAD_V3,
+    },
+    MofClass {
+        guid: ThreadGuid,
+        name: "Thread",
+        opcodes: &[1, 2, 3, 4],
+        opcode_names: &["Start", "End", "DCStart", "DCEnd"],
+        versions: &[4],
+        properties: THREAD_V4,
+    },
+    MofClass {
+        guid: ThreadGuid,
+        name: "Thread",
+        opcodes: &[72],
+        opcode_names: &["SetName"],
+        versions: &[2],
+        properties: props!["ProcessId": HexU32, "TThreadId": HexU32, "ThreadName": StringW],
+    },
+    MofClass {
+        guid: ThreadGuid,
+        name: "Thread",
+        opcodes: &[36],
+        opcode_names: &["CSwitch"],
+        versions: &[2, 3, 4],
+        properties: props![
+            "NewThreadId": HexU32,
+            "OldThreadId": HexU32,
+            "NewThreadPriority": I8,
+            "OldThreadPriority": I8,
+            "PreviousCState": U8,
+            "SpareByte": I8,
+            "OldThreadWaitReason": I8,
+            "OldThreadWaitMode": I8,
+            "OldThreadState": I8,
+            "OldThreadWaitIdealProcessor": I8,
+            "NewThreadWaitTime": HexU32,
+            "Reserved": U32,
+        ],
+    },
+    MofClass {
+        guid: ImageLoadGuid,
+        name: "Image",
+        opcodes: &[10, 2, 3, 4],
+        opcode_names: &["Load", "UnLoad", "DCStart", "DCEnd"],
+        versions: &[2],
+        properties: IMAGE_V2,
+    },
+    MofClass {
+        guid: ImageLoadGuid,
+        name: "Image",
+        opcodes: &[10, 2, 3, 4],
+        opcode_names: &["Load", "UnLoad", "DCStart", "DCEnd"],
+        versions: &[3],
+        properties: IMAGE_V3,
+    },
+    MofClass {
+        guid: DiskIoGuid,
+        name: "DiskIo",
+        opcodes: &[10, 11],
+        opcode_names: &["Read", "Write"],
+        versions: &[3],
+        properties: props![
+            "DiskNumber": U32,
+            "IrpFlags": HexU32,
+            "TransferSize": U32,
+            "Reserved": U32,
+            "ByteOffset": I64,
+            "FileObject": Pointer,
+            "Irp": Pointer,
+            "HighResResponseTime": U64,
+            "IssuingThreadId": U32,
+        ],
+    },
+    MofClass {
+        guid: DiskIoGuid,
+        name: "DiskIo",
+        opcodes: &[12, 13, 15],
+        opcode_names: &["ReadInit", "WriteInit", "FlushInit"],
+        versions: &[3],
+        properties: props!["Irp": Pointer, "IssuingThreadId": U32],
+    },
+    MofClass {
+        guid: DiskIoGuid,
+        name: "DiskIo",
+        opcodes: &[14],
+        opcode_names: &["FlushBuffers"],
+        versions: &[3],
+        properties: props![
+            "DiskNumber": U32,
+            "IrpFlags": HexU32,
+            "HighResResponseTime": U64,
+            "Irp": Pointer,
+            "IssuingThreadId": U32,
+        ],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[0, 32, 35, 36],
+        opcode_names: &["Name", "FileCreate", "FileDelete", "FileRundown"],
+        versions: &[2, 3],
+        properties: props!["FileObject": Pointer, "FileName": StringW],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[64],
+        opcode_names: &["Create"],
+        versions: &[3],
+        properties: props![
+            "IrpPtr": Pointer,
+            "FileObject": Pointer,
+            "TTID": U32,
+            "CreateOptions": U32,
+            "FileAttributes": U32,
+            "ShareAccess": U32,
+            "OpenPath": StringW,
+        ],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[65, 66, 73],
+        opcode_names: &["Cleanup", "Close", "Flush"],
+        versions: &[3],
+        properties: props![
+            "IrpPtr": Pointer,
+            "FileObject": Pointer,
+            "FileKey": Pointer,
+            "TTID": U32,
+        ],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[67, 68],
+        opcode_names: &["Read", "Write"],
+        versions: &[3],
+        properties: props![
+            "Offset": U64,
+            "IrpPtr": Pointer,
+            "FileObject": Pointer,
+            "FileKey": Pointer,
+            "TTID": U32,
+            "IoSize": U32,
+            "IoFlags": U32,
+        ],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[69, 70, 71, 74, 75],
+        opcode_names: &["SetInfo", "Delete", "Rename", "QueryInfo", "FSControl"],
+        versions: &[3],
+        properties: props![
+            "IrpPtr": Pointer,
+            "FileObject": Pointer,
+            "FileKey": Pointer,
+            "ExtraInfo": Pointer,
+            "TTID": U32,
+            "InfoClass": U32,
+        ],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[72, 77],
+        opcode_names: &["DirEnum", "DirNotify"],
+        versions: &[3],
+        properties: props![
+            "IrpPtr": Pointer,
+            "FileObject": Pointer,
+            "FileKey": Pointer,
+            "TTID": U32,
+            "Length": U32,
+            "InfoClass": U32,
+            "FileIndex": U32,
+            "FileName": StringW,
+        ],
+    },
+    MofClass {
+        guid: FileIoGuid,
+        name: "FileIo",
+        opcodes: &[76],
+        opcode_names: &["OpEnd"],
+        versions: &[3],
+        properties: props!["IrpPtr": Pointer, "ExtraInfo": Pointer, "NtStatus": HexU32],
+    },
+    MofClass {
+        guid: TcpIpGuid,
+        name: "TcpIp",
+        opcodes: &[18],
+        opcode_names: &["TCPCopyIPV4"],
+        versions: &[2],
+        properties: props![
+            "PID": U32,
+            "size": U32,
+            "daddr": IPv4,
+            "saddr": IPv4,
+            "dport": Port,
+            "sport": Port,
+            "connid": Pointer,
+            "seqnum": U32,
+        ],
+    },
+    MofClass {
+        guid: TcpIpGuid,
+        name: "TcpIp",
+        opcodes: &[26],
+        opcode_names: &["SendIPV6"],
+        versions: &[2],
+        properties: props![
+            "PID": U32,
+            "size": U32,
+            "daddr": IPv6,
+            "saddr": IPv6,
+            "dport": Port,
+            "sport": Port,
+            "startime": U32,
+            "endtime": U32,
+            "seqnum": U32,
+            "connid": Pointer,
+        ],
+    },
+    MofClass {
+        guid: TcpIpGuid,
+        name: "TcpIp",
+        opcodes: &[27, 29, 30, 32, 34],
+        opcode_names: &[
+            "RecvIPV6",
+            "DisconnectIPV6",
+            "RetransmitIPV6",
+            "ReconnectIPV6",
+            "TCPCopyIPV6",
+        ],
+        versions: &[2],
+        properties: props![
+            "PID": U32,
+            "size": U32,
+            "daddr": IPv6,
+            "saddr": IPv6,
+            "dport": Port,
+            "sport": Port,
+            "connid": Pointer,
+            "seqnum": U32,
+        ],
+    },
+    MofClass {
+        guid: TcpIpGuid,
+        name: "TcpIp",
+        opcodes: &[28, 31],
+        opcode_names: &["ConnectIPV6", "AcceptIPV6"],
+        versions: &[2],
+        properties: props![
+            "PID": U32,
+            "size": U32,
+            "daddr": IPv6,
+            "saddr": IPv6,
+            "dport": Port,
+            "sport": Port,
+            "mss": U16,
+            "sackopt": U16,
+            "tsopt": U16,
+            "wsopt": U16,
+            "rcvwin": U32,
+            "rcvwinscale": I16,
+            "sndwinscale": I16,
+            "seqnum": U32,
+            "connid": Pointer,
+        ],
+    },
+    MofClass {
+        guid: UdpIpGuid,
+        name: "UdpIp",
+        opcodes: &[10, 11],
+        opcode_names: &["SendIPV4", "RecvIPV4"],
+        versions: &[2],
+        properties: props![
+            "PID": U32,
+            "size": U32,
+            "daddr": IPv4,
+            "saddr": IPv4,
+            "dport": Port,
+            "sport": Port,
+            "seqnum": U32,
+            "connid": Pointer,
+        ],
+    },
+    MofClass {
+        guid: UdpIpGuid,
+        name: "UdpIp",
+        opcodes: &[26, 27],
+        opcode_names: &["SendIPV6", "RecvIPV6"],
+        versions: &[2],
+        properties: props![
+            "PID": U32,
+            "size": U32,
+            "daddr": IPv6,
+            "saddr": IPv6,
+            "dport": Port,
+            "sport": Port,
+            "seqnum": U32,
+            "connid": Pointer,
+        ],
+    },
+    MofClass {
+        guid: UdpIpGuid,
+        name: "UdpIp",
+        opcodes: &[17],
+        opcode_names: &["Fail"],
+        versions: &[2],
+        properties: props!["Proto": U16, "FailureCode": U16],
+    },
+    MofClass {
+        guid: RegistryGuid,
+        name: "Registry",
+        opcodes: &[
+            10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29,
+        ],
+        opcode_names: &[
+            "Create",
+            "Open",
+            "Delete",
+            "Query",
+            "SetValue",
+            "DeleteValue",
+            "QueryValue",
+            "EnumerateKey",
+            "EnumerateValueKey",
+            "QueryMultipleValue",
+            "SetInformation",
+            "Flush",
+            "KCBCreate",
+            "KCBDelete",
+            "KCBRundownBegin",
+            "KCBRundownEnd",
+            "Virtualize",
+            "Close",
+            "SetSecurity",
+            "QuerySecurity",
+        ],
+        versions: &[2],
+        properties: props![
+            "InitialTime": I64,
+            "Status": U32,
+            "Index": U32,
+            "KeyHandle": Pointer,
+            "KeyName": StringW,
+        ],
+    },
+    MofClass {
+        guid: PageFaultGuid,
+        name: "PageFault",
+        opcodes: &[10, 11, 12, 13, 14, 15],
+        opcode_names: &[
+            "TransitionFault",
+            "DemandZeroFault",
+            "CopyOnWrite",
+            "GuardPageFault",
+            "HardPageFault",
+            "AccessViolation",
+        ],
+        versions: &[2],
+        properties: props!["VirtualAddress": Pointer, "ProgramCounter": Pointer],
+    },
+    MofClass {
+        guid: PageFaultGuid,
+        name: "PageFault",
+        opcodes: &[32],
+        opcode_names: &["HardFault"],
+        versions: &[2],
+        properties: props![
+            "InitialTime": I64,
+            "ReadOffset": U64,
+            "VirtualAddress": Pointer,
+            "FileObject": Pointer,
+            "TThreadId": HexU32,
+            "ByteCount": U32,
+        ],
+    },
+    MofClass {
+        guid: PageFaultGuid,
+        name: "PageFault",
+        opcodes: &[98, 99],
+        opcode_names: &["VirtualAlloc", "VirtualFree"],
+        versions: &[2],
+        properties: props![
+            "BaseAddress": Pointer,
+            "RegionSize": Pointer,
+            "ProcessId": HexU32,
+            "Flags": HexU32,
+        ],
+    },
+    MofClass {
+        guid: PerfInfoGuid,
+        name: "PerfInfo",
+        opcodes: &[46],
+        opcode_names: &["SampleProfile"],
+        versions: &[2],
+        properties: props![
+            "InstructionPointer": Pointer,
+            "ThreadId": U32,
+            "Count": U16,
+            "Reserved": U16,
+        ],
+    },
+    MofClass {
+        guid: PerfInfoGuid,
+        name: "PerfInfo",
+        opcodes: &[66, 68, 69],
+        opcode_names: &["ThreadDPC", "DPC", "TimerDPC"],
+        versions: &[2],
+        properties: props!["InitialTime": I64, "Routine": Pointer],
+    },
+    MofClass {
+        guid: PerfInfoGuid,
+        name: "PerfInfo",
+        opcodes: &[67],
+        opcode_names: &["ISR"],
+        versions: &[2],
+        properties: props![
+            "InitialTime": I64,
+            "Routine": Pointer,
+            "ReturnValue": U8,
+            "Vector": U16,
+            "Reserved": U8,
+        ],
+    },
+    MofClass {
+        guid: PerfInfoGuid,
+        name: "PerfInfo",
+        opcodes: &[50],
+        opcode_names: &["ISR-MSI"],
+        versions: &[2],
+        properties: props![
+            "InitialTime": I64,
+            "Routine": Pointer,
+            "ReturnValue": U8,
+            "Vector": U16,
+            "Reserved": U8,
+            "MessageNumber": U32,
+        ],
+    },
+    MofClass {
+        guid: PerfInfoGuid,
+        name: "PerfInfo",
+        opcodes: &[51],
+        opcode_names: &["SysClEnter"],
+        versions: &[2],
+        properties: props!["SysCallAddress": Pointer],
+    },
+    MofClass {
+        guid: PerfInfoGuid,
+        name: "PerfInfo",
+        opcodes: &[52],
+        opcode_names: &["SysClExit"],
+        versions: &[2],
+        properties: props!["SysCallNtStatus": HexU32],
+    },
+    MofClass {
+        guid: event_kernel::OBJECT_GUID,
+        name: "ObTrace",
+        opcodes: &[32, 33, 38, 39],
+        opcode_names: &[
+            "CreateHandle",
+            "CloseHandle",
+            "HandleDCStart",
+            "HandleDCEnd",
+        ],
+        versions: &[2],
+        properties: props![
+            "Object": Pointer,
+            "Handle": HexU32,
+            "ObjectType": U16,
+            "ObjectName": StringW,
+        ],
+    },
+    MofClass {
+        guid: event_kernel::OBJECT_GUID,
+        name: "ObTrace",
+        opcodes: &[34],
+        opcode_names: &["DuplicateHandle"],
+        versions: &[2],
+        properties: props![
+            "Object": Pointer,
+            "SourceHandle": HexU32,
+            "TargetHandle": HexU32,
+            "TargetProcessId": HexU32,
+            "ObjectType": U16,
+            "SourceProcessId": HexU32,
+        ],
+    },
+    MofClass {
+        guid: event_kernel::OBJECT_GUID,
+        name: "ObTrace",
+        opcodes: &[36, 37],
+        opcode_names: &["TypeDCStart", "TypeDCEnd"],
+        versions: &[2],
+        properties: props!["ObjectType": U16, "Reserved": U16, "TypeName": StringW],
+    },
+];
+
+// the count of stacks is variable. Stack1..StackN follow the header
+const STACK_WALK_HEADER: &[MofProperty] =
+    props!["EventTimeStamp": U64, "StackProcess": U32, "StackThread": U32];
+
+// the layouts of the user event definitions. the empty versions match all versions
+static USER_CLASSES: OnceLock<Vec<MofClass>> = OnceLock::new();
+
+pub fn set_user_classes(classes: Vec<MofClass>) {
+    if USER_CLASSES.set(classes).is_err() {
+        error!("The user mof classes has been set");
+    }
+}
+
+pub fn find_class(guid: &GUID, opcode: u8, version: u8) -> Option<&'static MofClass> {
+    let user_class = USER_CLASSES.get().and_then(|classes| {
+        classes.iter().find(|class| {
+            class.guid == *guid
+                && class.opcodes.contains(&opcode)
+                && (class.versions.is_empty() || class.versions.contains(&version))
+        })
+    });
+    user_class.or_else(|| {
+        CLASSES.iter().find(|class| {
+            class.guid == *guid
+                && class.opcodes.contains(&opcode)
+                && class.versions.contains(&version)
+        })
+    })
+}
+
+/// Returns the task name, the opcode name and the properties. None if the event is not covered by the layouts.
+/// The byte range of properties are pushed to the `ranges`
+pub fn decode(
+    guid: &GUID,
+    opcode: u8,
+    version: u8,
+    pointer_size: u32,
+    user_data: &[u8],
+    ranges: &mut Vec<PropertyRange>,
+) -> Option<(
+    /*task_name*/ &'static str,
+    /*opcode_name*/ &'static str,
+    Result<LinkedHashMap<String, PropertyDecoded>, String>,
+)> {
+    if *guid == event_kernel::STACK_WALK_GUID && opcode == 32 {
+        return Some((
+            "StackWalk",
+            "Stack",
+            decode_stack_walk(pointer_size, user_data, ranges),
+        ));
+    }
+    let class = find_class(guid, opcode, version)?;
+    let opcode_name = class
+        .opcodes
+        .iter()
+        .position(|op| *op == opcode)
+        .and_then(|i| class.opcode_names.get(i))
+        .copied()
+        .unwrap_or_default();
+    Some((
+        class.name,
+        opcode_name,
+        decode_properties(class.properties, pointer_size, user_data, ranges),
+    ))
+}
+
+/// As the tdh, the properties after the end of user data are empty strings
+pub fn decode_properties(
+    properties: &[MofProperty],
+    pointer_size: u32,
+    user_data: &[u8],
+    ranges: &mut Vec<PropertyRange>,
+) -> Result<LinkedHashMap<String, PropertyDecoded>, String> {
+    let mut map = LinkedHashMap::new();
+    let mut offset = 0usize;
+    for property in properties {
+        let value = if offset >= user_data.len() {
+            PropertyDecoded::String(String::new())
+        } else {
+            let (value, size) =
+                decode_property(property.mof_type, pointer_size, &user_data[offset..]).ok_or_else(
+                    || {
+                        format!(
+                            "Too short user data: {} for the property: {} at: {offset}",
+                            user_data.len(),
+                            property.name
+                        )
+                    },
+                )?;
+            ranges.push(PropertyRange {
+                name: property.name.to_string(),
+                offset,
+                length: size,
+            });
+            offset += size;
+            value
+        };
+        map.insert(property.name.to_string(), value);
+    }
+    Ok(map)
+}
+
+fn decode_stack_walk(
+    pointer_size: u32,
+    user_data: &[u8],
+    ranges: &mut Vec<PropertyRange>,
+) -> Result<LinkedHashMap<String, PropertyDecoded>, String> {
+    const HEADER_SIZE: usize = 16;
+    let mut map = decode_properties(STACK_WALK_HEADER, pointer_size, user_data, ranges)?;
+    if user_data.len() < HEADER_SIZE {
+        return Err(format!(
+            "Too short user data: {} for StackWalk",
+            user_data.len()
+        ));
+    }
+    for (i, chunk) in user_data[HEADER_SIZE..]
+        .chunks_exact(pointer_size as usize)
+        .enumerate()
+    {
+        if let Some((value, size)) = decode_property(MofType::Pointer, pointer_size, chunk) {
+            ranges.push(PropertyRange {
+                name: format!("Stack{}", i + 1),
+                offset: HEADER_SIZE + i * size,
+                length: size,
+            });
+            map.insert(format!("Stack{}", i + 1), value);
+        }
+    }
+    Ok(map)
+}
+
+/// Returns the typed value and the size of consumed data
+fn decode_property(
+    mof_type: MofType,
+    pointer_size: u32,
+    data: &[u8],
+) -> Option<(PropertyDecoded, usize)> {
+    fn bytes<const N: usize>(data: &[u8]) -> Option<[u8; N]> {
+        data.get(..N).and_then(|bytes| bytes.try_into().ok())
+    }
+    let signed = |num: i64| PropertyDecoded::Signed(num, IntFormat::Decimal);
+    let unsigned = |num: u64| PropertyDecoded::Unsigned(num, IntFormat::Decimal);
+    let r = match mof_type {
+        MofType::I8 => (signed(i8::from_le_bytes(bytes(data)?) as i64), 1),
+        MofType::U8 => (unsigned(u8::from_le_bytes(bytes(data)?) as u64), 1),
+        MofType::I16 => (signed(i16::from_le_bytes(bytes(data)?) as i64), 2),
+        MofType::U16 => (unsigned(u16::from_le_bytes(bytes(data)?) as u64), 2),
+        MofType::I32 => (signed(i32::from_le_bytes(bytes(data)?) as i64), 4),
+        MofType::U32 => (unsigned(u32::from_le_bytes(bytes(data)?) as u64), 4),
+        MofType::HexU32 => (
+            PropertyDecoded::Unsigned(u32::from_le_bytes(bytes(data)?) as u64, IntFormat::Hex),
+            4,
+        ),
+        MofType::I64 => (signed(i64::from_le_bytes(bytes(data)?)), 8),
+        MofType::U64 => (unsigned(u64::from_le_bytes(bytes(data)?)), 8),
+        MofType::Pointer => {
+            if pointer_size == 4 {
+                let num = u32::from_le_bytes(bytes(data)?) as u64;
+                (PropertyDecoded::Pointer(num), 4)
+            } else {
+                (
+                    PropertyDecoded::Pointer(u64::from_le_bytes(bytes(data)?)),
+                    8,
+                )
+            }
+        }
+        MofType::StringA => {
+            let end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+            let s = String::from_utf8_lossy(&data[..end]).to_string();
+            (PropertyDecoded::String(s), (end + 1).min(data.len()))
+        }
+        MofType::StringW => {
+            let chars: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|c| *c != 0)
+                .collect();
+            let size = ((chars.len() + 1) * 2).min(data.len() & !1);
+            (
+                PropertyDecoded::String(String::from_utf16_lossy(&chars)),
+                size,
+            )
+        }
+        MofType::Sid => {
+            let (s, size) = format_sid(pointer_size, data)?;
+            (PropertyDecoded::Sid(s), size)
+        }
+        MofType::Guid => {
+            let guid = GUID::from_u128(u128::from_be_bytes(guid_bytes_be(bytes(data)?)));
+            (PropertyDecoded::Guid(Guid(guid)), 16)
+        }
+        MofType::IPv4 => (
+            PropertyDecoded::IpAddr(IpAddr::V4(Ipv4Addr::from(bytes::<4>(data)?))),
+            4,
+        ),
+        MofType::IPv6 => (
+            PropertyDecoded::IpAddr(IpAddr::V6(Ipv6Addr::from(bytes::<16>(data)?))),
+            16,
+        ),
+        MofType::Port => (unsigned(u16::from_be_bytes(bytes(data)?) as u64), 2),
+    };
+    Some(r)
+}
+
+// the first 3 fields of GUID are little endian
+fn guid_bytes_be(mut bytes: [u8; 16]) -> [u8; 16] {
+    bytes[0..4].reverse();
+    bytes[4..6].reverse();
+    bytes[6..8].reverse();
+    bytes
+}
+
+// the TOKEN_USER is followed by the SID. A null sid is 4 bytes of zero
+fn format_sid(pointer_size: u32, data: &[u8]) -> Option<(String, usize)> {
+    if u32::from_le_bytes(data.get(..4)?.try_into().ok()?) == 0 {
+        return Some((String::new(), 4));
+    }
+    let (s, size) = sid_text(data.get(pointer_size as usize * 2..)?)?;
+    Some((s, pointer_size as usize * 2 + size))
+}
+
+/// The string format of a SID. i.e. `S-1-5-18`. Returns the text and the size of SID
+pub fn sid_text(sid: &[u8]) -> Option<(String, usize)> {
+    let revision = *sid.first()?;
+    let sub_authority_count = *sid.get(1)? as usize;
+    let authority = sid
+        .get(2..8)?
+        .iter()
+        .fold(0u64, |authority, b| (authority << 8) | *b as u64);
+    let mut s = format!("S-{revision}-{authority}");
+    for i in 0..sub_authority_count {
+        let offset = 8 + i * 4;
+        let sub_authority = u32::from_le_bytes(sid.get(offset..offset + 4)?.try_into().ok()?);
+        s.push_str(format!("-{sub_authority}").as_str());
+    }
+    Some((s, 8 + sub_authority_count * 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property_text(map: &LinkedHashMap<String, PropertyDecoded>, name: &str) -> String {
+        match map.get(name).and_then(|property| property.text()) {
+            Some(s) => s.to_string(),
+            _ => panic!("no property: {name}"),
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(|c| c.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn process_start() {
+        let mut data = vec![];
+        data.extend_from_slice(&0xffffc68a5a1e8080u64.to_le_bytes());
+        data.extend_from_slice(&0x1f4u32.to_le_bytes());
+        data.extend_from_slice(&0x4u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&259i32.to_le_bytes());
+        data.extend_from_slice(&0x1aa000u64.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        // TOKEN_USER and S-1-5-18
+        data.extend_from_slice(&0xffffc68a5a1e8100u64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 5]);
+        data.extend_from_slice(&18u32.to_le_bytes());
+        data.extend_from_slice(b"notepad.exe\0");
+        data.extend_from_slice(&wide("notepad.exe a.txt"));
+        data.extend_from_slice(&wide(""));
+        data.extend_from_slice(&wide(""));
+
+        let (name, opcode_name, r) = decode(&ProcessGuid, 1, 4, 8, &data, &mut vec![]).unwrap();
+        let map = r.unwrap();
+        assert_eq!(name, "Process");
+        assert_eq!(opcode_name, "Start");
+        assert_eq!(
+            property_text(&map, "UniqueProcessKey"),
+            "0xFFFFC68A5A1E8080"
+        );
+        assert_eq!(property_text(&map, "ProcessId"), "0x1F4");
+        assert_eq!(property_text(&map, "SessionId"), "1");
+        assert_eq!(property_text(&map, "ExitStatus"), "259");
+        assert_eq!(property_text(&map, "UserSID"), "S-1-5-18");
+        assert_eq!(property_text(&map, "ImageFileName"), "notepad.exe");
+        assert_eq!(property_text(&map, "CommandLine"), "notepad.exe a.txt");
+        assert_eq!(property_text(&map, "ApplicationID"), "");
+        assert_eq!(map.len(), PROCESS_V4.len());
+    }
+
+    #[test]
+    fn image_load() {
+        let mut data = vec![];
+        data.extend_from_slice(&0x7ff800000000u64.to_le_bytes());
+        data.extend_from_slice(&0x1000u64.to_le_bytes());
+        data.extend_from_slice(&0x1f4u32.to_le_bytes());
+        data.extend_from_slice(&0x12345u32.to_le_bytes());
+        data.extend_from_slice(&0x5f000000u32.to_le_bytes());
+        data.extend_from_slice(&[12, 1, 0, 0]);
+        data.extend_from_slice(&0x7ff800000000u64.to_le_bytes());
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&wide(
+            "\\Device\\HarddiskVolume3\\Windows\\System32\\ntdll.dll",
+        ));
+
+        let (name, _, r) = decode(&ImageLoadGuid, 10, 3, 8, &data, &mut vec![]).unwrap();
+        let map = r.unwrap();
+        assert_eq!(name, "Image");
+        assert_eq!(property_text(&map, "ImageSize"), "0x1000");
+        assert_eq!(property_text(&map, "TimeDateStamp"), "1593835520");
+        assert_eq!(property_text(&map, "SignatureLevel"), "12");
+        assert_eq!(
+            property_text(&map, "FileName"),
+            "\\Device\\HarddiskVolume3\\Windows\\System32\\ntdll.dll"
+        );
+    }
+
+    #[test]
+    fn registry_open() {
+        let mut data = vec![];
+        data.extend_from_slice(&(-1i64).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0xffffa20f3c1d0000u64.to_le_bytes());
+        data.extend_from_slice(&wide("\\Registry\\Machine\\SOFTWARE"));
+
+        let (name, opcode_name, r) = decode(&RegistryGuid, 11, 2, 8, &data, &mut vec![]).unwrap();
+        let map = r.unwrap();
+        assert_eq!(name, "Registry");
+        assert_eq!(opcode_name, "Open");
+        assert_eq!(property_text(&map, "InitialTime"), "-1");
+        assert_eq!(property_text(&map, "KeyHandle"), "0xFFFFA20F3C1D0000");
+        assert_eq!(
+            property_text(&map, "KeyName"),
+            "\\Registry\\Machine\\SOFTWARE"
+        );
+    }
+
+    #[test]
+    fn udp_send() {
+        let mut data = vec![];
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(&[10, 0, 0, 1]);
+        data.extend_from_slice(&[192, 168, 1, 2]);
+        data.extend_from_slice(&53u16.to_be_bytes());
+        data.extend_from_slice(&50000u16.to_be_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+
+        let (_, _, r) = decode(&UdpIpGuid, 10, 2, 4, &data, &mut vec![]).unwrap();
+        let map = r.unwrap();
+        assert_eq!(property_text(&map, "daddr"), "10.0.0.1");
+        assert_eq!(property_text(&map, "saddr"), "192.168.1.2");
+        assert_eq!(property_text(&map, "dport"), "53");
+        assert_eq!(property_text(&map, "sport"), "50000");
+        assert_eq!(property_text(&map, "connid"), "0x0");
+    }
+
+    #[test]
+    fn stack_walk() {
+        let mut data = vec![];
+        data.extend_from_slice(&1234u64.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&0xfffff80000001000u64.to_le_bytes());
+        data.extend_from_slice(&0x7ff800002000u64.to_le_bytes());
+
+        let mut ranges = vec![];
+        let (_, _, r) =
+            decode(&event_kernel::STACK_WALK_GUID, 32, 2, 8, &data, &mut ranges).unwrap();
+        let map = r.unwrap();
+        assert_eq!(property_text(&map, "EventTimeStamp"), "1234");
+        assert_eq!(property_text(&map, "StackThread"), "16");
+        assert_eq!(property_text(&map, "Stack2"), "0x7FF800002000");
+        assert!(map.get("Stack3").is_none());
+        assert_eq!(ranges.len(), 5);
+        assert_eq!(
+            ranges[4],
+            PropertyRange {
+                name: "Stack2".to_string(),
+                offset: 24,
+                length: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn short_data() {
+        // the rest properties are empty
+        let (_, _, r) = decode(
+            &PageFaultGuid,
+            10,
+            2,
+            8,
+            &0x1000u64.to_le_bytes(),
+            &mut vec![],
+        )
+        .unwrap();
+        let map = r.unwrap();
+        assert_eq!(property_text(&map, "VirtualAddress"), "0x1000");
+        assert_eq!(property_text(&map, "ProgramCounter"), "");
+        // a property is cut
+        let (_, _, r) = decode(&PageFaultGuid, 10, 2, 8, &[0u8; 12], &mut vec![]).unwrap();
+        assert!(r.is_err());
+        // not covered
+        assert!(decode(&PageFaultGuid, 10, 100, 8, &[], &mut vec![]).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+//! The raw user data of event, and the byte range of each decoded property in it.
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Write;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RawPayload {
+    #[serde(with = "serde_hex")]
+    pub user_data: Vec<u8>,
+    #[serde(default)]
+    pub property_ranges: Vec<PropertyRange>,
+}
+
+/// The bytes of a property is `user_data[offset..offset + length]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PropertyRange {
+    pub name: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl RawPayload {
+    pub fn new(user_data: &[u8], property_ranges: Vec<PropertyRange>) -> Self {
+        Self {
+            user_data: user_data.to_vec(),
+            property_ranges,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.user_data.is_empty()
+    }
+
+    /// The hex dump of user data with 16 bytes each line, then the byte range of properties
+    pub fn hex_dump(&self) -> String {
+        let mut s = String::with_capacity(self.user_data.len() * 5 + 128);
+        let _ = writeln!(
+            s,
+            "offset    00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F  ascii"
+        );
+        for (i, line) in self.user_data.chunks(16).enumerate() {
+            let _ = write!(s, "{:08X}  ", i * 16);
+            for index in 0..16 {
+                match line.get(index) {
+                    Some(b) => {
+                        let _ = write!(s, "{b:02X} ");
+                    }
+                    None => s.push_str("   "),
+                }
+            }
+            s.push(' ');
+            for b in line {
+                s.push(if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                });
+            }
+            s.push('\n');
+        }
+
+        if self.property_ranges.is_empty() {
+            return s;
+        }
+        let name_width = self
+            .property_ranges
+            .iter()
+            .map(|range| range.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("property".len());
+        let _ = writeln!(s, "\n{:name_width$}  offset    length  bytes", "property");
+        let mut end_max = 0;
+        for range in self.property_ranges.iter() {
+            let end = (range.offset + range.length).min(self.user_data.len());
+            let bytes = self
+                .user_data
+                .get(range.offset..end)
+                .map(|bytes| {
+                    bytes
+                        .iter()
+                        .map(|b| format!("{b:02X}"))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .unwrap_or_default();
+            let _ = writeln!(
+                s,
+                "{:name_width$}  {:08X}  {:<6}  {bytes}",
+                range.name, range.offset, range.length
+            );
+            end_max = end_max.max(end);
+        }
+        // the remaining bytes are not described by the properties. i.e. an undocumented version
+        if end_max < self.user_data.len() {
+            let _ = writeln!(
+                s,
+                "not decoded: {:08X}..{:08X}",
+                end_max,
+                self.user_data.len()
+            );
+        }
+        s
+    }
+}
+
+mod serde_hex {
+    use super::*;
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(hex::encode(bytes).as_str())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s.as_str())
+            .map_err(|e| serde::de::Error::custom(format!("invalid hex: {s} {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_dump() {
+        let mut user_data = 500u32.to_le_bytes().to_vec();
+        user_data.extend_from_slice(b"abc\0");
+        user_data.extend_from_slice(&[0xff; 10]);
+        let payload = RawPayload::new(
+            &user_data,
+            vec![
+                PropertyRange {
+                    name: "ProcessId".to_string(),
+                    offset: 0,
+                    length: 4,
+                },
+                PropertyRange {
+                    name: "Name".to_string(),
+                    offset: 4,
+                    length: 4,
+                },
+            ],
+        );
+        let dump = payload.hex_dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[1],
+            "00000000  F4 01 00 00 61 62 63 00 FF FF FF FF FF FF FF FF  ....abc........."
+        );
+        assert_eq!(
+            lines[2],
+            "00000010  FF FF                                            .."
+        );
+        assert_eq!(lines[5], "ProcessId  00000000  4       F4 01 00 00");
+        assert_eq!(lines[6], "Name       00000004  4       61 62 63 00");
+        assert_eq!(lines[7], "not decoded: 00000008..00000012");
+    }
+
+    #[test]
+    fn serde() {
+        let payload = RawPayload::new(&[1, 0xab], vec![]);
+        let s = serde_json::to_string(&payload).unwrap();
+        assert_eq!(s, r#"{"user_data":"01ab","property_ranges":[]}"#);
+        let payload: RawPayload = serde_json::from_str(r#"{"user_data":"01ab"}"#).unwrap();
+        assert_eq!(payload.user_data, vec![1, 0xab]);
+    }
+}
@@ -0,0 +1,273 @@
+//! The schema of events learned from the decoded events. It records the property names, the observed types
+//! and some example values of every (event display name, opcode name). i.e. for the field browser and
+//! checking the `properties.*` fields of filter.
+use super::{EventRecordDecoded, PropertyDecoded};
+use linked_hash_map::LinkedHashMap;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const EXAMPLES_MAX: usize = 3;
+const EXAMPLE_LEN_MAX: usize = 64;
+
+static SCHEMA_REGISTRY: Lazy<RwLock<SchemaRegistry>> =
+    Lazy::new(|| RwLock::new(SchemaRegistry::default()));
+
+#[derive(Debug, Default)]
+pub struct SchemaRegistry {
+    // event display name -> opcode name -> schema
+    events: LinkedHashMap<String, LinkedHashMap<String, EventSchema>>,
+}
+
+#[derive(Debug, Default)]
+pub struct EventSchema {
+    // increased under the read lock of the registry when nothing new is observed
+    pub count: AtomicU64,
+    pub fields: LinkedHashMap<String, FieldSchema>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct FieldSchema {
+    // the variant names of PropertyDecoded. more than one if the type is changed between versions
+    pub types: Vec<&'static str>,
+    // the distinct texts of values
+    pub examples: Vec<String>,
+}
+
+impl SchemaRegistry {
+    pub fn record(&mut self, event: &EventRecordDecoded) {
+        let event_name = event.get_event_display_name();
+        let opcode_name = event.opcode_name.as_str();
+        let opcodes = match self.events.get_mut(event_name) {
+            Some(opcodes) => opcodes,
+            None => self
+                .events
+                .entry(event_name.to_string())
+                .or_insert_with(LinkedHashMap::new),
+        };
+        let schema = match opcodes.get_mut(opcode_name) {
+            Some(schema) => schema,
+            None => opcodes
+                .entry(opcode_name.to_string())
+                .or_insert_with(EventSchema::default),
+        };
+        *schema.count.get_mut() += 1;
+        let PropertyDecoded::Struct(ref properties) = event.properties else {
+            return;
+        };
+        for (name, property) in properties.iter() {
+            let field = match schema.fields.get_mut(name) {
+                Some(field) => field,
+                None => schema
+                    .fields
+                    .entry(name.clone())
+                    .or_insert_with(FieldSchema::default),
+            };
+            let type_name = property.type_name();
+            if !field.types.contains(&type_name) {
+                field.types.push(type_name);
+            }
+            if field.examples.len() < EXAMPLES_MAX {
+                let example = example(property);
+                if !field.examples.contains(&example) {
+                    field.examples.push(example);
+                }
+            }
+        }
+    }
+
+    /// Increase the count if the event has no new field, type or example. Returns false if it needs the record
+    pub fn record_known(&self, event: &EventRecordDecoded) -> bool {
+        let Some(schema) = self.schema(event.get_event_display_name(), &event.opcode_name) else {
+            return false;
+        };
+        if let PropertyDecoded::Struct(ref properties) = event.properties {
+            for (name, property) in properties.iter() {
+                let Some(field) = schema.fields.get(name) else {
+                    return false;
+                };
+                if !field.types.contains(&property.type_name()) {
+                    return false;
+                }
+                if field.examples.len() < EXAMPLES_MAX
+                    && !field.examples.contains(&example(property))
+                {
+                    return false;
+                }
+            }
+        }
+        schema.count.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// The (event display name, opcode name) in the order of first observed
+    pub fn events(&self) -> Vec<(String, String)> {
+        self.events
+            .iter()
+            .flat_map(|(event_name, opcodes)| {
+                opcodes
+                    .keys()
+                    .map(|opcode_name| (event_name.clone(), opcode_name.clone()))
+            })
+            .collect()
+    }
+
+    pub fn schema(&self, event_name: &str, opcode_name: &str) -> Option<&EventSchema> {
+        self.events.get(event_name)?.get(opcode_name)
+    }
+
+    /// The field is known if any observed event has it. None before any event is observed
+    pub fn is_known_field(&self, field: &str) -> Option<bool> {
+        if self.events.is_empty() {
+            return None;
+        }
+        Some(
+            self.events
+                .values()
+                .flat_map(|opcodes| opcodes.values())
+                .any(|schema| schema.fields.contains_key(field)),
+        )
+    }
+}
+
+impl EventSchema {
+    /// The table of fields for the field browser
+    pub fn fields_text(&self) -> String {
+        let name_width = self
+            .fields
+            .keys()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or_default()
+            .max("field".len());
+        let mut s = String::with_capacity(128 * (self.fields.len() + 1));
+        let _ = writeln!(s, "observed: {}\n", self.count.load(Ordering::Relaxed));
+        let _ = writeln!(s, "{:name_width$}  {:<20}  examples", "field", "types");
+        for (name, field) in self.fields.iter() {
+            let _ = writeln!(
+                s,
+                "{:name_width$}  {:<20}  {}",
+                name,
+                field.types.join("|"),
+                field.examples.join(", ")
+            );
+        }
+        s
+    }
+}
+
+impl Clone for EventSchema {
+    fn clone(&self) -> Self {
+        Self {
+            count: AtomicU64::new(self.count.load(Ordering::Relaxed)),
+            fields: self.fields.clone(),
+        }
+    }
+}
+
+// the text of value, truncated to EXAMPLE_LEN_MAX
+fn example(property: &PropertyDecoded) -> String {
+    let mut example = match property.text() {
+        Some(text) => text.into_owned(),
+        None => serde_json::to_string(property).unwrap_or_default(),
+    };
+    if example.len() > EXAMPLE_LEN_MAX {
+        let end = (0..=EXAMPLE_LEN_MAX)
+            .rev()
+            .find(|i| example.is_char_boundary(*i))
+            .unwrap_or_default();
+        example.truncate(end);
+        example.push_str("...");
+    }
+    example
+}
+
+/// Called for every event. The write lock is only taken when the event brings something new
+pub fn record(event: &EventRecordDecoded) {
+    if SCHEMA_REGISTRY.read().record_known(event) {
+        return;
+    }
+    SCHEMA_REGISTRY.write().record(event);
+}
+
+pub fn events() -> Vec<(String, String)> {
+    SCHEMA_REGISTRY.read().events()
+}
+
+pub fn schema(event_name: &str, opcode_name: &str) -> Option<EventSchema> {
+    SCHEMA_REGISTRY
+        .read()
+        .schema(event_name, opcode_name)
+        .cloned()
+}
+
+pub fn is_known_field(field: &str) -> Option<bool> {
+    SCHEMA_REGISTRY.read().is_known_field(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_trace::event_decoder::{self, IntFormat};
+    use windows::Win32::System::Diagnostics::Etw::EVENT_RECORD;
+
+    fn file_event(file_name: &str, irp: u64) -> EventRecordDecoded {
+        let mut properties = LinkedHashMap::new();
+        properties.insert(
+            "FileName".to_string(),
+            PropertyDecoded::String(file_name.to_string()),
+        );
+        properties.insert(
+            "IrpPtr".to_string(),
+            PropertyDecoded::Unsigned(irp, IntFormat::Hex),
+        );
+        let user_data = [0u8; 4];
+        let event_record = EVENT_RECORD {
+            UserData: user_data.as_ptr() as *mut _,
+            ..Default::default()
+        };
+        let mut event = event_decoder::decode_kernel_event(&event_record, "FileIo", "Create");
+        event.properties = PropertyDecoded::Struct(properties);
+        event.set_event_display_name("FileIo Init");
+        event
+    }
+
+    #[test]
+    fn record_events() {
+        let mut registry = SchemaRegistry::default();
+        assert_eq!(registry.is_known_field("FileName"), None);
+        for (file_name, irp) in [("a.txt", 1), ("b.txt", 2), ("a.txt", 3)] {
+            registry.record(&file_event(file_name, irp));
+        }
+
+        assert_eq!(
+            registry.events(),
+            vec![("FileIo Init".to_string(), "Create".to_string())]
+        );
+        let schema = registry.schema("FileIo Init", "Create").unwrap();
+        assert_eq!(schema.count.load(Ordering::Relaxed), 3);
+        let file_name = &schema.fields["FileName"];
+        assert_eq!(file_name.types, vec!["String"]);
+        assert_eq!(file_name.examples, vec!["a.txt", "b.txt"]);
+        assert_eq!(schema.fields["IrpPtr"].examples, vec!["0x1", "0x2", "0x3"]);
+        assert_eq!(registry.is_known_field("IrpPtr"), Some(true));
+        assert_eq!(registry.is_known_field("irp"), Some(false));
+    }
+
+    #[test]
+    fn record_known_events() {
+        let mut registry = SchemaRegistry::default();
+        assert!(!registry.record_known(&file_event("a.txt", 1)));
+        registry.record(&file_event("a.txt", 1));
+        // the same example
+        assert!(registry.record_known(&file_event("a.txt", 1)));
+        // a new example of FileName
+        assert!(!registry.record_known(&file_event("b.txt", 1)));
+        let schema = registry.schema("FileIo Init", "Create").unwrap();
+        assert_eq!(schema.count.load(Ordering::Relaxed), 2);
+        assert_eq!(schema.fields["FileName"].examples, vec!["a.txt"]);
+    }
+}
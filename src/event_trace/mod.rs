@@ -23,12 +23,20 @@ use windows::{
 
 mod event_config;
 mod event_decoder;
+pub mod event_definitions;
+mod event_extended;
 mod event_kernel;
+mod event_mof;
+mod event_payload;
+pub mod event_schema;
 mod stack_walk;
+pub mod etl;
 pub mod process_modules;
+pub mod replay;
 
 pub use event_decoder::{EventRecordDecoded, PropertyDecoded};
-pub use event_kernel::{event_property::*, EVENTS_DESC, LOST_EVENT_GUID};
+pub use event_definitions::events_desc;
+pub use event_kernel::{event_property::*, LOST_EVENT_GUID};
 pub use stack_walk::StackWalkMap;
 
 use process_modules::RunningModules;
@@ -71,7 +79,8 @@ pub struct Controller {
     boot_time: TimeStamp,
     perf_freq: i64,
     running_modules_map: Rc<RunningModules>,
-    inited_time: Option<i64>
+    inited_time: Option<i64>,
+    recorder: Option<replay::Recorder>,
 }
 
 unsafe impl std::marker::Send for Controller {}
@@ -80,7 +89,7 @@ impl Controller {
     fn new() -> Self {
         Self {
             is_stopping: false,
-            config: event_config::Config::new(event_kernel::EVENTS_DESC),
+            config: event_config::Config::new(events_desc()),
             h_trace_session: CONTROLTRACE_HANDLE::default(),
             h_trace_consumer: PROCESSTRACE_HANDLE {
                 Value: INVALID_PROCESSTRACE_HANDLE,
@@ -93,6 +102,7 @@ impl Controller {
             perf_freq: 1000_0000,
             running_modules_map: Rc::new(RunningModules::new(5, 10)),
             inited_time: None,
+            recorder: None,
         }
     }
 
@@ -229,6 +239,22 @@ impl Controller {
         r
     }
 
+    /// Record the events to the file for replaying. None for stopping to record
+    pub fn record_set(path: Option<&std::path::Path>) -> Result<()> {
+        let recorder = match path {
+            Some(path) => Some(replay::Recorder::create(path)?),
+            None => None,
+        };
+        // the old recorder waits for its writer thread, so it is dropped out of the lock
+        let recorder_old = mem::replace(&mut CONTEXT.lock().recorder, recorder);
+        drop(recorder_old);
+        Ok(())
+    }
+
+    pub fn is_running() -> bool {
+        0 != CONTEXT.lock().h_trace_session.Value
+    }
+
     pub fn stop() -> Result<()> {
         let mut context_mg = CONTEXT.lock();
         context_mg.is_stopping = true;
@@ -282,6 +308,9 @@ impl Controller {
         context_mg.unstored_events_map.borrow_mut().clear();
         context_mg.running_modules_map.clear();
         context_mg.inited_time = None;
+        if let Some(recorder) = context_mg.recorder.as_mut() {
+            recorder.flush();
+        }
         Ok(())
     }
 
@@ -373,12 +402,12 @@ impl Controller {
                 if !is_module_event && !is_lost_event && !is_event_trace {
                     if !context_mg
                         .config
-                        .is_flag_enable(EVENTS_DESC[event_indexes.0].major.flag)
+                        .is_flag_enable(events_desc()[event_indexes.0].major.flag)
                     {
                         error!(
                             "No enable major event is coming: {}-{} event_record: {}",
-                            EVENTS_DESC[event_indexes.0].major.name,
-                            EVENTS_DESC[event_indexes.0].minors[event_indexes.1].name,
+                            events_desc()[event_indexes.0].major.name,
+                            events_desc()[event_indexes.0].minors[event_indexes.1].name,
                             EventRecord(event_record)
                         );
                     }
@@ -405,39 +434,49 @@ impl Controller {
         };
         drop(context_mg);
 
-        let mut event_record_decoded = match event_decoder::Decoder::new(event_record) {
-            Ok(mut decoder) => match decoder.decode() {
-                Ok(event_record_decoded) => event_record_decoded,
+        let major_name = events_desc()[event_indexes.0].major.name;
+        let minor_name = events_desc()[event_indexes.0].minors[event_indexes.1].name;
+        // the covered kernel events are decoded without tdh
+        let mof_decoded = match event_decoder::decode_mof_event(event_record) {
+            Some(Ok(event_record_decoded)) => Some(event_record_decoded),
+            Some(Err(e)) => {
+                debug!(
+                    "Failed to decode_mof_event: {e} EventRecord: {}",
+                    EventRecord(event_record)
+                );
+                None
+            }
+            None => None,
+        };
+        let mut event_record_decoded = match mof_decoded {
+            Some(event_record_decoded) => event_record_decoded,
+            None => match event_decoder::Decoder::new(event_record) {
+                Ok(mut decoder) => match decoder.decode() {
+                    Ok(event_record_decoded) => event_record_decoded,
+                    Err(e) => {
+                        warn!(
+                            "Faild to decode: {e} EventRecord: {}",
+                            EventRecord(event_record)
+                        );
+                        event_decoder::decode_kernel_event(event_record, major_name, minor_name)
+                    }
+                },
                 Err(e) => {
-                    warn!(
-                        "Faild to decode: {e} EventRecord: {}",
+                    debug!(
+                        "Faild to Decoder::new: {e} EventRecord: {}",
                         EventRecord(event_record)
                     );
-                    event_decoder::decode_kernel_event(
-                        event_record,
-                        event_kernel::EVENTS_DESC[event_indexes.0].major.name,
-                        event_kernel::EVENTS_DESC[event_indexes.0].minors[event_indexes.1].name,
-                    )
+                    event_decoder::decode_kernel_event(event_record, major_name, minor_name)
                 }
             },
-            Err(e) => {
-                debug!(
-                    "Faild to Decoder::new: {e} EventRecord: {}",
-                    EventRecord(event_record)
-                );
-                event_decoder::decode_kernel_event(
-                    event_record,
-                    event_kernel::EVENTS_DESC[event_indexes.0].major.name,
-                    event_kernel::EVENTS_DESC[event_indexes.0].minors[event_indexes.1].name,
-                )
-            }
         };
 
-        if let Some(display_name) = EVENTS_DESC[event_indexes.0].major.display_name {
-            event_record_decoded.set_event_display_name(display_name);
+        event_definitions::name_decoded(events_desc(), event_indexes, &mut event_record_decoded);
+        if !is_stack_walk {
+            event_schema::record(&event_record_decoded);
         }
 
-        let context_mg = CONTEXT.lock();
+        let mut context_mg = CONTEXT.lock();
         if is_stack_walk {
             let mut sw = StackWalk::from_event_record_decoded(&event_record_decoded);
             sw.event_timestamp = TimeStamp::from_qpc(
@@ -463,6 +502,9 @@ impl Controller {
                     removed.0 .1
                 );
             } else {
+                if let Some(recorder) = context_mg.recorder.as_mut() {
+                    recorder.record(&event_record_decoded, Some(&sw), true);
+                }
                 let cb = context_mg.event_record_callback.clone().unwrap();
                 let running_modules_rc = context_mg.running_modules_map.clone();
                 mem::drop(context_mg);
@@ -470,6 +512,10 @@ impl Controller {
                 cb(event_record_decoded, Some(sw), running_modules_rc.as_ref());
             }
         } else {
+            // record before handle_event_for_module. the replaying handles it again
+            if let Some(recorder) = context_mg.recorder.as_mut() {
+                recorder.record(&event_record_decoded, None, is_enabled);
+            }
             if is_module_event {
                 context_mg.running_modules_map.handle_event_for_module(&mut event_record_decoded);
             }
@@ -628,7 +674,7 @@ pub static EVENTS_DISPLAY_NAME_MAP: Lazy<
     >,
 > = Lazy::new(|| {
     let mut map = LinkedHashMap::new();
-    for (index, event_desc) in EVENTS_DESC.iter().enumerate() {
+    for (index, event_desc) in events_desc().iter().enumerate() {
         let mut minor_map = LinkedHashMap::new();
         for (index_minor, desc_minor) in event_desc.minors.iter().enumerate() {
             minor_map.insert(desc_minor.name.to_ascii_lowercase(), index_minor);
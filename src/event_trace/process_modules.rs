@@ -1,6 +1,11 @@
 use crate::{
     event_trace::{EventRecordDecoded, Image, Process, StackAddress},
-    third_extend::strings::{AsPcwstr, StringEx},
+    pe_bytes::{self, u16_at, u32_at},
+    symbol_path::PdbSignature,
+    third_extend::{
+        strings::{AsPcwstr, StringEx},
+        Guid,
+    },
     utils::TimeStamp,
 };
 use anyhow::{anyhow, Result};
@@ -9,22 +14,24 @@ use indexmap::IndexMap;
 use linked_hash_map::LinkedHashMap;
 use once_cell::sync::Lazy;
 use parking_lot::FairMutex;
+use serde::{Deserialize, Serialize};
 use std::{
     cell::UnsafeCell,
     collections::{BTreeMap, HashMap, VecDeque},
     fs::File,
     io::{Read, Seek, SeekFrom},
     mem,
-    ops::{Bound, Deref},
+    ops::Deref,
     path::Path,
     ptr,
     rc::Rc,
     slice,
     sync::{Arc, OnceLock},
 };
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 use widestring::*;
 use windows::{
+    core::GUID,
     Wdk::{
         Foundation::OBJECT_ATTRIBUTES,
         System::{SystemServices::ZwClose, Threading::NtOpenProcess},
@@ -57,7 +64,7 @@ pub struct RunningModules {
 impl RunningModules {
     pub fn new(max_count: usize, num_seconds: i64) -> Self {
         let running_processes_modules = RunningProcessesModules::new(max_count, num_seconds);
-        let running_kernel_modules = RunningKernelModules::new();
+        let running_kernel_modules = RunningKernelModules::new(num_seconds);
         Self {
             running_processes_modules,
             running_kernel_modules,
@@ -97,8 +104,7 @@ impl RunningModules {
                 if is_kernel_session_space(address) {
                     // todo:
                 } else {
-                    let cursor = kernel_module_lock.upper_bound(Bound::Included(&address));
-                    if let Some((_, module_info_running)) = cursor.peek_prev() {
+                    if let Some(module_info_running) = kernel_module_lock.get(address, timestamp) {
                         if address
                             >= module_info_running.base_of_dll
                                 + module_info_running.size_of_image as u64
@@ -123,10 +129,9 @@ impl RunningModules {
                             continue;
                         }
                     }
-                    let cursor = process_info
-                        .modules_map
-                        .upper_bound(Bound::Included(&address));
-                    if let Some((_, module_info_running)) = cursor.peek_prev() {
+                    if let Some(module_info_running) =
+                        process_info.modules_map.get(address, timestamp)
+                    {
                         if address
                             >= module_info_running.base_of_dll
                                 + module_info_running.size_of_image as u64
@@ -214,6 +219,35 @@ impl RunningModules {
         }
     }
 
+    /// Handle the DCStart events as the Start and Load. The rundown events of a trace file
+    /// describe the processes and modules that are running before the trace starts
+    pub fn handle_rundown_for_module(&self, event_record: &mut EventRecordDecoded) {
+        if event_record.opcode_name != "DCStart" {
+            return self.handle_event_for_module(event_record);
+        }
+        match event_record.provider_id.0 {
+            Etw::ProcessGuid => match Process::get_process_id_from_event_record_decoded(event_record)
+            {
+                Ok(process_id) => self.process_start(process_id, event_record.timestamp),
+                Err(e) => error!(
+                    "Failed to get the rundown process id by process: {}, {e}",
+                    event_record.process_id
+                ),
+            },
+            Etw::ImageLoadGuid => {
+                let image = Image::from_event_record_decoded_with_mut(event_record, |disk_name| {
+                    DRIVE_LETTER_MAP
+                        .get()
+                        .unwrap()
+                        .get(disk_name)
+                        .map(|some| some.clone())
+                });
+                self.process_modules_load(&image, event_record.timestamp);
+            }
+            _ => {}
+        }
+    }
+
     fn process_start(&self, process_id: u32, start_time: TimeStamp) {
         let old_key = self.running_processes_modules.insert(
             process_id,
@@ -223,7 +257,7 @@ impl RunningModules {
                 start_time: Some(start_time),
                 end_time: None,
                 error: None,
-                modules_map: BTreeMap::new(),
+                modules_map: ModulesMap::new(self.running_processes_modules.num_seconds),
             })),
         );
         if old_key.is_some() {
@@ -244,7 +278,7 @@ impl RunningModules {
 
     fn process_modules_load(&self, image: &Image, timestamp: TimeStamp) {
         let (id, module_info_arc) =
-            module_map_insert(image.file_name.clone(), image.time_date_stamp);
+            module_map_insert(image.file_name.clone(), image.time_date_stamp, None);
         let module_info_running = ModuleInfoRunning {
             id: id as u32,
             module_info: module_info_arc.clone(),
@@ -252,13 +286,11 @@ impl RunningModules {
             size_of_image: image.image_size,
             entry_point: image.default_base,
             start: timestamp,
+            end: None,
         };
 
         if is_kernel_space(image.image_base) {
-            let _ = self
-                .running_kernel_modules
-                .lock()
-                .try_insert(image.image_base, module_info_running);
+            let _ = self.running_kernel_modules.lock().load(module_info_running);
         } else {
             if let Some(process_info_arc) = self.running_processes_modules.get_or_insert(
                 image.process_id,
@@ -270,10 +302,7 @@ impl RunningModules {
                 if process_info.path.is_empty() && process_info.error.is_none() {
                     process_info.path = image.file_name.clone();
                 }
-                if let Ok(_new) = process_info
-                    .modules_map
-                    .try_insert(image.image_base, module_info_running)
-                {
+                if process_info.modules_map.load(module_info_running) {
                     if let Some(ProcessError::NoModules(msg)) = process_info.error.take_if(|e| {
                         if let ProcessError::NoModules(_) = e {
                             true
@@ -299,7 +328,10 @@ impl RunningModules {
         let file_name = image.file_name.clone();
 
         if is_kernel_space(image_base) {
-            let _ = self.running_kernel_modules.lock().remove(&image_base);
+            let _ = self
+                .running_kernel_modules
+                .lock()
+                .unload(image_base, timestamp);
         } else {
             if let Some(process_info_arc) = self.running_processes_modules.get_or_insert(
                 process_id,
@@ -307,7 +339,7 @@ impl RunningModules {
                 "process_modules_unload",
             ) {
                 let process_info = unsafe { &mut *process_info_arc.get() };
-                if process_info.modules_map.remove(&image_base).is_none() {
+                if !process_info.modules_map.unload(image_base, timestamp) {
                     if process_info.error.is_none() {
                         warn!("No image: image_base: {image_base:#x} {file_name} when unloading in process: {process_id}");
                     }
@@ -367,7 +399,7 @@ impl RunningModules {
             } else {
                 file_name
             };
-            let (image_size, time_date_stamp) =
+            let (image_size, time_date_stamp, code_view) =
                 match get_image_info_from_file(Path::new(file_name.as_str())) {
                     Err(e) => {
                         info!("Failed to get_image_info_from_file: {file_name} {e}");
@@ -376,7 +408,7 @@ impl RunningModules {
                     Ok(info) => info,
                 };
 
-            let (id, module_info_arc) = module_map_insert(file_name, time_date_stamp);
+            let (id, module_info_arc) = module_map_insert(file_name, time_date_stamp, code_view);
             let module_info_running = ModuleInfoRunning {
                 id: id as u32,
                 module_info: module_info_arc.clone(),
@@ -384,29 +416,122 @@ impl RunningModules {
                 size_of_image: image_size,
                 entry_point: 0,
                 start: TimeStamp(0),
+                end: None,
             };
-            let _ = running_kernel_modules
-                .lock()
-                .try_insert(*image_base as u64, module_info_running);
+            let _ = running_kernel_modules.lock().load(module_info_running);
         }
     }
 }
 
-struct RunningKernelModules(FairMutex<BTreeMap<u64, ModuleInfoRunning>>);
+struct RunningKernelModules(FairMutex<ModulesMap>);
 
 impl RunningKernelModules {
-    fn new() -> Self {
-        RunningKernelModules(FairMutex::new(BTreeMap::new()))
+    fn new(num_seconds: i64) -> Self {
+        RunningKernelModules(FairMutex::new(ModulesMap::new(num_seconds)))
     }
 }
 
 impl Deref for RunningKernelModules {
-    type Target = FairMutex<BTreeMap<u64, ModuleInfoRunning>>;
+    type Target = FairMutex<ModulesMap>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
+/// The modules by the base address. The unloaded modules are kept with the time of load and unload, so the late
+/// stack walks are resolved by the modules at the time of the event, even if another module is loaded at the base
+#[derive(Debug)]
+struct ModulesMap {
+    modules: BTreeMap<u64, VecDeque<ModuleInfoRunning>>,
+    // (the unload time, base) in the order of unloading. the unloaded modules of all bases are dropped by it
+    unloaded: VecDeque<(TimeStamp, u64)>,
+    // the lower bound of the bases that may contain an address
+    max_size_of_image: u64,
+    num_seconds: i64,
+}
+
+impl ModulesMap {
+    fn new(num_seconds: i64) -> Self {
+        Self {
+            modules: BTreeMap::new(),
+            unloaded: VecDeque::new(),
+            max_size_of_image: 0,
+            num_seconds,
+        }
+    }
+
+    /// Returns false if a module is still loaded at the base
+    fn load(&mut self, module_info_running: ModuleInfoRunning) -> bool {
+        self.prune(module_info_running.start);
+        let modules = self
+            .modules
+            .entry(module_info_running.base_of_dll)
+            .or_default();
+        if modules.back().is_some_and(|module| module.end.is_none()) {
+            return false;
+        }
+        self.max_size_of_image = self
+            .max_size_of_image
+            .max(module_info_running.size_of_image as u64);
+        modules.push_back(module_info_running);
+        true
+    }
+
+    /// Returns false if no module is loaded at the base
+    fn unload(&mut self, base_of_dll: u64, timestamp: TimeStamp) -> bool {
+        let Some(module) = self
+            .modules
+            .get_mut(&base_of_dll)
+            .and_then(|modules| modules.back_mut())
+            .filter(|module| module.end.is_none())
+        else {
+            self.prune(timestamp);
+            return false;
+        };
+        module.end = Some(timestamp);
+        self.unloaded.push_back((timestamp, base_of_dll));
+        self.prune(timestamp);
+        true
+    }
+
+    // drop the modules unloaded `num_seconds` before in all bases. the front of a base is unloaded first
+    fn prune(&mut self, timestamp: TimeStamp) {
+        while let Some((end, base_of_dll)) = self.unloaded.front().copied() {
+            if (timestamp - end).num_seconds() < self.num_seconds {
+                break;
+            }
+            self.unloaded.pop_front();
+            if let Some(modules) = self.modules.get_mut(&base_of_dll) {
+                modules.pop_front();
+                if modules.is_empty() {
+                    self.modules.remove(&base_of_dll);
+                }
+            }
+        }
+    }
+
+    /// The module that is loaded at the timestamp with the nearest base below or equal to the address. the address
+    /// may be beyond the end of the module. the bases further than the largest image are not searched
+    fn get(&self, address: u64, timestamp: TimeStamp) -> Option<&ModuleInfoRunning> {
+        let lowest = address.saturating_sub(self.max_size_of_image);
+        self.modules
+            .range(lowest..=address)
+            .rev()
+            .find_map(|(_, modules)| {
+                modules.iter().rev().find(|module| {
+                    module.start.0 <= timestamp.0
+                        && module.end.is_none_or(|end| timestamp.0 < end.0)
+                })
+            })
+    }
+
+    fn clear(&mut self) {
+        self.modules.clear();
+        self.unloaded.clear();
+        self.max_size_of_image = 0;
+    }
+}
+
 struct RunningProcessesModules {
     map: UnsafeCell<HashMap<u32, Rc<UnsafeCell<ProcessInfo>>>>,
     ended_map: UnsafeCell<LinkedHashMap<u32, VecDeque<Rc<UnsafeCell<ProcessInfo>>>>>, // the process_id may be repeated. because of delay removing
@@ -433,22 +558,34 @@ impl RunningProcessesModules {
             return Err(RunningProcessError::SpecialProcess(process_id));
         }
         let processes_map = unsafe { &mut *self.map.get() };
-        if let Some(process_info_arc) = processes_map.get(&process_id) {
-            return Ok(process_info_arc.clone());
+        let running = processes_map.get(&process_id);
+        if let Some(process_info_arc) = running {
+            let process_info = unsafe { &*process_info_arc.get() };
+            if process_info
+                .start_time
+                .is_none_or(|start_time| current_timestamp.0 >= start_time.0)
+            {
+                return Ok(process_info_arc.clone());
+            }
         }
+        // the process id may be reused. the late events before the start are of the ended process
         let processes_ended_map = unsafe { &mut *self.ended_map.get() };
         if let Some(process_info_vec) = processes_ended_map.get(&process_id) {
-            for process_info_arc in process_info_vec {
+            for process_info_arc in process_info_vec.iter().rev() {
                 let process_info = unsafe { &mut *process_info_arc.get() };
                 // equal: include the self
                 let start_time = process_info.start_time.unwrap_or(process_info.create_time);
                 if current_timestamp.0 >= start_time.0
-                    && (current_timestamp - process_info.end_time.unwrap()).num_seconds() < 10
+                    && (current_timestamp - process_info.end_time.unwrap()).num_seconds()
+                        < self.num_seconds
                 {
                     return Ok(process_info_arc.clone());
                 }
             }
         }
+        if let Some(process_info_arc) = running {
+            return Ok(process_info_arc.clone());
+        }
         Err(RunningProcessError::NotFound(process_id))
     }
 
@@ -477,7 +614,7 @@ impl RunningProcessesModules {
             error: Some(ProcessError::NoModules(format!(
                 "Insert after not discovering"
             ))),
-            modules_map: BTreeMap::new(),
+            modules_map: ModulesMap::new(self.num_seconds),
         }));
         let map = unsafe { &mut *self.map.get() };
         map.insert(process_id, rc.clone());
@@ -621,7 +758,7 @@ impl RunningProcessesModules {
                 start_time: None,
                 end_time: None,
                 error: None,
-                modules_map: BTreeMap::new(),
+                modules_map: ModulesMap::new(self.num_seconds),
             })),
         ) {
             if !is_new {
@@ -733,15 +870,16 @@ impl RunningProcessesModules {
                 if let Err(e) = r {
                     warn!("Failed to GetModuleInformation: {}", e);
                 }
-                let (_, time_date_stamp) =
+                let (_, time_date_stamp, code_view) =
                     match get_image_info_from_file(Path::new(file_name.as_str())) {
                         Ok(info) => info,
                         Err(e) => {
                             warn!("Failed to get_image_info_from_file: {e}");
-                            (0, 0)
+                            (0, 0, None)
                         }
                     };
-                let (id, module_info_arc) = module_map_insert(file_name.clone(), time_date_stamp);
+                let (id, module_info_arc) =
+                    module_map_insert(file_name.clone(), time_date_stamp, code_view);
                 let module_info_running = ModuleInfoRunning {
                     id: id as u32,
                     module_info: module_info_arc.clone(),
@@ -749,10 +887,9 @@ impl RunningProcessesModules {
                     size_of_image: module_info.SizeOfImage,
                     entry_point: module_info.EntryPoint as u64,
                     start: TimeStamp(0),
+                    end: None,
                 };
-                let _ = process_info
-                    .modules_map
-                    .try_insert(module_info.lpBaseOfDll as u64, module_info_running);
+                let _ = process_info.modules_map.load(module_info_running);
             }
         }
         let _ = unsafe { ZwClose(h_process_out) };
@@ -771,6 +908,32 @@ enum RunningProcessError {
 pub struct ModuleInfo {
     pub file_name: String,
     pub time_data_stamp: u32,
+    // None if the image is not read or has not the RSDS record
+    pub code_view: Option<CodeView>,
+}
+
+/// The RSDS CodeView record of the debug directory of image. The pdb is matched by the guid and age
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeView {
+    pub guid: Guid,
+    pub age: u32,
+    pub pdb_path: String,
+}
+
+impl CodeView {
+    /// The key of symstore is the guid and age. i.e. `6A399AE04BC64DE9870B3657F8947E7E1` for the age 1
+    pub fn pdb_signature(&self) -> PdbSignature {
+        let guid = self.guid.0;
+        let mut key = format!("{:08X}{:04X}{:04X}", guid.data1, guid.data2, guid.data3);
+        for b in guid.data4 {
+            key.push_str(format!("{b:02X}").as_str());
+        }
+        key.push_str(format!("{:X}", self.age).as_str());
+        PdbSignature {
+            pdb_path: self.pdb_path.clone(),
+            key,
+        }
+    }
 }
 
 impl ModuleInfo {
@@ -787,8 +950,9 @@ pub struct ModuleInfoRunning {
     pub size_of_image: u32,
     #[allow(unused)]
     pub entry_point: u64,
-    #[allow(unused)]
     pub start: TimeStamp,
+    // None while the module is loaded
+    pub end: Option<TimeStamp>,
 }
 
 #[derive(Debug)]
@@ -798,7 +962,7 @@ pub struct ProcessInfo {
     start_time: Option<TimeStamp>, // the time when creating the process.
     end_time: Option<TimeStamp>,
     error: Option<ProcessError>,
-    modules_map: BTreeMap<u64, ModuleInfoRunning>,
+    modules_map: ModulesMap,
 }
 
 #[derive(thiserror::Error, Debug, PartialEq)]
@@ -820,6 +984,12 @@ pub fn get_module_info_by_id(id: u32) -> Option<Arc<ModuleInfo>> {
     }
 }
 
+/// Returns the modules that id is from `id_from`. the id of module is the index of returned vec plus `id_from`
+pub fn get_modules_info_from(id_from: u32) -> Vec<Arc<ModuleInfo>> {
+    let lock = MODULES_MAP.lock();
+    lock.values().skip(id_from as usize).cloned().collect()
+}
+
 pub fn get_file_name_from_path(path: &str) -> &str {
     if let Some(offset) = path.rfind("\\") {
         path.get((offset + 1)..).unwrap_or("no_file_name")
@@ -830,7 +1000,11 @@ pub fn get_file_name_from_path(path: &str) -> &str {
 
 pub fn get_image_info_from_file(
     file_path: &Path,
-) -> Result<(/*image_size*/ u32, /*time_data_stamp*/ u32)> {
+) -> Result<(
+    /*image_size*/ u32,
+    /*time_data_stamp*/ u32,
+    /*code_view*/ Option<CodeView>,
+)> {
     let mut file = match File::open(file_path) {
         Err(e) => {
             return Err(anyhow!("Failed to open file: {} {e}", file_path.display()));
@@ -877,11 +1051,99 @@ pub fn get_image_info_from_file(
                 let nt_header: &IMAGE_NT_HEADERS32 = unsafe { mem::transmute(data.as_ptr()) };
                 nt_header.OptionalHeader.SizeOfImage
             };
-            Ok((image_size, time_data_stamp))
+            let debug_directory = if nt_header.OptionalHeader.Magic == IMAGE_NT_OPTIONAL_HDR64_MAGIC
+            {
+                nt_header.OptionalHeader.DataDirectory[IMAGE_DIRECTORY_ENTRY_DEBUG.0 as usize]
+            } else {
+                let nt_header: &IMAGE_NT_HEADERS32 = unsafe { mem::transmute(data.as_ptr()) };
+                nt_header.OptionalHeader.DataDirectory[IMAGE_DIRECTORY_ENTRY_DEBUG.0 as usize]
+            };
+            // the image is still usable without the code view. the pdb is matched by TimeDateStamp
+            let section_headers_offset = nt_header_offset as u64
+                + mem::size_of::<u32>() as u64
+                + mem::size_of::<IMAGE_FILE_HEADER>() as u64
+                + nt_header.FileHeader.SizeOfOptionalHeader as u64;
+            let code_view = read_code_view(
+                &mut file,
+                section_headers_offset,
+                nt_header.FileHeader.NumberOfSections,
+                debug_directory,
+            )
+            .unwrap_or_else(|e| {
+                debug!("Failed to read_code_view: {} {e}", file_path.display());
+                None
+            });
+            Ok((image_size, time_data_stamp, code_view))
         }
     }
 }
 
+fn read_code_view(
+    file: &mut File,
+    section_headers_offset: u64,
+    number_of_sections: u16,
+    debug_directory: IMAGE_DATA_DIRECTORY,
+) -> Result<Option<CodeView>> {
+    if debug_directory.VirtualAddress == 0 || debug_directory.Size == 0 {
+        return Ok(None);
+    }
+    // the file offset of debug directory is found by the section that contains it
+    let section_headers = read_at(
+        file,
+        section_headers_offset,
+        number_of_sections as usize * mem::size_of::<IMAGE_SECTION_HEADER>(),
+    )?;
+    let debug_directory_offset =
+        pe_bytes::rva_to_offset(&section_headers, debug_directory.VirtualAddress)
+            .ok_or_else(|| anyhow!("No section for the debug directory"))?;
+
+    let entries = read_at(file, debug_directory_offset, debug_directory.Size as usize)?;
+    for entry in entries.chunks_exact(mem::size_of::<IMAGE_DEBUG_DIRECTORY>()) {
+        let (Some(debug_type), Some(size_of_data), Some(pointer_to_raw_data)) =
+            (u32_at(entry, 12), u32_at(entry, 16), u32_at(entry, 24))
+        else {
+            continue;
+        };
+        if debug_type != IMAGE_DEBUG_TYPE_CODEVIEW.0 || size_of_data == 0 {
+            continue;
+        }
+        let data = read_at(file, pointer_to_raw_data as u64, size_of_data as usize)?;
+        if let Some(code_view) = parse_code_view(&data) {
+            return Ok(Some(code_view));
+        }
+    }
+    Ok(None)
+}
+
+// "RSDS", guid, age, the null terminated utf8 path of pdb
+fn parse_code_view(data: &[u8]) -> Option<CodeView> {
+    if data.get(0..4)? != b"RSDS" {
+        return None;
+    }
+    let guid = GUID::from_values(
+        u32_at(data, 4)?,
+        u16_at(data, 8)?,
+        u16_at(data, 10)?,
+        data.get(12..20)?.try_into().ok()?,
+    );
+    let age = u32_at(data, 20)?;
+    let path = data.get(24..)?;
+    let len = path.iter().position(|b| *b == 0).unwrap_or(path.len());
+    Some(CodeView {
+        guid: Guid(guid),
+        age,
+        pdb_path: String::from_utf8_lossy(&path[..len]).into_owned(),
+    })
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut data = vec![0u8; len];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut data))
+        .map_err(|e| anyhow!("Failed to read {len} bytes at {offset:#x}: {e}"))?;
+    Ok(data)
+}
+
 fn drive_letter_map_init() {
     let mut map = HashMap::<String, AsciiChar>::new();
     let mut file_name_ret = Vec::<u16>::with_capacity(260);
@@ -920,9 +1182,10 @@ fn drive_letter_map_init() {
     let _ = DRIVE_LETTER_MAP.set(map);
 }
 
-fn module_map_insert(
+pub fn module_map_insert(
     file_name: String,
     time_date_stamp: u32,
+    code_view: Option<CodeView>,
 ) -> (/*id*/ usize, /*id*/ Arc<ModuleInfo>) {
     let mut module_lock = MODULES_MAP.lock();
     if let Some(some) = module_lock.get_full(&(file_name.clone(), time_date_stamp)) {
@@ -931,6 +1194,7 @@ fn module_map_insert(
         let module_info_arc = Arc::new(ModuleInfo {
             file_name: file_name.clone(),
             time_data_stamp: time_date_stamp,
+            code_view,
         });
         let entry = module_lock.insert_full(
             (file_name.clone(), time_date_stamp),
@@ -956,10 +1220,14 @@ fn is_kernel_session_space(_address: u64) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::{RunningKernelModules, RunningModules, RunningProcessesModules, DRIVE_LETTER_MAP};
-    use crate::pdb::{pdb_path_set, get_location_info as pdb_get_location_info};
+    use super::{
+        ModuleInfoRunning, ModulesMap, RunningKernelModules, RunningModules,
+        RunningProcessesModules, DRIVE_LETTER_MAP,
+    };
+    use crate::pdb::{get_location_info as pdb_get_location_info, symbol_path_set};
+    use crate::utils::TimeStamp;
     use std::path::Path;
-    use windows::Win32::System::Threading::GetCurrentProcessId;
+    use windows::{core::GUID, Win32::System::Threading::GetCurrentProcessId};
 
     #[test]
     fn store_process_modules() {
@@ -987,7 +1255,7 @@ mod tests {
 
     #[test]
     fn enum_drivers() {
-        let mut running_kernel_modules = RunningKernelModules::new();
+        let mut running_kernel_modules = RunningKernelModules::new(10);
         RunningModules::enum_drivers(&mut running_kernel_modules);
         println!("{:#?}", running_kernel_modules.0);
     }
@@ -996,16 +1264,112 @@ mod tests {
     fn get_location_info() {
         let out_dir = env!("CARGO_MANIFEST_DIR");
         let pkg_name = env!("CARGO_PKG_NAME");
-        let (_, time_date_stamp) = super::get_image_info_from_file(Path::new(
+        let (_, time_date_stamp, code_view) = super::get_image_info_from_file(Path::new(
             format!("{out_dir}\\target\\debug\\{pkg_name}.exe").as_str(),
         ))
         .unwrap();
-        pdb_path_set(format!("{out_dir}\\target\\debug").as_str());
+        symbol_path_set(format!("{out_dir}\\target\\debug").as_str()).unwrap();
         let module_info = super::ModuleInfo {
             file_name: format!("{out_dir}\\target\\debug\\{pkg_name}.exe"),
             time_data_stamp: time_date_stamp,
+            code_view,
         };
-        let r = pdb_get_location_info(Path::new(module_info.file_name.as_str()), module_info.time_data_stamp, 0x2b6168).unwrap();
+        let r = pdb_get_location_info(Path::new(module_info.file_name.as_str()), module_info.time_data_stamp, module_info.code_view.as_ref(), 0x2b6168).unwrap();
         println!("{r:?}");
     }
+
+    #[test]
+    fn modules_map_by_time() {
+        // the filetime of 1 second
+        const SECOND: i64 = 10_000_000;
+        let module = |file_name: &str, base_of_dll, start| {
+            let (id, module_info) = super::module_map_insert(file_name.to_string(), 0, None);
+            ModuleInfoRunning {
+                id: id as u32,
+                module_info,
+                base_of_dll,
+                size_of_image: 0x1000,
+                entry_point: 0,
+                start: TimeStamp(start * SECOND),
+                end: None,
+            }
+        };
+        let name_at = |map: &ModulesMap, address, time: i64| {
+            map.get(address, TimeStamp(time * SECOND))
+                .map(|module| module.module_info.file_name.clone())
+        };
+        let mut map = ModulesMap::new(10);
+        assert!(map.load(module("C:\\low.dll", 0x1000, 0)));
+        assert!(map.load(module("C:\\a.dll", 0x10000, 100)));
+        assert!(!map.load(module("C:\\a.dll", 0x10000, 101)));
+        assert!(map.unload(0x10000, TimeStamp(110 * SECOND)));
+        assert!(!map.unload(0x10000, TimeStamp(111 * SECOND)));
+        assert!(map.load(module("C:\\b.dll", 0x10000, 115)));
+
+        assert_eq!(name_at(&map, 0x10010, 105).as_deref(), Some("C:\\a.dll"));
+        assert_eq!(name_at(&map, 0x10010, 125).as_deref(), Some("C:\\b.dll"));
+        assert_eq!(name_at(&map, 0x1010, 125).as_deref(), Some("C:\\low.dll"));
+        // nothing is loaded at the base between the unload and the load, the lower bases are beyond the images
+        assert_eq!(name_at(&map, 0x10010, 112), None);
+        assert_eq!(name_at(&map, 0x10010, 50), None);
+        assert_eq!(name_at(&map, 0x800, 125), None);
+
+        // the unloaded a.dll is dropped after num_seconds
+        assert!(map.unload(0x10000, TimeStamp(130 * SECOND)));
+        assert_eq!(name_at(&map, 0x10010, 105), None);
+        assert_eq!(name_at(&map, 0x10010, 125).as_deref(), Some("C:\\b.dll"));
+    }
+
+    #[test]
+    fn modules_map_prune_all_bases() {
+        const SECOND: i64 = 10_000_000;
+        let module = |file_name: &str, base_of_dll, start| {
+            let (id, module_info) = super::module_map_insert(file_name.to_string(), 0, None);
+            ModuleInfoRunning {
+                id: id as u32,
+                module_info,
+                base_of_dll,
+                size_of_image: 0x1000,
+                entry_point: 0,
+                start: TimeStamp(start * SECOND),
+                end: None,
+            }
+        };
+        let mut map = ModulesMap::new(10);
+        assert!(map.load(module("C:\\a.dll", 0x10000, 100)));
+        assert!(map.unload(0x10000, TimeStamp(110 * SECOND)));
+        assert!(map.get(0x10010, TimeStamp(105 * SECOND)).is_some());
+
+        // a.dll is unloaded once and never loaded again, it is dropped by the changes of other bases
+        assert!(map.load(module("C:\\b.dll", 0x20000, 115)));
+        assert!(map.get(0x10010, TimeStamp(105 * SECOND)).is_some());
+        assert!(map.load(module("C:\\c.dll", 0x30000, 120)));
+        assert!(map.get(0x10010, TimeStamp(105 * SECOND)).is_none());
+        assert!(!map.modules.contains_key(&0x10000));
+        assert!(map.unloaded.is_empty());
+        assert!(map.get(0x20010, TimeStamp(125 * SECOND)).is_some());
+    }
+
+    #[test]
+    fn parse_code_view() {
+        let mut data = b"RSDS".to_vec();
+        data.extend_from_slice(&0x6A399AE0u32.to_le_bytes());
+        data.extend_from_slice(&0x4BC6u16.to_le_bytes());
+        data.extend_from_slice(&0x4DE9u16.to_le_bytes());
+        data.extend_from_slice(&[0x87, 0x0B, 0x36, 0x57, 0xF8, 0x94, 0x7E, 0x7E]);
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"D:\\build\\ntkrnlmp.pdb\0");
+        let code_view = super::parse_code_view(&data).unwrap();
+        assert_eq!(
+            code_view.guid.0,
+            GUID::from_u128(0x6A399AE0_4BC6_4DE9_870B_3657F8947E7E)
+        );
+        assert_eq!(code_view.age, 3);
+        assert_eq!(code_view.pdb_path, "D:\\build\\ntkrnlmp.pdb");
+        assert_eq!(
+            code_view.pdb_signature().key,
+            "6A399AE04BC64DE9870B3657F8947E7E3"
+        );
+        assert!(super::parse_code_view(b"NB10").is_none());
+    }
 }
@@ -0,0 +1,315 @@
+use super::{
+    etl::{self, EtlReader},
+    event_config, event_decoder, event_definitions, event_kernel, event_schema, events_desc,
+    process_modules::RunningModules,
+    EventRecordDecoded, StackWalk,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+use tracing::{debug, error, info};
+use windows::{core::GUID, Win32::System::Diagnostics::Etw};
+
+// a recorded stream is json lines. one line for a event that is passed to the event callback or updates the running modules
+#[derive(Serialize)]
+struct EventRecorded<'a> {
+    #[serde(flatten)]
+    event: &'a EventRecordDecoded,
+    stack_walk: Option<&'a StackWalk>,
+    // false for the module event that only updates the running modules
+    is_enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct EventReplayed {
+    #[serde(flatten)]
+    event: EventRecordDecoded,
+    #[serde(default)]
+    stack_walk: Option<StackWalk>,
+    #[serde(default = "is_enabled_default")]
+    is_enabled: bool,
+}
+
+fn is_enabled_default() -> bool {
+    true
+}
+
+/// Record the decoded events and stack walks in the order of coming.
+/// The events are copied to a writer thread, so the event callback is not blocked by the serializing.
+pub struct Recorder {
+    sender: Option<mpsc::Sender<RecordMessage>>,
+    writer_thread: Option<JoinHandle<()>>,
+}
+
+enum RecordMessage {
+    Event(
+        EventRecordDecoded,
+        Option<StackWalk>,
+        /*is_enabled*/ bool,
+    ),
+    Flush,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).map_err(|e| anyhow!("Failed to create {}: {e}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        let (sender, receiver) = mpsc::channel::<RecordMessage>();
+        let writer_thread = thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    RecordMessage::Event(event, stack_walk, is_enabled) => {
+                        let event_recorded = EventRecorded {
+                            event: &event,
+                            stack_walk: stack_walk.as_ref(),
+                            is_enabled,
+                        };
+                        let r = serde_json::to_writer(&mut writer, &event_recorded)
+                            .map_err(|e| anyhow!("{e}"))
+                            .and_then(|_| writer.write_all(b"\n").map_err(|e| anyhow!("{e}")));
+                        if let Err(e) = r {
+                            error!("Failed to record the event: {e}");
+                        }
+                    }
+                    RecordMessage::Flush => {
+                        if let Err(e) = writer.flush() {
+                            error!("Failed to flush the recorder: {e}");
+                        }
+                    }
+                }
+            }
+            if let Err(e) = writer.flush() {
+                error!("Failed to flush the recorder: {e}");
+            }
+        });
+        Ok(Self {
+            sender: Some(sender),
+            writer_thread: Some(writer_thread),
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        event: &EventRecordDecoded,
+        stack_walk: Option<&StackWalk>,
+        is_enabled: bool,
+    ) {
+        if let Some(sender) = self.sender.as_ref() {
+            let message = RecordMessage::Event(event.clone(), stack_walk.cloned(), is_enabled);
+            if sender.send(message).is_err() {
+                error!("Failed to record the event: the writer thread exited");
+            }
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let Some(sender) = self.sender.as_ref() {
+            let _ = sender.send(RecordMessage::Flush);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    // wait for the queued events are written
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(writer_thread) = self.writer_thread.take() {
+            let _ = writer_thread.join();
+        }
+    }
+}
+
+pub fn replay_from_file(
+    path: &Path,
+    fn_event_callback: impl FnMut(
+        EventRecordDecoded,
+        /*stack_walk*/ Option<StackWalk>,
+        /*running_modules_map*/ &RunningModules,
+    ),
+) -> Result<usize> {
+    let file = File::open(path).map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))?;
+    replay(BufReader::new(file), fn_event_callback)
+}
+
+/// Drive the event callback by a recorded stream as the events come from a live session.
+/// The module events update a new RunningModules before passing to the callback.
+/// Returns the count of replayed lines.
+pub fn replay(
+    reader: impl BufRead,
+    mut fn_event_callback: impl FnMut(
+        EventRecordDecoded,
+        /*stack_walk*/ Option<StackWalk>,
+        /*running_modules_map*/ &RunningModules,
+    ),
+) -> Result<usize> {
+    // not init. the processes and drivers of current machine are not in the recorded stream
+    let running_modules = RunningModules::new(5, 10);
+    let mut count = 0usize;
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let replayed = serde_json::from_str::<EventReplayed>(line.as_str())
+            .map_err(|e| anyhow!("Failed to parse the line {}: {e}", line_number + 1))?;
+        let mut event = replayed.event;
+        if let Some(sw) = replayed.stack_walk {
+            fn_event_callback(event, Some(sw), &running_modules);
+        } else {
+            if event.provider_id.0 == Etw::ProcessGuid
+                || event.provider_id.0 == Etw::ImageLoadGuid
+            {
+                running_modules.handle_event_for_module(&mut event);
+            }
+            if replayed.is_enabled {
+                fn_event_callback(event, None, &running_modules);
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Drive the event callback by the events of a etl file that is recorded by xperf or wpr.
+/// The events are decoded as the events of live session. The events not in the events_desc are skipped.
+/// Returns the count of events passed to the callback.
+pub fn replay_etl(
+    path: &Path,
+    mut fn_event_callback: impl FnMut(
+        EventRecordDecoded,
+        /*stack_walk*/ Option<StackWalk>,
+        /*running_modules_map*/ &RunningModules,
+    ),
+) -> Result<usize> {
+    let mut reader = EtlReader::open(path)?;
+    let events_opcode_map = event_config::Config::new(events_desc()).events_opcode_map;
+    // not init. the processes and drivers of current machine are not in the file
+    let running_modules = RunningModules::new(5, 10);
+    let mut count = 0usize;
+    let mut skipped_count = 0usize;
+    while let Some(event) = reader.next_event()? {
+        let Some(logfile_header) = reader.logfile_header() else {
+            return Err(anyhow!("No logfile header in the first buffer"));
+        };
+        let Some((major_index, minor_index)) = events_opcode_map
+            .get(&(
+                GUID::from_u128(event.provider_id),
+                event.descriptor.opcode as u32,
+            ))
+            .copied()
+        else {
+            skipped_count += 1;
+            continue;
+        };
+        let major_name = events_desc()[major_index].major.name;
+        let minor_name = events_desc()[major_index].minors[minor_index].name;
+        let event_record = etl::event_record(&event, logfile_header);
+        let is_stack_walk = event_record.EventHeader.ProviderId == event_kernel::STACK_WALK_GUID;
+
+        // the StackWalk is decoded from the raw data, the tdh is not needed
+        // the covered kernel events are decoded by the layouts, others by the tdh of current machine
+        let mof_decoded = match event_decoder::decode_mof_event(&event_record) {
+            Some(Ok(event_record_decoded)) => Some(event_record_decoded),
+            Some(Err(e)) => {
+                debug!("Failed to decode_mof_event: {e} {major_name}-{minor_name}");
+                None
+            }
+            None => None,
+        };
+        let mut event_record_decoded = match mof_decoded {
+            Some(event_record_decoded) => event_record_decoded,
+            // the StackWalk is decoded from the raw data, the tdh is not needed
+            None if is_stack_walk => {
+                event_decoder::decode_kernel_event(&event_record, major_name, minor_name)
+            }
+            None => match event_decoder::Decoder::new(&event_record).and_then(|mut d| d.decode()) {
+                Ok(event_record_decoded) => event_record_decoded,
+                Err(e) => {
+                    debug!("Failed to decode: {e} {major_name}-{minor_name}");
+                    event_decoder::decode_kernel_event(&event_record, major_name, minor_name)
+                }
+            },
+        };
+        event_definitions::name_decoded(
+            events_desc(),
+            (major_index, minor_index),
+            &mut event_record_decoded,
+        );
+        if !is_stack_walk {
+            event_schema::record(&event_record_decoded);
+        }
+
+        if is_stack_walk {
+            let sw = etl::stack_walk(&event, logfile_header)?;
+            fn_event_callback(event_record_decoded, Some(sw), &running_modules);
+        } else {
+            let provider_id = event_record.EventHeader.ProviderId;
+            if provider_id == Etw::ProcessGuid || provider_id == Etw::ImageLoadGuid {
+                running_modules.handle_rundown_for_module(&mut event_record_decoded);
+            }
+            fn_event_callback(event_record_decoded, None, &running_modules);
+        }
+        count += 1;
+    }
+    info!(
+        "read {} buffers from {}, {count} events replayed, {skipped_count} events skipped",
+        reader.buffer_count(),
+        path.display()
+    );
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const STREAM: &str = r#"{"provider_id":"3D6FA8D1-FE05-11D0-9DDA-00C04FD7BA7C","event_guid":"3D6FA8D1-FE05-11D0-9DDA-00C04FD7BA7C","event_descriptor":{"Id":0,"Version":2,"Channel":0,"Level":0,"Opcode":1,"Task":0,"Keyword":0},"decoding_source":"DecodingSourceWbem","provider_name":"","level_name":"","channel_name":"","keywords_name":"","event_display_name":null,"event_name":"Thread","opcode_name":"Start","event_message":"","provider_message":"","process_id":4,"thread_id":16,"timestamp":"133627865451234567","properties":{"TThreadId":"0x10"},"stack_walk":null,"is_enabled":true}
+{"provider_id":"3D6FA8D1-FE05-11D0-9DDA-00C04FD7BA7C","event_guid":"3D6FA8D1-FE05-11D0-9DDA-00C04FD7BA7C","event_descriptor":{"Id":0,"Version":2,"Channel":0,"Level":0,"Opcode":2,"Task":0,"Keyword":0},"decoding_source":"DecodingSourceWbem","provider_name":"","level_name":"","channel_name":"","keywords_name":"","event_display_name":null,"event_name":"Thread","opcode_name":"End","event_message":"","provider_message":"","process_id":4,"thread_id":16,"timestamp":"133627865451234568","properties":{"TThreadId":"0x10"},"is_enabled":false}
+
+{"provider_id":"DEF2FE46-7BD6-4B80-BD94-F57FE20D0CE3","event_guid":"DEF2FE46-7BD6-4B80-BD94-F57FE20D0CE3","event_descriptor":{"Id":0,"Version":2,"Channel":0,"Level":0,"Opcode":32,"Task":0,"Keyword":0},"decoding_source":"DecodingSourceWbem","provider_name":"","level_name":"","channel_name":"","keywords_name":"","event_display_name":null,"event_name":"StackWalk","opcode_name":"Stack","event_message":"","provider_message":"","process_id":4,"thread_id":16,"timestamp":"133627865451234569","properties":{},"stack_walk":{"event_timestamp":133627865451234567,"stack_process":4,"stack_thread":16,"stacks":[["0",{"raw":140735000000000,"relative":null}]]}}
+"#;
+
+    #[test]
+    fn replay_stream() {
+        let mut events = vec![];
+        let count = replay(Cursor::new(STREAM), |event, stack_walk, _running_modules| {
+            events.push((event.opcode_name.clone(), stack_walk.map(|sw| sw.event_timestamp)));
+        })
+        .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            events,
+            vec![
+                ("Start".to_string(), None),
+                ("Stack".to_string(), Some(133627865451234567))
+            ]
+        );
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join("sys_monitor_record_and_replay.jsonl");
+        let mut events = vec![];
+        replay(Cursor::new(STREAM), |event, _stack_walk, _running_modules| {
+            events.push(event);
+        })
+        .unwrap();
+        {
+            let mut recorder = Recorder::create(&path).unwrap();
+            for event in events.iter() {
+                recorder.record(event, None, true);
+            }
+        }
+        let count = replay_from_file(&path, |_event, _stack_walk, _running_modules| {}).unwrap();
+        assert_eq!(count, 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    event_list::EventList,
+    event_record_model::{Columns, EventRecordModel, StackWalkSymbolized},
+    event_trace::EventRecordDecoded,
+    filter::ExpressionForOne,
+};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+use strum::{AsRefStr, VariantArray};
+
+// the count of rows is copied out of the list at a time. the list is not locked when writing
+const EXPORT_STEP: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, VariantArray, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for format in ExportFormat::VARIANTS {
+            if s == format.as_ref() {
+                return Ok(*format);
+            }
+        }
+        Err(anyhow!("invalid ExportFormat string: {s}"))
+    }
+}
+
+pub struct Exporter {
+    format: ExportFormat,
+    // None for all rows
+    filter: Option<ExpressionForOne>,
+    // the fields of properties as the extra columns of csv
+    properties: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct EventExported<'a> {
+    #[serde(flatten)]
+    event: &'a EventRecordDecoded,
+    process_path: &'a str,
+    stack_walk: Option<StackWalkSymbolized>,
+    stack_walk_2: Option<StackWalkSymbolized>,
+}
+
+impl Exporter {
+    pub fn new(
+        format: ExportFormat,
+        filter: Option<ExpressionForOne>,
+        properties: Vec<String>,
+    ) -> Self {
+        Self {
+            format,
+            filter,
+            properties,
+        }
+    }
+
+    pub fn export_to_file(
+        &self,
+        list: &EventList<EventRecordModel>,
+        path: &Path,
+    ) -> Result<usize> {
+        let file = File::create(path)
+            .map_err(|e| anyhow!("Failed to create {}: {e}", path.display()))?;
+        self.export(list, BufWriter::new(file))
+    }
+
+    /// Write the rows in the order of the list. Returns the count of written rows.
+    /// The rows are written by chunks, so the rows pushed or removed during exporting may be missed or repeated.
+    pub fn export(
+        &self,
+        list: &EventList<EventRecordModel>,
+        mut writer: impl Write,
+    ) -> Result<usize> {
+        if self.format == ExportFormat::Csv {
+            self.write_csv_header(&mut writer)?;
+        }
+        let mut count = 0usize;
+        let mut index = Some(0usize);
+        let mut rows = Vec::with_capacity(EXPORT_STEP);
+        while let Some(index_from) = index {
+            index = list.traversal_from(index_from, true, EXPORT_STEP, |_index, item| {
+                let is_matched = match self.filter {
+                    Some(ref filter) => filter.evaluate(
+                        |path, value| item.find_by_path_value(path, value),
+                        |value| item.find_by_value(value),
+                    )?,
+                    None => true,
+                };
+                if is_matched {
+                    rows.push(item.clone());
+                }
+                Ok(false)
+            })?;
+            for row in rows.drain(..) {
+                match self.format {
+                    ExportFormat::Csv => self.write_csv_row(&mut writer, &row)?,
+                    ExportFormat::JsonLines => self.write_json_line(&mut writer, &row)?,
+                }
+                count += 1;
+            }
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    fn write_csv_header(&self, writer: &mut impl Write) -> Result<()> {
+        let mut fields: Vec<String> = Columns::VARIANTS
+            .iter()
+            .map(|column| column.as_ref().to_string())
+            .collect();
+        for property in self.properties.iter() {
+            fields.push(format!("properties.{property}"));
+        }
+        write_csv_record(writer, fields.iter().map(|field| field.as_str()))
+    }
+
+    fn write_csv_row(&self, writer: &mut impl Write, row: &EventRecordModel) -> Result<()> {
+        let mut fields: Vec<String> = Columns::VARIANTS
+            .iter()
+            .map(|column| row.column_text(column))
+            .collect();
+        for property in self.properties.iter() {
+            fields.push(row.property_text(property).unwrap_or_default());
+        }
+        write_csv_record(writer, fields.iter().map(|field| field.as_str()))
+    }
+
+    fn write_json_line(&self, writer: &mut impl Write, row: &EventRecordModel) -> Result<()> {
+        let (stack_walk, stack_walk_2) = row.stack_walk_symbolized();
+        let event = EventExported {
+            event: row.array.as_ref(),
+            process_path: row.process_path.as_str(),
+            stack_walk,
+            stack_walk_2,
+        };
+        serde_json::to_writer(&mut *writer, &event)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Parse the fields of properties separated by comma. i.e. `FileName, IrpPtr`
+pub fn parse_properties(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|field| field.trim().trim_start_matches("properties.").to_string())
+        .filter(|field| !field.is_empty())
+        .collect()
+}
+
+// rfc4180: quote the field when it has comma, quote or line break
+fn write_csv_record<'a>(
+    writer: &mut impl Write,
+    fields: impl Iterator<Item = &'a str>,
+) -> Result<()> {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(csv_escape(field).as_bytes())?;
+    }
+    writer.write_all(b"\r\n")?;
+    Ok(())
+}
+
+fn csv_escape(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        std::borrow::Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        std::borrow::Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(csv_escape("abc"), "abc");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("a\nb"), "\"a\nb\"");
+    }
+
+    #[test]
+    fn csv_record() {
+        let mut buf = vec![];
+        write_csv_record(&mut buf, ["1", "a,b", ""].into_iter()).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "1,\"a,b\",\r\n");
+    }
+
+    #[test]
+    fn properties() {
+        assert_eq!(
+            parse_properties(" FileName, properties.IrpPtr ,,"),
+            vec!["FileName".to_string(), "IrpPtr".to_string()]
+        );
+    }
+
+    #[test]
+    fn format_from_str() {
+        assert_eq!(ExportFormat::from_str("csv").unwrap(), ExportFormat::Csv);
+        assert_eq!(
+            ExportFormat::from_str("json_lines").unwrap(),
+            ExportFormat::JsonLines
+        );
+        assert!(ExportFormat::from_str("xml").is_err());
+    }
+}
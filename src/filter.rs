@@ -1,6 +1,6 @@
 use crate::event_list::Node;
 use crate::event_record_model::{Columns, EventRecordModel};
-use crate::event_trace::EVENTS_DISPLAY_NAME_MAP;
+use crate::event_trace::{event_schema, EVENTS_DISPLAY_NAME_MAP};
 use anyhow::{anyhow, Result};
 use chumsky::prelude::*;
 use once_cell::sync::Lazy;
@@ -204,7 +204,7 @@ pub struct Path {
 
 impl ExpressionForOne {
     pub fn parse(src: &str) -> Result<ExpressionForOne> {
-        Self::build_parser()
+        let expression = Self::build_parser()
             .parse(src.trim())
             .into_result()
             .map_err(|e| {
@@ -220,8 +220,42 @@ impl ExpressionForOne {
                     );
                 });
                 anyhow!(s)
-            })
+            })?;
+        Ok(expression)
+    }
+
+    /// The `properties.*` fields that are not in any observed events of the schema registry.
+    /// Empty if no event is observed
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let mut fields = vec![];
+        self.visit_fields(&mut |path| {
+            if let Some(ref field) = path.field {
+                if path.key == Columns::Properties
+                    && event_schema::is_known_field(field) == Some(false)
+                    && !fields.contains(field)
+                {
+                    fields.push(field.clone());
+                }
+            }
+        });
+        fields
     }
+
+    fn visit_fields(&self, f: &mut impl FnMut(&Path)) {
+        match self {
+            ExpressionForOne::Parentheses(expr) | ExpressionForOne::Non(expr) => {
+                expr.visit_fields(f)
+            }
+            ExpressionForOne::And(expr_left, expr_right)
+            | ExpressionForOne::Or(expr_left, expr_right) => {
+                expr_left.visit_fields(f);
+                expr_right.visit_fields(f);
+            }
+            ExpressionForOne::KvPair { key, .. } => f(key),
+            ExpressionForOne::FindValue(_) => {}
+        }
+    }
+
     pub fn evaluate(
         &self,
         fn_path_value: impl Fn(/*path*/ &Path, /*value*/ &Value) -> Result<bool> + Clone,
@@ -395,6 +429,14 @@ impl ExpressionForOne {
                 just(columns[4].as_ref()).to(columns[4].clone()),
                 just(columns[5].as_ref()).to(columns[5].clone()),
                 just(columns[6].as_ref()).to(columns[6].clone()),
+                just(columns[7].as_ref()).to(columns[7].clone()),
+                just(columns[8].as_ref()).to(columns[8].clone()),
+                just(columns[9].as_ref()).to(columns[9].clone()),
+                just(columns[10].as_ref()).to(columns[10].clone()),
+                just(columns[11].as_ref()).to(columns[11].clone()),
+                just(columns[12].as_ref()).to(columns[12].clone()),
+                just(columns[13].as_ref()).to(columns[13].clone()),
+                just(columns[14].as_ref()).to(columns[14].clone()),
             ));
             let path = column
                 .then(just(".").ignore_then(text::ident()).or_not())
@@ -543,6 +585,14 @@ impl ExpressionForPair {
             just(columns[4].as_ref()).to(columns[4].clone()),
             just(columns[5].as_ref()).to(columns[5].clone()),
             just(columns[6].as_ref()).to(columns[6].clone()),
+            just(columns[7].as_ref()).to(columns[7].clone()),
+            just(columns[8].as_ref()).to(columns[8].clone()),
+            just(columns[9].as_ref()).to(columns[9].clone()),
+            just(columns[10].as_ref()).to(columns[10].clone()),
+            just(columns[11].as_ref()).to(columns[11].clone()),
+            just(columns[12].as_ref()).to(columns[12].clone()),
+            just(columns[13].as_ref()).to(columns[13].clone()),
+            just(columns[14].as_ref()).to(columns[14].clone()),
         ));
         let path = column
             .then(just(".").ignore_then(text::ident()).or_not())
@@ -640,6 +690,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn header_columns() {
+        let src = r#"cpu = 2 && event_property = 1"#;
+        let r = ExpressionForOne::parse(src.trim()).unwrap();
+        assert_eq!(
+            r,
+            ExpressionForOne::And(
+                Box::new(ExpressionForOne::KvPair {
+                    key: Path {
+                        key: Columns::Cpu,
+                        field: None,
+                    },
+                    value: Value::I64(2),
+                }),
+                Box::new(ExpressionForOne::KvPair {
+                    key: Path {
+                        key: Columns::EventProperty,
+                        field: None,
+                    },
+                    value: Value::I64(1),
+                }),
+            )
+        );
+    }
+
     #[test]
     fn expression_for_pair_succuss() {
         let src = r#"handle || memory"#;
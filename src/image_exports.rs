@@ -0,0 +1,314 @@
+//! The symbolization by the export directory of image when no pdb is available.
+//! The functions bounds are from the exception directory(.pdata) on x64, so the frame in a not exported
+//! function is not attributed to the previous export.
+use crate::pe_bytes::{self, c_str, u16_at, u32_at, SECTION_HEADER_SIZE};
+use anyhow::{anyhow, Result};
+use linked_hash_map::LinkedHashMap;
+use once_cell::sync::Lazy;
+use parking_lot::FairMutex;
+use std::{
+    collections::BTreeMap,
+    fs,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+const IMAGE_DIRECTORY_ENTRY_EXCEPTION: usize = 3;
+const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x20b;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+
+#[derive(Debug, Default)]
+pub struct ImageExports {
+    // of the file header. the file is rejected if it is not the loaded module
+    time_date_stamp: u32,
+    // rva -> name. the name is `#ordinal` if it is exported by ordinal only
+    functions: BTreeMap<u32, String>,
+    // the sorted (begin rva, end rva) of RUNTIME_FUNCTION
+    runtime_functions: Vec<(u32, u32)>,
+}
+
+impl ImageExports {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let data = fs::read(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let nt_header_offset = u32_at(data, 0x3c).ok_or_else(|| anyhow!("No dos header"))? as usize;
+        if data.get(nt_header_offset..nt_header_offset + 4) != Some(b"PE\0\0".as_slice()) {
+            return Err(anyhow!("No PE signature"));
+        }
+        let file_header = nt_header_offset + 4;
+        let machine = u16_at(data, file_header).ok_or_else(|| anyhow!("No file header"))?;
+        let number_of_sections = u16_at(data, file_header + 2).unwrap_or_default();
+        let size_of_optional_header = u16_at(data, file_header + 16).unwrap_or_default();
+        let optional_header = file_header + 20;
+        let magic = u16_at(data, optional_header).ok_or_else(|| anyhow!("No optional header"))?;
+        let is_64 = magic == IMAGE_NT_OPTIONAL_HDR64_MAGIC;
+        let (number_of_rva_and_sizes, data_directory) = if is_64 {
+            (u32_at(data, optional_header + 108), optional_header + 112)
+        } else {
+            (u32_at(data, optional_header + 92), optional_header + 96)
+        };
+        let number_of_rva_and_sizes = number_of_rva_and_sizes.unwrap_or_default() as usize;
+        let directory = |index: usize| -> Option<(u32, u32)> {
+            if index >= number_of_rva_and_sizes {
+                return None;
+            }
+            let rva = u32_at(data, data_directory + index * 8)?;
+            let size = u32_at(data, data_directory + index * 8 + 4)?;
+            (rva != 0 && size != 0).then_some((rva, size))
+        };
+
+        let section_headers = optional_header + size_of_optional_header as usize;
+        let section_headers = data
+            .get(section_headers..)
+            .map(|headers| {
+                &headers[..headers
+                    .len()
+                    .min(number_of_sections as usize * SECTION_HEADER_SIZE)]
+            })
+            .unwrap_or_default();
+        let rva_to_offset = |rva: u32| -> Option<usize> {
+            pe_bytes::rva_to_offset(section_headers, rva).and_then(|offset| offset.try_into().ok())
+        };
+
+        let mut exports = Self {
+            time_date_stamp: u32_at(data, file_header + 4).unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some((export_rva, export_size)) = directory(IMAGE_DIRECTORY_ENTRY_EXPORT) {
+            let export = rva_to_offset(export_rva).ok_or_else(|| anyhow!("No export section"))?;
+            let export_end = export_rva
+                .checked_add(export_size)
+                .ok_or_else(|| anyhow!("The export directory is out of range"))?;
+            let base = u32_at(data, export + 16).unwrap_or_default();
+            let number_of_functions = u32_at(data, export + 20).unwrap_or_default();
+            let number_of_names = u32_at(data, export + 24).unwrap_or_default();
+            let address_of_functions = u32_at(data, export + 28).and_then(rva_to_offset);
+            let address_of_names = u32_at(data, export + 32).and_then(rva_to_offset);
+            let address_of_name_ordinals = u32_at(data, export + 36).and_then(rva_to_offset);
+
+            let mut names = BTreeMap::new();
+            if let (Some(address_of_names), Some(address_of_name_ordinals)) =
+                (address_of_names, address_of_name_ordinals)
+            {
+                for i in 0..number_of_names as usize {
+                    let (Some(name_rva), Some(ordinal)) = (
+                        u32_at(data, address_of_names + i * 4),
+                        u16_at(data, address_of_name_ordinals + i * 2),
+                    ) else {
+                        break;
+                    };
+                    if let Some(name) =
+                        rva_to_offset(name_rva).and_then(|offset| c_str(data, offset))
+                    {
+                        names.insert(ordinal as u32, name);
+                    }
+                }
+            }
+            if let Some(address_of_functions) = address_of_functions {
+                for i in 0..number_of_functions {
+                    let Some(rva) = u32_at(data, address_of_functions + i as usize * 4) else {
+                        break;
+                    };
+                    // the forwarder string is in the export directory. i.e. NTDLL.RtlAllocateHeap
+                    if rva == 0 || (rva >= export_rva && rva < export_end) {
+                        continue;
+                    }
+                    let name = match names.remove(&i) {
+                        Some(name) => name,
+                        None => {
+                            let ordinal = base.checked_add(i).ok_or_else(|| {
+                                anyhow!("The ordinal base is out of range: {base}")
+                            })?;
+                            format!("#{ordinal}")
+                        }
+                    };
+                    exports.functions.entry(rva).or_insert(name);
+                }
+            }
+        }
+
+        if is_64 && machine == IMAGE_FILE_MACHINE_AMD64 {
+            if let Some((pdata_rva, pdata_size)) = directory(IMAGE_DIRECTORY_ENTRY_EXCEPTION) {
+                if let Some(pdata) = rva_to_offset(pdata_rva) {
+                    for i in 0..pdata_size as usize / 12 {
+                        let (Some(begin), Some(end)) = (
+                            u32_at(data, pdata + i * 12),
+                            u32_at(data, pdata + i * 12 + 4),
+                        ) else {
+                            break;
+                        };
+                        exports.runtime_functions.push((begin, end));
+                    }
+                    exports.runtime_functions.sort_unstable();
+                }
+            }
+        }
+        Ok(exports)
+    }
+
+    /// The nearest exported function. i.e. `CreateFileW+0x3a`.
+    /// None if the offset is in a function that is not exported, or before any export
+    pub fn get_location_info_by_offset(&self, offset: u32) -> Option<String> {
+        let (rva, name) = self
+            .functions
+            .range((Bound::Unbounded, Bound::Included(&offset)))
+            .next_back()?;
+        if !self.runtime_functions.is_empty() {
+            let index = self
+                .runtime_functions
+                .partition_point(|(begin, _)| *begin <= offset);
+            if let Some((begin, end)) = index.checked_sub(1).map(|i| self.runtime_functions[i]) {
+                // the function that contains the offset starts after the export
+                if offset < end && begin > *rva {
+                    return None;
+                }
+            }
+        }
+        Some(format!("{name}+{:#x}", offset - rva))
+    }
+}
+
+// key: (module path, TimeDateStamp). None if the file is failed to parse, it is not read again
+type ExportsMap = LinkedHashMap<(PathBuf, u32), Option<Arc<ImageExports>>>;
+
+static EXPORTS_LOADED: Lazy<FairMutex<ExportsMap>> =
+    Lazy::new(|| FairMutex::new(LinkedHashMap::new()));
+
+/// The function location by the exports of the module file on disk. i.e. `CreateFileW+0x3a`
+pub fn get_location_info(
+    module_name: &Path,
+    module_time_date_stamp: u32,
+    offset: u32,
+) -> Result<String> {
+    let key = (module_name.to_path_buf(), module_time_date_stamp);
+    let exports = EXPORTS_LOADED.lock().get(&key).cloned();
+    let exports = match exports {
+        Some(Some(exports)) => exports,
+        Some(None) => return Err(anyhow!("No exports of {}", module_name.display())),
+        None => {
+            let r = ImageExports::from_file(module_name).and_then(|exports| {
+                // the file is replaced after the module is loaded
+                if exports.time_date_stamp != module_time_date_stamp {
+                    return Err(anyhow!(
+                        "The TimeDateStamp {:#x} of {} is not {module_time_date_stamp:#x} of the module",
+                        exports.time_date_stamp,
+                        module_name.display()
+                    ));
+                }
+                Ok(Arc::new(exports))
+            });
+            EXPORTS_LOADED.lock().insert(key, r.as_ref().ok().cloned());
+            r?
+        }
+    };
+    exports
+        .get_location_info_by_offset(offset)
+        .ok_or_else(|| anyhow!("No export for {:#x} in {}", offset, module_name.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u16(data: &mut [u8], offset: usize, value: u16) {
+        data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // a x64 image with one section at rva 0x1000 that is at 0x400 of file
+    fn image() -> Vec<u8> {
+        let mut data = vec![0u8; 0x800];
+        put_u32(&mut data, 0x3c, 0x80);
+        data[0x80..0x84].copy_from_slice(b"PE\0\0");
+        put_u16(&mut data, 0x84, IMAGE_FILE_MACHINE_AMD64);
+        put_u16(&mut data, 0x86, 1);
+        put_u16(&mut data, 0x94, 240);
+        let optional_header = 0x98;
+        put_u16(&mut data, optional_header, IMAGE_NT_OPTIONAL_HDR64_MAGIC);
+        put_u32(&mut data, optional_header + 108, 16);
+        // export directory at 0x1000, pdata at 0x1200
+        put_u32(&mut data, optional_header + 112, 0x1000);
+        put_u32(&mut data, optional_header + 116, 0x100);
+        put_u32(&mut data, optional_header + 112 + 3 * 8, 0x1200);
+        put_u32(&mut data, optional_header + 112 + 3 * 8 + 4, 36);
+        let section = optional_header + 240;
+        put_u32(&mut data, section + 8, 0x400);
+        put_u32(&mut data, section + 12, 0x1000);
+        put_u32(&mut data, section + 16, 0x400);
+        put_u32(&mut data, section + 20, 0x400);
+
+        // 3 functions: CreateFileW, ReadFile and one by ordinal only
+        let export = 0x400;
+        put_u32(&mut data, export + 16, 1);
+        put_u32(&mut data, export + 20, 3);
+        put_u32(&mut data, export + 24, 2);
+        put_u32(&mut data, export + 28, 0x1040);
+        put_u32(&mut data, export + 32, 0x1050);
+        put_u32(&mut data, export + 36, 0x1060);
+        put_u32(&mut data, 0x440, 0x2000);
+        put_u32(&mut data, 0x444, 0x2100);
+        put_u32(&mut data, 0x448, 0x2300);
+        put_u32(&mut data, 0x450, 0x1070);
+        put_u32(&mut data, 0x454, 0x1080);
+        put_u16(&mut data, 0x460, 0);
+        put_u16(&mut data, 0x462, 1);
+        data[0x470..0x47c].copy_from_slice(b"CreateFileW\0");
+        data[0x480..0x489].copy_from_slice(b"ReadFile\0");
+
+        // the not exported function at 0x2200 is between ReadFile and the ordinal
+        let pdata = 0x600;
+        for (i, (begin, end)) in [(0x2000, 0x2080), (0x2100, 0x2180), (0x2200, 0x2280)]
+            .into_iter()
+            .enumerate()
+        {
+            put_u32(&mut data, pdata + i * 12, begin);
+            put_u32(&mut data, pdata + i * 12 + 4, end);
+        }
+        data
+    }
+
+    #[test]
+    fn exports() {
+        let exports = ImageExports::parse(&image()).unwrap();
+        assert_eq!(exports.functions.len(), 3);
+        assert_eq!(exports.runtime_functions.len(), 3);
+        assert_eq!(
+            exports.get_location_info_by_offset(0x203a).as_deref(),
+            Some("CreateFileW+0x3a")
+        );
+        assert_eq!(
+            exports.get_location_info_by_offset(0x2110).as_deref(),
+            Some("ReadFile+0x10")
+        );
+        assert_eq!(exports.get_location_info_by_offset(0x2210), None);
+        assert_eq!(
+            exports.get_location_info_by_offset(0x2301).as_deref(),
+            Some("#3+0x1")
+        );
+        assert_eq!(exports.get_location_info_by_offset(0x1fff), None);
+    }
+
+    #[test]
+    fn exports_out_of_range() {
+        let mut data = image();
+        put_u32(&mut data, 0x88, 0x5e8c1b2a);
+        assert_eq!(
+            ImageExports::parse(&data).unwrap().time_date_stamp,
+            0x5e8c1b2a
+        );
+        // the ordinal of #3
+        put_u32(&mut data, 0x400 + 16, u32::MAX);
+        assert!(ImageExports::parse(&data).is_err());
+        let mut data = image();
+        put_u32(&mut data, 0x98 + 116, u32::MAX);
+        assert!(ImageExports::parse(&data).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+//! The symbolization by pdbs that does not depend on Windows. It is shared by the monitor and the offline
+//! symbolizer `pdb_symbolize`
+#![feature(btree_cursors, map_try_insert)]
+
+pub mod demangle;
+pub mod etl;
+pub mod pdb_info;
+pub mod pe_bytes;
+pub mod source_snippet;
+pub mod symbol_index;
+pub mod symbol_path;
+pub mod symbol_server;
@@ -9,13 +9,21 @@ use slint::{
     Model, ModelRc, PhysicalPosition, SharedString, StandardListViewItem, TableColumn, VecModel,
 };
 use std::{
+    cell::RefCell,
     fs::create_dir_all,
-    path::Path,
+    path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    thread,
 };
 use strum::VariantArray;
+use sys_monitor::{
+    demangle, etl, pdb_info, pe_bytes, source_snippet, symbol_index, symbol_path, symbol_server,
+};
 use tracing::{error, info, warn};
 use tracing_subscriber::{
     filter::{EnvFilter, LevelFilter},
@@ -27,8 +35,12 @@ mod event_list;
 mod event_list_model;
 mod event_record_model;
 mod event_trace;
+mod export;
 mod filter;
+mod image_exports;
 mod pdb;
+mod session;
+mod symbolizer;
 mod third_extend;
 mod utils;
 
@@ -38,6 +50,8 @@ static LOG_TARGET_MAP: phf::Map<&'static str, (&'static str, LevelFilter)> = phf
     "miss_stack_walk" => ("sys_monitor::event_trace::stack_walk", LevelFilter::INFO),
 };
 const LOG_LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error"];
+// the lines before and after the code line of a frame
+const SOURCE_SNIPPET_CONTEXT: usize = 5;
 
 fn main() {
     let file_appender = tracing_appender::rolling::never("./logs", "logs.log");
@@ -57,6 +71,24 @@ fn main() {
     subscriber.init();
 
     event_trace::process_modules::init();
+    let mut definitions_errors = vec![];
+    if let Some(path) = event_trace::event_definitions::DEFINITIONS_FILES
+        .iter()
+        .map(Path::new)
+        .find(|path| path.is_file())
+    {
+        match event_trace::event_definitions::init(path) {
+            Ok(errors) => definitions_errors.extend(
+                errors
+                    .into_iter()
+                    .map(|e| format!("{}: {e}", path.display())),
+            ),
+            Err(e) => {
+                error!("{e}");
+                definitions_errors.push(e.to_string());
+            }
+        }
+    }
 
     let app = App::new().unwrap();
     let window = app.window();
@@ -83,12 +115,33 @@ fn main() {
     let event_list_arc =
         Arc::new(event_list::EventList::<event_record_model::EventRecordModel>::new());
     let event_list_arc_1 = event_list_arc.clone();
+    let event_list_arc_2 = event_list_arc.clone();
+    let event_list_arc_3 = event_list_arc.clone();
+    let event_list_arc_4 = event_list_arc.clone();
+    let event_list_arc_5 = event_list_arc.clone();
 
     let event_list_model_rc = Rc::new(event_list_model::ListModel::new(event_list_arc));
     let event_list_model_rc_1 = event_list_model_rc.clone();
     let event_list_model_rc_2 = event_list_model_rc.clone();
     let event_list_model_rc_3 = event_list_model_rc.clone();
     let event_list_model_rc_4 = event_list_model_rc.clone();
+    let event_list_model_rc_5 = event_list_model_rc.clone();
+    let event_list_model_rc_6 = event_list_model_rc.clone();
+    let event_list_model_rc_7 = event_list_model_rc.clone();
+    let event_list_model_rc_8 = event_list_model_rc.clone();
+    let event_list_model_rc_9 = event_list_model_rc.clone();
+    let event_list_model_rc_10 = event_list_model_rc.clone();
+    let event_list_model_rc_11 = event_list_model_rc.clone();
+
+    // the rows pushed by the trace and by unfreezing are notified in order through the same one
+    let mut delay_notify_arc = Arc::new(delay_notify::DelayNotify::new(
+        delay_notify::AppNotifySink(app.as_weak()),
+        100,
+        200,
+    ));
+    Arc::get_mut(&mut delay_notify_arc).unwrap().init();
+    let delay_notify_2 = delay_notify_arc.clone();
+    let delay_notify_3 = delay_notify_arc.clone();
 
     let row_data: ModelRc<ModelRc<StandardListViewItem>> = ModelRc::from(event_list_model_rc);
     let column_names_rc = Rc::new(VecModel::default());
@@ -100,8 +153,16 @@ fn main() {
             Columns::ProcessName => 150.0,
             Columns::ProcessId => 110.0,
             Columns::ThreadId => 100.0,
+            Columns::Cpu => 60.0,
             Columns::EventName => 120.0,
             Columns::OpcodeName => 140.0,
+            Columns::KernelTime => 100.0,
+            Columns::UserTime => 100.0,
+            Columns::ActivityId => 280.0,
+            Columns::RelatedActivityId => 280.0,
+            Columns::HeaderFlags => 100.0,
+            Columns::EventProperty => 120.0,
+            Columns::Extended => 200.0,
             Columns::Properties => 200.0,
         };
         column_names_rc.push(table_column);
@@ -124,39 +185,145 @@ fn main() {
             ret
         });
     app.global::<EventsViewData>()
-        .on_stack_walk(move |index_row| {
+        .on_row_data_raw(move |index_row| {
+            let mut ret = SharedString::from("");
+            if let Some(row) = event_list_model_rc_9.row_data_detail(index_row as usize) {
+                if let Some(row_item) = row
+                    .value
+                    .as_any()
+                    .downcast_ref::<event_record_model::EventRecordModel>()
+                {
+                    ret = SharedString::from(row_item.array.raw.hex_dump());
+                }
+            }
+            ret
+        });
+    let app_weak = app.as_weak();
+    // the results of the previous rows are dropped if they arrive late
+    let stack_walk_generation = Arc::new(AtomicU64::new(0));
+    app.global::<EventsViewData>()
+        .on_stack_walk(move |index_row, demangled| {
+            let generation = stack_walk_generation.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut stacks = (None, None);
             if let Some(row) = event_list_model_rc_2.row_data_detail(index_row as usize) {
                 if let Some(row_item) = row
                     .value
                     .as_any()
                     .downcast_ref::<event_record_model::EventRecordModel>()
                 {
-                    return row_item.stack_walk();
+                    let app_weak_1 = app_weak.clone();
+                    let stack_walk_generation_1 = stack_walk_generation.clone();
+                    stacks = row_item.stack_walk_symbolize_async(move |stacks, stacks_2| {
+                        let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| {
+                            if stack_walk_generation_1.load(Ordering::Relaxed) != generation {
+                                return;
+                            }
+                            app_handle.global::<EventsViewData>().set_stacks_info((
+                                event_record_model::stack_walk_info(stacks, demangled),
+                                event_record_model::stack_walk_info(stacks_2, demangled),
+                            ));
+                        });
+                    });
                 }
             }
-            (StackWalkInfo::default(), StackWalkInfo::default())
+            let app_handle = app_weak.unwrap();
+            app_handle.global::<EventsViewData>().set_stacks_info((
+                event_record_model::stack_walk_info(stacks.0, demangled),
+                event_record_model::stack_walk_info(stacks.1, demangled),
+            ));
+        });
+    let app_weak = app.as_weak();
+    app.global::<EventsViewData>()
+        .on_row_find(move |text, index_from, is_forward| {
+            if text.is_empty() {
+                return (SharedString::default(), true);
+            }
+            let fe = match filter::ExpressionForOne::parse(text.as_str()) {
+                Ok(fe) => fe,
+                Err(e) => return (SharedString::from(e.to_string()), false),
+            };
+            let app_weak_1 = app_weak.clone();
+            event_list_model_rc_3.row_find_next(
+                fe,
+                if index_from < 0 {
+                    None
+                } else {
+                    Some(index_from as usize)
+                },
+                is_forward,
+                move |r| {
+                    let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| match r {
+                        Ok(index) => app_handle.invoke_row_find_finished(
+                            index.map(|index| index as i32).unwrap_or(-1),
+                            SharedString::default(),
+                        ),
+                        Err(e) => app_handle
+                            .invoke_row_find_finished(-1, SharedString::from(e.to_string())),
+                    });
+                },
+            );
+            (SharedString::default(), true)
+        });
+    let app_weak = app.as_weak();
+    app.global::<EventsViewData>()
+        .on_row_find_marks(move |text, bucket_count| {
+            let fe = if text.is_empty() {
+                None
+            } else {
+                filter::ExpressionForOne::parse(text.as_str()).ok()
+            };
+            let Some(fe) = fe else {
+                app_weak
+                    .unwrap()
+                    .global::<EventsViewData>()
+                    .set_find_marks(ModelRc::default());
+                return;
+            };
+            let app_weak_1 = app_weak.clone();
+            event_list_model_rc_5.row_find_marks(fe, bucket_count.max(1) as usize, move |r| {
+                let marks = r.unwrap_or_else(|e| {
+                    error!("Failed to find marks: {e}");
+                    vec![]
+                });
+                let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| {
+                    app_handle
+                        .global::<EventsViewData>()
+                        .set_find_marks(ModelRc::new(VecModel::from(marks)));
+                });
+            });
+        });
+    app.global::<EventsViewData>()
+        .on_row_toggle_bookmark(move |index_row| {
+            if index_row >= 0 {
+                event_list_model_rc_10.row_toggle_bookmark(index_row as usize);
+            }
+        });
+    let app_weak = app.as_weak();
+    app.global::<EventsViewData>()
+        .on_row_find_bookmark(move |index_from, is_forward| {
+            let app_weak_1 = app_weak.clone();
+            event_list_model_rc_11.row_find_bookmark(
+                if index_from < 0 {
+                    None
+                } else {
+                    Some(index_from as usize)
+                },
+                is_forward,
+                move |r| {
+                    let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| match r {
+                        Ok(index) => app_handle.invoke_row_find_finished(
+                            index.map(|index| index as i32).unwrap_or(-1),
+                            SharedString::default(),
+                        ),
+                        Err(e) => app_handle
+                            .invoke_row_find_finished(-1, SharedString::from(e.to_string())),
+                    });
+                },
+            );
         });
-    app.global::<EventsViewData>().on_row_find(move |text| {
-        if text.is_empty() {
-            return (SharedString::default(), ModelRc::default(), true);
-        }
-        let r = filter::ExpressionForOne::parse(text.as_str());
-        let fe = match r {
-            Ok(fe) => fe,
-            Err(e) => return (SharedString::from(e.to_string()), ModelRc::default(), false),
-        };
-        match event_list_model_rc_3.row_find(&fe) {
-            Ok(vec) => (
-                SharedString::default(),
-                ModelRc::new(VecModel::from(vec)),
-                true,
-            ),
-            Err(e) => (SharedString::from(e.to_string()), ModelRc::default(), false),
-        }
-    });
 
     let mut event_descs = vec![];
-    for major in event_trace::EVENTS_DESC.iter() {
+    for major in event_trace::events_desc().iter() {
         let mut minors: Vec<(bool, SharedString)> = vec![];
         for minor in major.minors {
             minors.push((
@@ -177,6 +344,8 @@ fn main() {
     }
     app.global::<EnablesData>()
         .set_event_descs(ModelRc::from(event_descs.as_slice()));
+    app.global::<EnablesData>()
+        .set_definitions_errors(definitions_errors.join("\n").into());
     app.global::<EnablesData>()
         .on_toggled_major(|index, checked| {
             event_trace::Controller::set_config_enables(index as usize, None, checked);
@@ -211,33 +380,136 @@ fn main() {
         )
     });
 
-    app.on_set_filter_expression_for_one(|text| {
+    // the texts of applied filters for saving session
+    let session_filters_rc = Rc::new(RefCell::new(session::SessionFilters::default()));
+    let session_filters_rc_1 = session_filters_rc.clone();
+    let session_filters_rc_2 = session_filters_rc.clone();
+    app.on_set_filter_expression_for_one(move |text| {
         if text.is_empty() {
             filter::filter_expression_for_one_set(None);
+            session_filters_rc_1.borrow_mut().for_one = String::new();
             return (SharedString::new(), true);
         }
         match filter::ExpressionForOne::parse(text.as_str()) {
             Err(e) => (SharedString::from(e.to_string()), false),
             Ok(ok) => {
+                // still applied, the fields may be observed later
+                let unknown_fields = ok.unknown_fields();
                 filter::filter_expression_for_one_set(Some(ok));
-                (SharedString::new(), true)
+                session_filters_rc_1.borrow_mut().for_one = text.to_string();
+                if unknown_fields.is_empty() {
+                    (SharedString::new(), true)
+                } else {
+                    let message = format!(
+                        "update success! but the fields are not observed: {}",
+                        unknown_fields.join(", ")
+                    );
+                    (SharedString::from(message), true)
+                }
             }
         }
     });
-    app.on_set_filter_expression_for_pair(|text| {
+    app.on_set_filter_expression_for_pair(move |text| {
         if text.is_empty() {
             filter::filter_expression_for_pair_set(vec![]);
+            session_filters_rc_2.borrow_mut().for_pair = String::new();
             return (SharedString::new(), true);
         }
         match filter::ExpressionForPair::parse(text.as_str()) {
             Err(e) => (SharedString::from(e.to_string()), false),
             Ok(ok) => {
                 filter::filter_expression_for_pair_set(ok);
+                session_filters_rc_2.borrow_mut().for_pair = text.to_string();
                 (SharedString::new(), true)
             }
         }
     });
 
+    let app_weak = app.as_weak();
+    app.on_save_session(move |path| {
+        let path = PathBuf::from(path.as_str());
+        if path.as_os_str().is_empty() {
+            return (SharedString::from("The path is empty"), false);
+        }
+        let filters = session_filters_rc.borrow().clone();
+        let event_list_arc = event_list_arc_3.clone();
+        let app_weak_1 = app_weak.clone();
+        thread::spawn(move || {
+            let (message, is_ok) = match session::save(&event_list_arc, &filters, &path) {
+                Ok(count) => {
+                    info!("saved {count} rows to {}", path.display());
+                    (format!("{count} rows saved"), true)
+                }
+                Err(e) => {
+                    error!("Failed to save session: {e}");
+                    (e.to_string(), false)
+                }
+            };
+            let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| {
+                app_handle.invoke_session_finished(is_ok, SharedString::from(message));
+            });
+        });
+        (SharedString::new(), true)
+    });
+
+    let app_weak = app.as_weak();
+    app.on_load_session(move |path| {
+        if event_trace::Controller::is_running() {
+            return (
+                SharedString::from("Stop the trace before loading a session"),
+                false,
+            );
+        }
+        // the loaded rows are buffered until unfreezing when the view is frozen
+        if event_list_model_rc_7.is_frozen() {
+            return (
+                SharedString::from("Unfreeze the view before loading a session"),
+                false,
+            );
+        }
+        let path = PathBuf::from(path.as_str());
+        if !path.is_file() {
+            return (SharedString::from("The file is not exist"), false);
+        }
+        event_list_model_rc_7.clear();
+        let event_list_arc = event_list_arc_4.clone();
+        let app_weak_1 = app_weak.clone();
+        thread::spawn(move || {
+            let r = session::load(&event_list_arc, &path);
+            let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| {
+                let row_data = app_handle.global::<EventsViewData>().get_row_data();
+                let rows = row_data
+                    .as_any()
+                    .downcast_ref::<event_list_model::ListModel>()
+                    .unwrap();
+                rows.notify_reset();
+                match r {
+                    Ok(loaded) => {
+                        info!("loaded {} rows from {}", loaded.count, path.display());
+                        let for_one = SharedString::from(loaded.filters.for_one);
+                        let for_pair = SharedString::from(loaded.filters.for_pair);
+                        app_handle.set_filter_text_for_one(for_one.clone());
+                        app_handle.set_filter_text_for_pair(for_pair.clone());
+                        let _ = app_handle.invoke_set_filter_expression_for_one(for_one);
+                        let _ = app_handle.invoke_set_filter_expression_for_pair(for_pair);
+                        app_handle.invoke_session_finished(
+                            true,
+                            SharedString::from(format!("{} rows loaded", loaded.count)),
+                        );
+                    }
+                    Err(e) => {
+                        error!("Failed to load session: {e}");
+                        app_handle
+                            .invoke_session_finished(false, SharedString::from(e.to_string()));
+                    }
+                }
+            });
+        });
+        (SharedString::new(), true)
+    });
+
+    // the pdb directory of exe, then the _NT_SYMBOL_PATH
+    let mut symbol_path_sources = vec![];
     match utils::get_exe_dir() {
         Err(e) => warn!("{e}"),
         Ok(path) => {
@@ -246,20 +518,148 @@ fn main() {
             if let Err(e) = create_dir_all(dir) {
                 error!("{e}");
             } else {
-                pdb::pdb_path_set(s.as_str());
-                app.set_pdb_directory(SharedString::from(s.as_str()))
+                symbol_path_sources.push(s);
+            }
+            // the parsed pdbs are cached in the index files for the next runs
+            let s = format!("{path}\\symbol_index");
+            let dir = Path::new(s.as_str());
+            if let Err(e) = create_dir_all(dir) {
+                error!("{e}");
+            } else {
+                pdb::symbol_index_dir_set(dir);
             }
         }
     }
-    app.on_edit_pdb_directory(|path| {
-        let dir = Path::new(path.as_str());
-        if !dir.exists() {
-            return (SharedString::from("The path is not exist"), false);
+    if let Ok(nt_symbol_path) = std::env::var("_NT_SYMBOL_PATH") {
+        symbol_path_sources.push(nt_symbol_path);
+    }
+    pdb::symbol_path_init(&symbol_path_sources);
+    app.set_pdb_directory(SharedString::from(pdb::symbol_path_get()));
+    app.on_edit_pdb_directory(|path| match pdb::symbol_path_set(path.as_str()) {
+        Err(e) => (SharedString::from(e.to_string()), false),
+        Ok(_) => (SharedString::new(), true),
+    });
+    app.on_pdbs_resolved(|| SharedString::from(pdb::pdbs_resolved_text()));
+    app.set_source_roots(SharedString::from(source_snippet::source_roots_get()));
+    app.on_edit_source_roots(|s| match source_snippet::source_roots_set(s.as_str()) {
+        Err(e) => (SharedString::from(e.to_string()), false),
+        Ok(_) => (SharedString::new(), true),
+    });
+    app.global::<EventsViewData>()
+        .on_source_snippet(|line_location| {
+            // the frame without line or not symbolized yet
+            if line_location.is_empty() {
+                return SharedString::new();
+            }
+            match source_snippet::snippet(line_location.as_str(), SOURCE_SNIPPET_CONTEXT) {
+                Ok(s) => SharedString::from(s),
+                Err(e) => SharedString::from(format!("{e:#}")),
+            }
+        });
+
+    let app_weak = app.as_weak();
+    app.on_export_events(move |path, format, properties, filter_text| {
+        let format = match export::ExportFormat::from_str(format.as_str()) {
+            Ok(format) => format,
+            Err(e) => return (SharedString::from(e.to_string()), false),
+        };
+        let filter = if filter_text.trim().is_empty() {
+            None
+        } else {
+            match filter::ExpressionForOne::parse(filter_text.as_str()) {
+                Ok(fe) => Some(fe),
+                Err(e) => return (SharedString::from(e.to_string()), false),
+            }
+        };
+        let path = PathBuf::from(path.as_str());
+        if path.as_os_str().is_empty() {
+            return (SharedString::from("The path is empty"), false);
         }
-        if !dir.is_dir() {
-            return (SharedString::from("The path is not directory"), false);
+        let exporter =
+            export::Exporter::new(format, filter, export::parse_properties(properties.as_str()));
+        let event_list_arc = event_list_arc_2.clone();
+        let app_weak_1 = app_weak.clone();
+        thread::spawn(move || {
+            let (message, is_ok) = match exporter.export_to_file(&event_list_arc, &path) {
+                Ok(count) => {
+                    info!("exported {count} rows to {}", path.display());
+                    (format!("{count} rows exported"), true)
+                }
+                Err(e) => {
+                    error!("Failed to export: {e}");
+                    (e.to_string(), false)
+                }
+            };
+            let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| {
+                app_handle.invoke_export_finished(is_ok, SharedString::from(message));
+            });
+        });
+        (SharedString::new(), true)
+    });
+
+    app.on_record_events(|path, is_recording| {
+        let r = if is_recording {
+            if path.is_empty() {
+                return (SharedString::from("The path is empty"), false);
+            }
+            event_trace::Controller::record_set(Some(Path::new(path.as_str())))
+        } else {
+            event_trace::Controller::record_set(None)
+        };
+        match r {
+            Ok(()) => (SharedString::new(), true),
+            Err(e) => (SharedString::from(e.to_string()), false),
+        }
+    });
+
+    let app_weak = app.as_weak();
+    app.on_replay_events(move |path| {
+        if event_trace::Controller::is_running() {
+            return (SharedString::from("Stop the trace before replaying"), false);
+        }
+        // the replayed rows are buffered until unfreezing when the view is frozen
+        if event_list_model_rc_8.is_frozen() {
+            return (
+                SharedString::from("Unfreeze the view before replaying"),
+                false,
+            );
+        }
+        let path = PathBuf::from(path.as_str());
+        if !path.is_file() {
+            return (SharedString::from("The file is not exist"), false);
         }
-        pdb::pdb_path_set(path.as_str());
+        event_list_model_rc_8.clear();
+        let event_list_arc = event_list_arc_5.clone();
+        let delay_notify = delay_notify_3.clone();
+        let app_weak_1 = app_weak.clone();
+        thread::spawn(move || {
+            let event_callback = event_callback_new(event_list_arc, delay_notify);
+            // the etl file is recorded by xperf or wpr, others are recorded by this
+            let r = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("etl")) {
+                event_trace::replay::replay_etl(&path, event_callback)
+            } else {
+                event_trace::replay::replay_from_file(&path, event_callback)
+            };
+            let _ = app_weak_1.upgrade_in_event_loop(move |app_handle| {
+                let row_data = app_handle.global::<EventsViewData>().get_row_data();
+                let rows = row_data
+                    .as_any()
+                    .downcast_ref::<event_list_model::ListModel>()
+                    .unwrap();
+                rows.notify_reset();
+                let (message, is_ok) = match r {
+                    Ok(count) => {
+                        info!("replayed {count} events from {}", path.display());
+                        (format!("{count} events replayed"), true)
+                    }
+                    Err(e) => {
+                        error!("Failed to replay: {e}");
+                        (e.to_string(), false)
+                    }
+                };
+                app_handle.invoke_session_finished(is_ok, SharedString::from(message));
+            });
+        });
         (SharedString::new(), true)
     });
 
@@ -317,121 +717,42 @@ fn main() {
         let _ = targets_filter_handle.modify(|_filter| {});
     });
 
+    // the item is `event display name / opcode name`
+    app.on_schema_events(|| {
+        let items: Vec<StandardListViewItem> = event_trace::event_schema::events()
+            .into_iter()
+            .map(|(event_name, opcode_name)| {
+                StandardListViewItem::from(SharedString::from(format!(
+                    "{event_name} / {opcode_name}"
+                )))
+            })
+            .collect();
+        ModelRc::new(VecModel::from(items))
+    });
+    app.on_schema_fields(|event| {
+        let Some((event_name, opcode_name)) = event.split_once(" / ") else {
+            return SharedString::default();
+        };
+        event_trace::event_schema::schema(event_name, opcode_name)
+            .map(|schema| SharedString::from(schema.fields_text()))
+            .unwrap_or_default()
+    });
+
     app.on_clear(move || {
         event_list_model_rc_4.clear();
     });
 
-    let app_weak = app.as_weak();
+    app.on_freeze(move |is_frozen| {
+        if is_frozen {
+            event_list_model_rc_6.freeze();
+        } else {
+            event_list_model_rc_6.unfreeze(delay_notify_arc.clone());
+        }
+    });
+
     app.on_trace_start(move || {
-        let app_weak_1 = app_weak.clone();
-        let event_list_arc_1 = event_list_arc_1.clone();
-        let mut stack_walk_map = event_trace::StackWalkMap::<
-            Option<Weak<event_list::Node<EventRecordModel>>>,
-        >::new(32, 10, 15);
-        let mut delay_notify = Box::new(delay_notify::DelayNotify::new(100, 200));
-        delay_notify.init(app_weak_1.clone());
         let result = event_trace::Controller::start(
-            move |event_record, stack_walk, running_modules_map| {
-                let process_id = event_record.process_id;
-                let thread_id = event_record.thread_id;
-                let timestamp = event_record.timestamp.0;
-
-                if let Some(mut sw) = stack_walk {
-                    if let Some((some_row, is_from_second_sw_map)) =
-                        stack_walk_map.remove(&(sw.stack_thread, sw.event_timestamp), timestamp)
-                    {
-                        if let Some(weak) = some_row.0 {
-                            if let Some(arc_node) = weak.upgrade() {
-                                running_modules_map.convert_to_module_offset(
-                                    sw.stack_process,
-                                    event_record.timestamp,
-                                    sw.stacks.as_mut_slice(),
-                                );
-                                let erm = arc_node
-                                    .value
-                                    .as_any()
-                                    .downcast_ref::<event_record_model::EventRecordModel>()
-                                    .unwrap();
-                                if is_from_second_sw_map {
-                                    erm.set_stack_walk_2(sw);
-                                } else {
-                                    erm.set_stack_walk(sw);
-                                }
-                            }
-                        }
-                    } else {
-                        error!(
-                            "Can't find event: {}:{}:{} for the stack walk: {}:{}:{}",
-                            sw.stack_process,
-                            sw.stack_thread as i32,
-                            utils::TimeStamp(sw.event_timestamp).to_string_detail(),
-                            process_id as i32,
-                            thread_id as i32,
-                            utils::TimeStamp(timestamp).to_string_detail(),
-                        );
-                    }
-
-                    return;
-                }
-
-                // get_process_path_by_id need to be before handle_event_for_module. because handle_event_for_module may be remove the process by the "process end" event.
-                let process_path =
-                    running_modules_map.get_process_path_by_id(process_id, event_record.timestamp);
-
-                let debug_msg = format!(
-                    "{}-{} in stack_walk_map",
-                    event_record.event_name, event_record.opcode_name
-                );
-                let er = event_record_model::EventRecordModel::new(event_record, process_path);
-                let is_matched = match filter::filter_for_one(
-                    |path, value| er.find_by_path_value(path, value),
-                    |value| er.find_by_value(value),
-                ) {
-                    Err(e) => {
-                        error!("Failed to filter: {e}");
-                        return;
-                    }
-                    Ok(is_matched) => is_matched,
-                };
-
-                let row_arc = Arc::new(event_list::Node::new(er));
-                let mut is_push_to_list = false;
-                let mut notify: Option<delay_notify::Notify> = None;
-                if is_matched {
-                    match filter::filter_for_pair(&row_arc) {
-                        Err(e) => {
-                            error!("Failed to filter: {e}");
-                            is_push_to_list = true;
-                        }
-                        Ok(ok) => {
-                            if let Some(node) = ok {
-                                event_list_arc_1.remove(node);
-                                notify = Some(delay_notify::Notify::Remove);
-                            } else {
-                                is_push_to_list = true;
-                            }
-                        }
-                    }
-                }
-
-                if is_push_to_list {
-                    stack_walk_map.insert(
-                        (thread_id, timestamp),
-                        Some(Arc::downgrade(&row_arc)),
-                        debug_msg,
-                    );
-                    let index = event_list_arc_1.push(row_arc);
-                    notify = Some(delay_notify::Notify::Push(index, 1));
-                } else {
-                    stack_walk_map.insert((thread_id, timestamp), None, debug_msg);
-                }
-
-                if let Some(notify) = notify {
-                    delay_notify.notify(app_weak_1.clone(), notify);
-                }
-
-                return;
-            },
+            event_callback_new(event_list_arc_1.clone(), delay_notify_2.clone()),
             |ret| {
                 info!("{:?}", ret);
             },
@@ -452,3 +773,145 @@ fn main() {
 
     info!("end");
 }
+
+/// The callback for every event from a live session or a replaying stream.
+/// It matches the stack walks, filters the events and pushes them to the list.
+fn event_callback_new(
+    event_list_arc: Arc<event_list::EventList<EventRecordModel>>,
+    delay_notify: Arc<delay_notify::DelayNotify<delay_notify::AppNotifySink>>,
+) -> impl FnMut(
+    event_trace::EventRecordDecoded,
+    /*stack_walk*/ Option<event_trace::StackWalk>,
+    /*running_modules_map*/ &event_trace::process_modules::RunningModules,
+) + Send
+       + 'static {
+    let mut stack_walk_map =
+        event_trace::StackWalkMap::<Option<Weak<event_list::Node<EventRecordModel>>>>::new(
+            32, 10, 15,
+        );
+    move |event_record, stack_walk, running_modules_map| {
+        let process_id = event_record.process_id;
+        let thread_id = event_record.thread_id;
+        let timestamp = event_record.timestamp.0;
+
+        if let Some(mut sw) = stack_walk {
+            if let Some((some_row, is_from_second_sw_map)) =
+                stack_walk_map.remove(&(sw.stack_thread, sw.event_timestamp), timestamp)
+            {
+                if let Some(weak) = some_row.0 {
+                    if let Some(arc_node) = weak.upgrade() {
+                        // the modules at the time of the event, the stack walk may come after an unload
+                        running_modules_map.convert_to_module_offset(
+                            sw.stack_process,
+                            utils::TimeStamp(sw.event_timestamp),
+                            sw.stacks.as_mut_slice(),
+                        );
+                        let erm = arc_node
+                            .value
+                            .as_any()
+                            .downcast_ref::<event_record_model::EventRecordModel>()
+                            .unwrap();
+                        if is_from_second_sw_map {
+                            erm.set_stack_walk_2(sw);
+                        } else {
+                            erm.set_stack_walk(sw);
+                        }
+                    }
+                }
+            } else {
+                error!(
+                    "Can't find event: {}:{}:{} for the stack walk: {}:{}:{}",
+                    sw.stack_process,
+                    sw.stack_thread as i32,
+                    utils::TimeStamp(sw.event_timestamp).to_string_detail(),
+                    process_id as i32,
+                    thread_id as i32,
+                    utils::TimeStamp(timestamp).to_string_detail(),
+                );
+            }
+
+            return;
+        }
+
+        // get_process_path_by_id need to be before handle_event_for_module. because handle_event_for_module may be remove the process by the "process end" event.
+        let process_path =
+            running_modules_map.get_process_path_by_id(process_id, event_record.timestamp);
+
+        let debug_msg = format!(
+            "{}-{} in stack_walk_map",
+            event_record.event_name, event_record.opcode_name
+        );
+        // the stack trace in the extended data belongs to the event itself
+        let extended_stack_walk =
+            event_record
+                .extended
+                .stack_walk(process_id, thread_id, event_record.timestamp.0);
+        let er = event_record_model::EventRecordModel::new(event_record, process_path);
+        if let Some(mut sw) = extended_stack_walk {
+            running_modules_map.convert_to_module_offset(
+                sw.stack_process,
+                er.array.timestamp,
+                sw.stacks.as_mut_slice(),
+            );
+            er.set_stack_walk(sw);
+        }
+        let is_matched = match filter::filter_for_one(
+            |path, value| er.find_by_path_value(path, value),
+            |value| er.find_by_value(value),
+        ) {
+            Err(e) => {
+                error!("Failed to filter: {e}");
+                return;
+            }
+            Ok(is_matched) => is_matched,
+        };
+
+        let row_arc = Arc::new(event_list::Node::new(er));
+        let mut is_push_to_list = false;
+        let mut notify: Option<delay_notify::Notify> = None;
+        if is_matched {
+            match filter::filter_for_pair(&row_arc) {
+                Err(e) => {
+                    error!("Failed to filter: {e}");
+                    is_push_to_list = true;
+                }
+                Ok(ok) => {
+                    if let Some(node) = ok {
+                        notify = match event_list_arc
+                            .remove(node, event_list_model::REMOVE_INDEX_MAX_DISTANCE)
+                        {
+                            event_list::Removed::Index(index) => {
+                                Some(delay_notify::Notify::Remove(index, 1))
+                            }
+                            event_list::Removed::NoIndex => Some(delay_notify::Notify::Reset),
+                            // notified when unfreezing
+                            event_list::Removed::Deferred => None,
+                        };
+                    } else {
+                        is_push_to_list = true;
+                    }
+                }
+            }
+        }
+
+        if is_push_to_list {
+            stack_walk_map.insert(
+                (thread_id, timestamp),
+                Some(Arc::downgrade(&row_arc)),
+                debug_msg,
+            );
+            // None when the view is frozen. notified when unfreezing
+            if let Some(index) = event_list_arc.push(row_arc) {
+                notify = Some(delay_notify::Notify::Push(index, 1));
+            }
+        } else {
+            stack_walk_map.insert((thread_id, timestamp), None, debug_msg);
+        }
+
+        if let Some(notify) = notify {
+            delay_notify.notify(notify);
+        }
+
+        return;
+    }
+}
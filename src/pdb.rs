@@ -1,292 +1,442 @@
-use crate::utils::TimeDateStamp;
+use crate::{
+    event_trace::process_modules::{get_image_info_from_file, CodeView},
+    image_exports,
+    pdb_info::{self, parse_pdb, PdbInfo},
+    source_snippet,
+    symbol_index::{SourceStamp, SymbolIndex},
+    symbol_path::{self, PdbSignature, SymbolPath, SymbolPathEntry},
+    symbol_server,
+};
 use anyhow::{anyhow, Context, Result};
-use chumsky::container::Container;
 use linked_hash_map::LinkedHashMap;
 use once_cell::sync::Lazy;
 use parking_lot::FairMutex;
-use pdb::{FallibleIterator, StringRef, SymbolData, PDB};
 use std::{
-    collections::{BTreeMap, HashMap},
-    fs::File,
-    ops::Bound,
+    collections::HashMap,
+    fmt::Write,
+    fs::create_dir_all,
     path::{Path, PathBuf},
     sync::Arc,
 };
-use tracing::error;
-
-#[derive(Debug)]
-pub struct ProcedureInfo {
-    pub name: String,
-    pub rva: u32, // base by iamge start
-    pub len: u32,
-    #[allow(unused)]
-    pub global: bool,
-    pub line_map: BTreeMap<u32, LineInfo>,
-}
+use tracing::warn;
 
-#[derive(Debug)]
-pub struct LineInfo {
-    pub rva: u32, // base by iamge start
-    pub length: Option<u32>,
-    #[allow(unused)]
-    pub module_index: u32,
-    pub file_name: StringRef,
-    pub line_start: u32,
-    #[allow(unused)]
-    pub line_end: u32,
-    #[allow(unused)]
-    pub column_start: Option<u32>,
-    #[allow(unused)]
-    pub column_end: Option<u32>,
+/// The symbols of a module. parsed from the pdb, or mapped from the symbol index that is built by a
+/// previous run
+pub enum ModuleSymbols {
+    Pdb(PdbInfo),
+    Index(SymbolIndex),
 }
 
-pub struct PdbInfo {
-    file_name_map: HashMap<StringRef, String>,
-    #[allow(unused)]
-    modules_files_vec: Vec<ModuleInfo>,
-    functions_map: BTreeMap<u32, ProcedureInfo>,
+impl ModuleSymbols {
+    pub fn get_location_info_by_offset(
+        &self,
+        offset: u32,
+    ) -> Vec<(
+        /*function_location*/ String,
+        /*line_location*/ String,
+    )> {
+        match self {
+            Self::Pdb(pdb_info) => pdb_info.get_location_info_by_offset(offset),
+            Self::Index(index) => index.get_location_info_by_offset(offset),
+        }
+    }
+
+    /// The estimated bytes in memory. the pages of the mapped index are managed by the system
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Self::Pdb(pdb_info) => pdb_info.memory_size(),
+            Self::Index(_) => size_of::<SymbolIndex>(),
+        }
+    }
 }
 
-pub struct FileInfo {
-    #[allow(unused)]
-    name: StringRef,
-    // checksum
+static SYMBOL_PATH: Lazy<FairMutex<SymbolPath>> =
+    Lazy::new(|| FairMutex::new(SymbolPath::default()));
+
+/// The syntax of `_NT_SYMBOL_PATH`. i.e. `dir1;dir2;cache*dir;srv*cache*url`.
+/// The directories must exist, the caches are created
+pub fn symbol_path_set(path: &str) -> Result<()> {
+    let symbol_path = SymbolPath::parse(path)?;
+    for entry in symbol_path.entries.iter() {
+        symbol_path_entry_prepare(entry)?;
+    }
+    symbol_path_replace(symbol_path);
+    Ok(())
 }
 
-// corresponding a object file
-#[allow(unused)]
-pub struct ModuleInfo {
-    module_name: String,
-    object_file_name: String,
-    files_map: HashMap<StringRef, FileInfo>, // todo: checksum
+/// Set the symbol path by the sources in order. i.e. the pdb directory of exe and the `_NT_SYMBOL_PATH`.
+/// The invalid entries are skipped with a warning, so one source can't disable the others
+pub fn symbol_path_init(sources: &[String]) {
+    let mut symbol_path = SymbolPath::default();
+    for source in sources.iter() {
+        for entry in symbol_path::split(source) {
+            match symbol_path
+                .parse_entry(entry)
+                .and_then(|parsed| symbol_path_entry_prepare(&parsed).map(|_| parsed))
+            {
+                Ok(parsed) => symbol_path.entries.push(parsed),
+                Err(e) => warn!("Skip the symbol path entry: {entry} {e}"),
+            }
+        }
+    }
+    symbol_path_replace(symbol_path);
 }
 
-impl PdbInfo {
-    pub fn get_location_info_by_offset(
-        &self,
-        offset: u32,
-    ) -> (
-        /*function_location*/ String,
-        /*line_location*/ String,
-    ) {
-        let cursor = self.functions_map.upper_bound(Bound::Included(&offset));
-        if let Some((_, procedure_info)) = cursor.peek_prev() {
-            if offset < procedure_info.rva + procedure_info.len {
-                let cursor_line = procedure_info
-                    .line_map
-                    .upper_bound(Bound::Included(&offset));
-                if let Some((_, line_info)) = cursor_line.peek_prev() {
-                    if let Some(len) = line_info.length {
-                        if offset >= line_info.rva + len {
-                            return (
-                                format!(
-                                    "{}+{:#x}",
-                                    procedure_info.name,
-                                    offset - procedure_info.rva
-                                ),
-                                String::new(),
-                            );
-                        }
-                    }
-                    let file_name = self.file_name_map.get(&line_info.file_name).unwrap();
-                    return (
-                        format!("{}+{:#x}", procedure_info.name, offset - procedure_info.rva),
-                        format!("{file_name}: {}", line_info.line_start),
-                    );
-                } else {
-                    return (
-                        format!("{}+{:#x}", procedure_info.name, offset - procedure_info.rva),
-                        String::new(),
-                    );
-                }
+fn symbol_path_entry_prepare(entry: &SymbolPathEntry) -> Result<()> {
+    match entry {
+        SymbolPathEntry::Directory(dir) => {
+            if !dir.is_dir() {
+                return Err(anyhow!("The directory is not exist: {}", dir.display()));
             }
         }
-        (String::new(), String::new())
+        SymbolPathEntry::Cache(dir) | SymbolPathEntry::Server { cache: dir, .. } => {
+            create_dir_all(dir).map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?
+        }
     }
+    Ok(())
+}
+
+fn symbol_path_replace(symbol_path: SymbolPath) {
+    *SYMBOL_PATH.lock() = symbol_path;
+    PDBS_MISSING.lock().clear();
+    // the failed downloads may succeed by the new servers
+    symbol_server::download_requested_clear();
+}
+
+pub fn symbol_path_get() -> String {
+    SYMBOL_PATH.lock().to_string()
 }
 
-static PDB_PATH: Lazy<FairMutex<String>> = Lazy::new(|| FairMutex::new(String::new()));
+// None if the symbol index is not used
+static SYMBOL_INDEX_DIR: Lazy<FairMutex<Option<PathBuf>>> = Lazy::new(|| FairMutex::new(None));
 
-pub fn pdb_path_set(path: &str) {
-    let path = if path.ends_with("\\") {
-        &path[..path.len() - 1]
-    } else {
-        path
-    };
-    *PDB_PATH.lock() = path.to_string();
+/// The directory of the symbol index files. The directory must exist
+pub fn symbol_index_dir_set(dir: &Path) {
+    *SYMBOL_INDEX_DIR.lock() = Some(dir.to_path_buf());
 }
 
-pub fn pdb_path_get() -> String {
-    PDB_PATH.lock().clone()
+// i.e. `<dir>\ntdll.pdb-6A399AE04BC64DE9870B3657F8947E7E1.idx`, `<dir>\ntdll.dll-5E8C1B2A.idx` for the
+// module without code view
+fn symbol_index_path(
+    module_name: &Path,
+    module_time_date_stamp: u32,
+    pdb_signature: Option<&PdbSignature>,
+) -> Option<PathBuf> {
+    let dir = SYMBOL_INDEX_DIR.lock().clone()?;
+    let pdb_name = pdb_signature
+        .and_then(|pdb_signature| symbol_path::pdb_file_name(pdb_signature.pdb_path.as_str()));
+    let file_name = match (pdb_name, pdb_signature) {
+        (Some(pdb_name), Some(pdb_signature)) => format!("{pdb_name}-{}.idx", pdb_signature.key),
+        _ => format!(
+            "{}-{module_time_date_stamp:08X}.idx",
+            module_name.file_name()?.to_string_lossy()
+        ),
+    };
+    Some(dir.join(file_name))
 }
 
-static PDBS_LOADED: Lazy<FairMutex<LinkedHashMap<(PathBuf, u32), Arc<PdbInfo>>>> =
+// module path -> the resolved pdb path or the error. for showing in the pdb config
+static PDBS_RESOLVED: Lazy<FairMutex<LinkedHashMap<PathBuf, Result<PathBuf, String>>>> =
     Lazy::new(|| FairMutex::new(LinkedHashMap::new()));
 
+/// The location that resolved each module, or the reason of failure
+pub fn pdbs_resolved_text() -> String {
+    let lock = PDBS_RESOLVED.lock();
+    let mut s = String::with_capacity(128 * lock.len());
+    for (module_path, resolved) in lock.iter() {
+        let _ = match resolved {
+            Ok(pdb_path) => writeln!(s, "{} => {}", module_path.display(), pdb_path.display()),
+            Err(e) => writeln!(s, "{} => {e}", module_path.display()),
+        };
+    }
+    s
+}
+
+// the budget of the parsed pdbs in memory
+const PDBS_LOADED_BUDGET: usize = 512 * 1024 * 1024;
+
+static PDBS_LOADED: Lazy<FairMutex<PdbsLoaded>> =
+    Lazy::new(|| FairMutex::new(PdbsLoaded::new(PDBS_LOADED_BUDGET)));
+
+// the modules that are parsing the pdb. the other threads wait for it instead of parsing again
+static PDBS_LOADING: Lazy<FairMutex<HashMap<(PathBuf, u32), Arc<FairMutex<()>>>>> =
+    Lazy::new(|| FairMutex::new(HashMap::new()));
+
+// the modules that no pdb is found for. they are symbolized by the exports without searching again, until
+// a pdb is downloaded or the symbol path is changed. key: (module path, TimeDateStamp), value: the
+// downloaded count before searching
+static PDBS_MISSING: Lazy<FairMutex<HashMap<(PathBuf, u32), usize>>> =
+    Lazy::new(|| FairMutex::new(HashMap::new()));
+
+fn is_pdb_missing(key: &(PathBuf, u32)) -> bool {
+    PDBS_MISSING
+        .lock()
+        .get(key)
+        .is_some_and(|downloaded| *downloaded == symbol_server::downloaded_count())
+}
+
+/// The parsed pdbs in the order of recently used. The least recently used are dropped when the size is
+/// over the budget
+struct PdbsLoaded {
+    // key: (module path, TimeDateStamp)
+    map: LinkedHashMap<(PathBuf, u32), (Arc<ModuleSymbols>, /*size*/ usize)>,
+    size: usize,
+    budget: usize,
+}
+
+impl PdbsLoaded {
+    fn new(budget: usize) -> Self {
+        Self {
+            map: LinkedHashMap::new(),
+            size: 0,
+            budget,
+        }
+    }
+
+    fn get(&mut self, key: &(PathBuf, u32)) -> Option<Arc<ModuleSymbols>> {
+        self.map
+            .get_refresh(key)
+            .map(|(symbols, _)| symbols.clone())
+    }
+
+    // the inserted is kept even if it is over the budget alone
+    fn insert(&mut self, key: (PathBuf, u32), symbols: Arc<ModuleSymbols>) {
+        let size = symbols.memory_size();
+        if let Some((_, size_old)) = self.map.insert(key, (symbols, size)) {
+            self.size -= size_old;
+        }
+        self.size += size;
+        while self.size > self.budget && self.map.len() > 1 {
+            if let Some((_, (_, size_removed))) = self.map.pop_front() {
+                self.size -= size_removed;
+            }
+        }
+    }
+}
+
+/// The locations if the pdb of module has been parsed, or by the exports if the module has no pdb.
+/// i.e. for showing without waiting the symbolization. None if it needs the symbolization
+pub fn get_location_info_loaded(
+    module_name: &Path,
+    module_time_date_stamp: u32,
+    offset: u32,
+) -> Option<
+    Result<
+        Vec<(
+            /*function_location*/ String,
+            /*line_location*/ String,
+        )>,
+    >,
+> {
+    let key = (module_name.to_path_buf(), module_time_date_stamp);
+    let symbols = PDBS_LOADED.lock().get(&key);
+    if let Some(symbols) = symbols {
+        return Some(Ok(symbols.get_location_info_by_offset(offset)));
+    }
+    is_pdb_missing(&key)
+        .then(|| get_location_info_by_exports(module_name, module_time_date_stamp, offset))
+}
+
 pub fn get_location_info(
     module_name: &Path,
     module_time_date_stamp: u32,
+    code_view: Option<&CodeView>,
     offset: u32,
-) -> Result<(
-    /*function_location*/ String,
-    /*line_location*/ String,
-)> {
-    if let Some(pdb_info) = PDBS_LOADED
-        .lock()
-        .get(&(module_name.to_path_buf(), module_time_date_stamp))
-    {
-        return Ok(pdb_info.get_location_info_by_offset(offset));
+) -> Result<
+    Vec<(
+        /*function_location*/ String,
+        /*line_location*/ String,
+    )>,
+> {
+    let key = (module_name.to_path_buf(), module_time_date_stamp);
+    let symbols = PDBS_LOADED.lock().get(&key);
+    if let Some(symbols) = symbols {
+        return Ok(symbols.get_location_info_by_offset(offset));
+    }
+    if is_pdb_missing(&key) {
+        return get_location_info_by_exports(module_name, module_time_date_stamp, offset);
     }
 
-    let pdb_info =
-        get_pdb_info_from_pdb_file(module_name, module_time_date_stamp).with_context(|| {
-            format!(
-                "Faile to get_pdb_info_from_pdb_file for {}-{module_time_date_stamp}",
-                module_name.display()
-            )
-        })?;
-    let _ = PDBS_LOADED.lock().insert(
-        (module_name.to_path_buf(), module_time_date_stamp),
-        pdb_info.clone(),
-    );
-    Ok(pdb_info.get_location_info_by_offset(offset))
+    let downloaded = symbol_server::downloaded_count();
+    let loading = PDBS_LOADING
+        .lock()
+        .entry(key.clone())
+        .or_insert_with(|| Arc::new(FairMutex::new(())))
+        .clone();
+    let r = {
+        let _loading_guard = loading.lock();
+        // it may be parsed, or found no pdb by another thread when waiting
+        let symbols = PDBS_LOADED.lock().get(&key);
+        match symbols {
+            Some(symbols) => Some(Ok(symbols)),
+            None if is_pdb_missing(&key) => None,
+            None => {
+                let r = get_pdb_info_from_pdb_file(module_name, module_time_date_stamp, code_view);
+                // recorded before the loading is removed, so the next threads do not search again
+                match &r {
+                    Ok(symbols) => PDBS_LOADED.lock().insert(key.clone(), symbols.clone()),
+                    Err(_) => {
+                        PDBS_MISSING.lock().insert(key.clone(), downloaded);
+                    }
+                }
+                Some(r)
+            }
+        }
+    };
+    PDBS_LOADING.lock().remove(&key);
+
+    let symbols = match r {
+        Some(Ok(symbols)) => symbols,
+        None => return get_location_info_by_exports(module_name, module_time_date_stamp, offset),
+        Some(Err(e)) => {
+            // the nearest exported function of the module file when no pdb is found
+            let r = get_location_info_by_exports(module_name, module_time_date_stamp, offset);
+            return r.or_else(|_| {
+                Err(e).with_context(|| {
+                    format!(
+                        "Faile to get_pdb_info_from_pdb_file for {}-{module_time_date_stamp}",
+                        module_name.display()
+                    )
+                })
+            });
+        }
+    };
+    Ok(symbols.get_location_info_by_offset(offset))
 }
 
-// module_name: file name i.e. system_monitor.exe
+fn get_location_info_by_exports(
+    module_name: &Path,
+    module_time_date_stamp: u32,
+    offset: u32,
+) -> Result<
+    Vec<(
+        /*function_location*/ String,
+        /*line_location*/ String,
+    )>,
+> {
+    let function_location =
+        image_exports::get_location_info(module_name, module_time_date_stamp, offset)?;
+    Ok(vec![(
+        format!("{function_location} [export]"),
+        String::new(),
+    )])
+}
+
+// module_name: the path of module i.e. C:\Windows\System32\ntdll.dll
+// code_view: None if it is unknown, then it is read from the module file
 fn get_pdb_info_from_pdb_file(
     module_name: &Path,
     module_time_date_stamp: u32,
-) -> Result<Arc<PdbInfo>> {
-    let module_file_name = if let Some(module_file_name) = module_name.file_name() {
-        module_file_name
-    } else {
+    code_view: Option<&CodeView>,
+) -> Result<Arc<ModuleSymbols>> {
+    if module_name.file_name().is_none() {
         return Err(anyhow!("No file name for {}", module_name.display()));
+    }
+    let code_view = match code_view {
+        Some(code_view) => Some(code_view.clone()),
+        None => get_image_info_from_file(module_name)
+            .ok()
+            .and_then(|(_, _, code_view)| code_view),
     };
-    let pdb_prefix = pdb_path_get();
-    let pdb_path = Path::new(pdb_prefix.as_str())
-        .join(module_file_name)
-        .with_extension("pdb");
-
-    let file = File::open(pdb_path.as_path())
-        .with_context(|| format!("Failed to open {}", pdb_path.display()))?;
-    let mut pdb = PDB::open(file)?;
-    let pdb_info = pdb.pdb_information()?;
-
-    const TIME_DATE_STAMP_DIFF: u32 = 5;
-    if pdb_info.signature.abs_diff(module_time_date_stamp) > TIME_DATE_STAMP_DIFF {
-        return Err(anyhow!(
-            "Unmatched TimeDateStamp (> {TIME_DATE_STAMP_DIFF}), module is {} pdb is {}",
-            TimeDateStamp(module_time_date_stamp).to_string_detail(),
-            TimeDateStamp(pdb_info.signature).to_string_detail()
-        ));
+    let pdb_signature = code_view.as_ref().map(CodeView::pdb_signature);
+    // the index is used without reading the pdb if the pdb is not changed since it is built
+    let index_path = symbol_index_path(module_name, module_time_date_stamp, pdb_signature.as_ref());
+    if let Some(index) = index_path
+        .as_ref()
+        .and_then(|index_path| SymbolIndex::open(index_path).ok())
+    {
+        PDBS_RESOLVED
+            .lock()
+            .insert(module_name.to_path_buf(), Ok(index.source().path.clone()));
+        source_snippet::source_links_add(index.source_links());
+        return Ok(Arc::new(ModuleSymbols::Index(index)));
     }
+    let symbol_path = SYMBOL_PATH.lock().clone();
+    let pdb_paths = symbol_path.pdb_candidates(module_name, pdb_signature.as_ref());
 
-    let address_map = pdb.address_map()?;
-    let string_table = pdb.string_table()?;
-    let dbi = pdb.debug_information()?;
-    let mut modules = dbi.modules()?;
-
-    let mut file_name_map = HashMap::with_capacity(128);
-    let mut modules_files_vec = Vec::with_capacity(16);
-    let mut functions_map = BTreeMap::with_capacity(32);
-    while let Some(module) = modules.next()? {
-        let module_info = match pdb.module_info(&module)? {
-            Some(info) => info,
-            None => {
-                continue;
-            }
-        };
-        let program = module_info.line_program()?;
-        let mut symbols = module_info.symbols()?;
-        let mut files_map = HashMap::new();
-        let mut files = program.files();
-        while let Some(file_info) = files.next()? {
-            let file_name = match file_info.name.to_raw_string(&string_table) {
-                Err(e) => {
-                    let s = format!("{e}");
-                    error!(s);
-                    s
-                }
-                Ok(rs) => format!("{rs}"),
-            };
-            let _ = file_name_map.try_insert(file_info.name, file_name);
-            files_map.insert(
-                file_info.name,
-                FileInfo {
-                    name: file_info.name,
-                },
-            );
+    // the TimeDateStamp is a fallback for the image without code view
+    let r = pdb_info::open_matched_pdb(
+        &pdb_paths,
+        Some(module_time_date_stamp),
+        pdb_signature.as_ref(),
+    );
+    let (pdb_path, mut pdb) = match r {
+        Ok((pdb_path, pdb)) => {
+            PDBS_RESOLVED
+                .lock()
+                .insert(module_name.to_path_buf(), Ok(pdb_path.clone()));
+            (pdb_path, pdb)
         }
-        let module_index = modules_files_vec.len() as u32;
-        modules_files_vec.push(ModuleInfo {
-            module_name: format!("{}", module.module_name()),
-            object_file_name: format!("{}", module.object_file_name()),
-            files_map,
-        });
-
-        while let Some(symbol) = symbols.next()? {
-            match symbol.parse() {
-                Ok(data) => {
-                    if let SymbolData::Procedure(proc) = data {
-                        if let Some(proc_rva) = proc.offset.to_rva(&address_map) {
-                            let mut line_map = BTreeMap::new();
-                            let mut lines = program.lines_for_symbol(proc.offset);
-                            while let Some(line_info) = lines.next()? {
-                                if let Some(rva) = line_info.offset.to_rva(&address_map) {
-                                    let file_info =
-                                        match program.get_file_info(line_info.file_index) {
-                                            Err(e) => {
-                                                error!("{e}");
-                                                continue;
-                                            }
-                                            Ok(file_info) => file_info,
-                                        };
-                                    line_map.insert(
-                                        rva.0,
-                                        LineInfo {
-                                            rva: rva.0,
-                                            length: line_info.length,
-                                            module_index,
-                                            file_name: file_info.name,
-                                            line_start: line_info.line_start,
-                                            line_end: line_info.line_end,
-                                            column_start: line_info.column_start,
-                                            column_end: line_info.column_end,
-                                        },
-                                    );
-                                }
-                            }
-                            functions_map.insert(
-                                proc_rva.0,
-                                ProcedureInfo {
-                                    name: format!("{}", proc.name),
-                                    rva: proc_rva.0,
-                                    len: proc.len,
-                                    global: proc.global,
-                                    line_map: line_map,
-                                },
-                            );
-                        }
+        Err(e) => {
+            let mut e = e.to_string();
+            // it is found in the cache by the next time after downloaded
+            if let Some(pdb_signature) = pdb_signature.as_ref() {
+                for request in symbol_server::download_requests(&symbol_path, pdb_signature) {
+                    let url = request.url.clone();
+                    if symbol_server::download_in_background(request) {
+                        e.push_str(format!(". Downloading from {url}").as_str());
                     }
                 }
-                Err(_e) => {
-                    //warn!("{e} in file: {}", pdb_path.display());
-                }
             }
+            PDBS_RESOLVED
+                .lock()
+                .insert(module_name.to_path_buf(), Err(e.clone()));
+            return Err(anyhow!(e));
         }
-    }
+    };
 
-    Ok(Arc::new(PdbInfo {
-        file_name_map,
-        modules_files_vec,
-        functions_map,
-    }))
+    // the stamp is taken before parsing, so the index is invalid if the pdb is changed while parsing
+    let source = SourceStamp::from_file(pdb_path.as_path());
+    let pdb_info = parse_pdb(&mut pdb)?;
+    source_snippet::source_links_add(pdb_info.source_links.iter().cloned());
+    if let Some(index_path) = index_path {
+        let r = source.and_then(|source| SymbolIndex::write(&index_path, &pdb_info, &source));
+        if let Err(e) = r {
+            warn!("Failed to build the symbol index: {e:#}");
+        }
+    }
+    Ok(Arc::new(ModuleSymbols::Pdb(pdb_info)))
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{ModuleSymbols, PdbInfo, PdbsLoaded};
     use crate::event_trace::process_modules::get_image_info_from_file;
-    use std::path::Path;
+    use std::{
+        collections::{BTreeMap, HashMap},
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    #[test]
+    fn pdbs_loaded_lru() {
+        let pdb_info = |name: &str| {
+            Arc::new(ModuleSymbols::Pdb(PdbInfo {
+                file_name_map: HashMap::new(),
+                modules_files_vec: vec![],
+                functions_map: BTreeMap::new(),
+                publics_map: BTreeMap::from([(0x1000, name.repeat(100))]),
+                source_links: vec![],
+            }))
+        };
+        let key = |name: &str| (PathBuf::from(name), 1);
+        let size = pdb_info("a").memory_size();
+        let mut loaded = PdbsLoaded::new(size * 2);
+        loaded.insert(key("a.dll"), pdb_info("a"));
+        loaded.insert(key("b.dll"), pdb_info("b"));
+        // a is used recently, so b is dropped
+        assert!(loaded.get(&key("a.dll")).is_some());
+        loaded.insert(key("c.dll"), pdb_info("c"));
+        assert!(loaded.get(&key("b.dll")).is_none());
+        assert!(loaded.get(&key("a.dll")).is_some());
+        assert!(loaded.get(&key("c.dll")).is_some());
+        assert_eq!(loaded.size, size * 2);
+
+        // the one over the budget alone is kept
+        let mut loaded = PdbsLoaded::new(1);
+        loaded.insert(key("a.dll"), pdb_info("a"));
+        assert!(loaded.get(&key("a.dll")).is_some());
+    }
 
     #[test]
     fn get_pdb_info_from_pdb_file() {
@@ -296,10 +446,11 @@ mod tests {
             format!("{out_dir}\\target\\debug\\{pkg_name}.exe").as_str(),
         ))
         .unwrap();
-        super::pdb_path_set(format!("{out_dir}\\target\\debug").as_str());
+        super::symbol_path_set(format!("{out_dir}\\target\\debug").as_str()).unwrap();
         let r = super::get_pdb_info_from_pdb_file(
             &Path::new(format!("{pkg_name}.exe").as_str()),
             module_info.1,
+            module_info.2.as_ref(),
         )
         .unwrap();
         println!("{:?}", r.get_location_info_by_offset(0x2b6168));
@@ -313,10 +464,11 @@ mod tests {
             format!("{out_dir}\\target\\debug\\{pkg_name}.exe").as_str(),
         ))
         .unwrap();
-        super::pdb_path_set(format!("{out_dir}\\target\\debug").as_str());
+        super::symbol_path_set(format!("{out_dir}\\target\\debug").as_str()).unwrap();
         let location_info = super::get_location_info(
             &Path::new(format!("{pkg_name}.exe").as_str()),
             module_info.1,
+            module_info.2.as_ref(),
             0x2b6168,
         )
         .unwrap();
@@ -0,0 +1,582 @@
+//! The symbols parsed from a pdb. The procedures with their lines and inline sites, and the public symbols
+use crate::{source_snippet, symbol_path::PdbSignature};
+use anyhow::{anyhow, Context, Result};
+use pdb::{
+    AddressMap, FallibleIterator, IdData, IdFinder, IdIndex, LineProgram, StringRef, SymbolData,
+    PDB,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    ops::Bound,
+    path::PathBuf,
+};
+use tracing::{error, warn};
+
+#[derive(Debug)]
+pub struct ProcedureInfo {
+    pub name: String,
+    pub rva: u32, // base by iamge start
+    pub len: u32,
+    #[allow(unused)]
+    pub global: bool,
+    pub line_map: BTreeMap<u32, LineInfo>,
+    pub inline_sites: Vec<InlineSiteInfo>,
+}
+
+// the code of a function inlined into the procedure
+#[derive(Debug)]
+pub struct InlineSiteInfo {
+    pub name: String,
+    // 1 if it is inlined into the procedure, 2 if it is inlined into a depth 1 site, ...
+    pub depth: u32,
+    // the code ranges of site and the lines in the inlined function
+    pub line_map: BTreeMap<u32, LineInfo>,
+}
+
+#[derive(Debug)]
+pub struct LineInfo {
+    pub rva: u32, // base by iamge start
+    pub length: Option<u32>,
+    #[allow(unused)]
+    pub module_index: u32,
+    pub file_name: StringRef,
+    pub line_start: u32,
+    #[allow(unused)]
+    pub line_end: u32,
+    #[allow(unused)]
+    pub column_start: Option<u32>,
+    #[allow(unused)]
+    pub column_end: Option<u32>,
+}
+
+pub struct PdbInfo {
+    pub file_name_map: HashMap<StringRef, String>,
+    #[allow(unused)]
+    pub modules_files_vec: Vec<ModuleInfo>,
+    pub functions_map: BTreeMap<u32, ProcedureInfo>,
+    // rva -> name of the public symbols. for the pdb that is stripped of the procedures
+    pub publics_map: BTreeMap<u32, String>,
+    // (build path, path in repository) by the Source Link or srcsrv
+    pub source_links: Vec<(String, String)>,
+}
+
+pub struct FileInfo {
+    #[allow(unused)]
+    name: StringRef,
+    // checksum
+}
+
+// corresponding a object file
+#[allow(unused)]
+pub struct ModuleInfo {
+    module_name: String,
+    object_file_name: String,
+    files_map: HashMap<StringRef, FileInfo>, // todo: checksum
+}
+
+impl PdbInfo {
+    /// The logical frames of the offset. The inlined functions are before the function that contains them,
+    /// the line of each outer frame is where the inner function is called
+    pub fn get_location_info_by_offset(
+        &self,
+        offset: u32,
+    ) -> Vec<(
+        /*function_location*/ String,
+        /*line_location*/ String,
+    )> {
+        let cursor = self.functions_map.upper_bound(Bound::Included(&offset));
+        if let Some((_, procedure_info)) = cursor.peek_prev() {
+            if offset < procedure_info.rva + procedure_info.len {
+                let mut sites: Vec<&InlineSiteInfo> = procedure_info
+                    .inline_sites
+                    .iter()
+                    // the range of site is unknown without the length
+                    .filter(|site| {
+                        line_info_at(&site.line_map, offset)
+                            .is_some_and(|line_info| line_info.length.is_some())
+                    })
+                    .collect();
+                sites.sort_by_key(|site| Reverse(site.depth));
+                let mut frames = Vec::with_capacity(sites.len() + 1);
+                for site in sites {
+                    frames.push((
+                        format!("{} [inline]", site.name),
+                        self.line_location(&site.line_map, offset),
+                    ));
+                }
+                frames.push((
+                    format!("{}+{:#x}", procedure_info.name, offset - procedure_info.rva),
+                    self.line_location(&procedure_info.line_map, offset),
+                ));
+                return frames;
+            }
+        }
+
+        // the public symbol has no length, it is not used after a procedure at or after it
+        let cursor = self.publics_map.upper_bound(Bound::Included(&offset));
+        if let Some((rva, name)) = cursor.peek_prev() {
+            let procedure_between = self
+                .functions_map
+                .range((Bound::Included(rva), Bound::Included(&offset)))
+                .next()
+                .is_some();
+            if !procedure_between {
+                return vec![(format!("{name}+{:#x}", offset - rva), String::new())];
+            }
+        }
+        vec![(String::new(), String::new())]
+    }
+
+    /// The estimated bytes in memory
+    pub fn memory_size(&self) -> usize {
+        fn lines_size(line_map: &BTreeMap<u32, LineInfo>) -> usize {
+            line_map.len() * (size_of::<u32>() + size_of::<LineInfo>())
+        }
+        let files: usize = self
+            .file_name_map
+            .values()
+            .map(|name| size_of::<StringRef>() + size_of::<String>() + name.len())
+            .sum();
+        let functions: usize = self
+            .functions_map
+            .values()
+            .map(|procedure_info| {
+                let sites: usize = procedure_info
+                    .inline_sites
+                    .iter()
+                    .map(|site| {
+                        size_of::<InlineSiteInfo>() + site.name.len() + lines_size(&site.line_map)
+                    })
+                    .sum();
+                size_of::<u32>()
+                    + size_of::<ProcedureInfo>()
+                    + procedure_info.name.len()
+                    + lines_size(&procedure_info.line_map)
+                    + sites
+            })
+            .sum();
+        let publics: usize = self
+            .publics_map
+            .values()
+            .map(|name| size_of::<u32>() + size_of::<String>() + name.len())
+            .sum();
+        let source_links: usize = self
+            .source_links
+            .iter()
+            .map(|(file_name, repo_path)| {
+                size_of::<(String, String)>() + file_name.len() + repo_path.len()
+            })
+            .sum();
+        size_of::<Self>() + files + functions + publics + source_links
+    }
+
+    // i.e. `C:\src\main.rs: 12`. empty if the offset is not in the lines
+    fn line_location(&self, line_map: &BTreeMap<u32, LineInfo>, offset: u32) -> String {
+        match line_info_at(line_map, offset) {
+            // the file name may be missing in a broken pdb
+            Some(line_info) => match self.file_name_map.get(&line_info.file_name) {
+                Some(file_name) => format!("{file_name}: {}", line_info.line_start),
+                None => format!("<unknown file>: {}", line_info.line_start),
+            },
+            None => String::new(),
+        }
+    }
+}
+
+fn line_info_at(line_map: &BTreeMap<u32, LineInfo>, offset: u32) -> Option<&LineInfo> {
+    let cursor = line_map.upper_bound(Bound::Included(&offset));
+    let (_, line_info) = cursor.peek_prev()?;
+    match line_info.length {
+        Some(len) if offset >= line_info.rva + len => None,
+        _ => Some(line_info),
+    }
+}
+
+/// The `<GUID><age>` of the pdb, i.e. the key of `PdbSignature` by the code view of the image
+pub fn pdb_key(pdb: &mut PDB<File>) -> Result<String> {
+    let pdb_information = pdb.pdb_information()?;
+    // the age in image is the age of dbi stream, the age of pdb stream may be increased by the tools
+    let age = pdb
+        .debug_information()?
+        .age()
+        .unwrap_or(pdb_information.age);
+    Ok(format!("{:032X}{age:X}", pdb_information.guid.as_u128()))
+}
+
+/// The first of the candidates that is the pdb of the module, with its path. Err is the reason of each
+/// candidate
+pub fn open_matched_pdb(
+    pdb_paths: &[PathBuf],
+    module_time_date_stamp: Option<u32>,
+    pdb_signature: Option<&PdbSignature>,
+) -> Result<(PathBuf, PDB<'static, File>)> {
+    let mut errors = vec![];
+    for pdb_path in pdb_paths.iter().filter(|pdb_path| pdb_path.is_file()) {
+        let r = File::open(pdb_path)
+            .with_context(|| format!("Failed to open {}", pdb_path.display()))
+            .and_then(|file| Ok(PDB::open(file)?))
+            .and_then(|mut pdb| {
+                check_pdb_matched(&mut pdb, module_time_date_stamp, pdb_signature)?;
+                Ok(pdb)
+            });
+        match r {
+            Ok(pdb) => return Ok((pdb_path.clone(), pdb)),
+            Err(e) => errors.push(format!("{}: {e:#}", pdb_path.display())),
+        }
+    }
+    if errors.is_empty() {
+        Err(anyhow!("No pdb in the {} locations", pdb_paths.len()))
+    } else {
+        Err(anyhow!("No matched pdb. {}", errors.join("; ")))
+    }
+}
+
+/// Match by the `<GUID><age>` of the code view. The TimeDateStamp is a fallback for the image without code
+/// view, any pdb is matched if neither is known
+pub fn check_pdb_matched(
+    pdb: &mut PDB<File>,
+    module_time_date_stamp: Option<u32>,
+    pdb_signature: Option<&PdbSignature>,
+) -> Result<()> {
+    if let Some(pdb_signature) = pdb_signature {
+        let key = pdb_key(pdb)?;
+        if !key.eq_ignore_ascii_case(pdb_signature.key.as_str()) {
+            return Err(anyhow!(
+                "Unmatched guid and age, module is {} pdb is {key}",
+                pdb_signature.key
+            ));
+        }
+        return Ok(());
+    }
+
+    const TIME_DATE_STAMP_DIFF: u32 = 5;
+    let Some(module_time_date_stamp) = module_time_date_stamp else {
+        return Ok(());
+    };
+    let signature = pdb.pdb_information()?.signature;
+    if signature.abs_diff(module_time_date_stamp) > TIME_DATE_STAMP_DIFF {
+        return Err(anyhow!(
+            "Unmatched TimeDateStamp (> {TIME_DATE_STAMP_DIFF}), module is {module_time_date_stamp:08X} pdb is {signature:08X}"
+        ));
+    }
+    Ok(())
+}
+
+pub fn parse_pdb(pdb: &mut PDB<File>) -> Result<PdbInfo> {
+    let address_map = pdb.address_map()?;
+    let string_table = pdb.string_table()?;
+    // the names of inlined functions
+    let id_information = pdb.id_information()?;
+    let mut id_finder = id_information.finder();
+    let mut ids = id_information.iter();
+    while ids.next()?.is_some() {
+        id_finder.update(&ids);
+    }
+    let dbi = pdb.debug_information()?;
+    let mut modules = dbi.modules()?;
+
+    let mut file_name_map = HashMap::with_capacity(128);
+    let mut modules_files_vec = Vec::with_capacity(16);
+    let mut functions_map = BTreeMap::new();
+    while let Some(module) = modules.next()? {
+        let module_info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => {
+                continue;
+            }
+        };
+        let program = module_info.line_program()?;
+        let mut symbols = module_info.symbols()?;
+        let inlinees: HashMap<u32, _> = module_info
+            .inlinees()?
+            .map(|inlinee| Ok((inlinee.index().0, inlinee)))
+            .collect()?;
+        let mut files_map = HashMap::new();
+        let mut files = program.files();
+        while let Some(file_info) = files.next()? {
+            let file_name = match file_info.name.to_raw_string(&string_table) {
+                Err(e) => {
+                    let s = format!("{e}");
+                    error!(s);
+                    s
+                }
+                Ok(rs) => format!("{rs}"),
+            };
+            let _ = file_name_map.try_insert(file_info.name, file_name);
+            files_map.insert(
+                file_info.name,
+                FileInfo {
+                    name: file_info.name,
+                },
+            );
+        }
+        let module_index = modules_files_vec.len() as u32;
+        modules_files_vec.push(ModuleInfo {
+            module_name: format!("{}", module.module_name()),
+            object_file_name: format!("{}", module.object_file_name()),
+            files_map,
+        });
+
+        // (rva, offset, end symbol) of the procedure that the inline sites belong to
+        let mut procedure_current = None;
+        // symbol index -> depth of the inline sites
+        let mut site_depths = HashMap::new();
+        while let Some(symbol) = symbols.next()? {
+            match symbol.parse() {
+                Ok(SymbolData::Procedure(proc)) => {
+                    if let Some(proc_rva) = proc.offset.to_rva(&address_map) {
+                        let mut line_map = BTreeMap::new();
+                        let mut lines = program.lines_for_symbol(proc.offset);
+                        while let Some(line_info) = lines.next()? {
+                            if let Some(line_info) =
+                                to_line_info(&program, &address_map, &line_info, module_index)
+                            {
+                                line_map.insert(line_info.rva, line_info);
+                            }
+                        }
+                        functions_map.insert(
+                            proc_rva.0,
+                            ProcedureInfo {
+                                name: format!("{}", proc.name),
+                                rva: proc_rva.0,
+                                len: proc.len,
+                                global: proc.global,
+                                line_map,
+                                inline_sites: vec![],
+                            },
+                        );
+                        procedure_current = Some((proc_rva.0, proc.offset, proc.end));
+                        site_depths.clear();
+                    }
+                }
+                Ok(SymbolData::InlineSite(site)) => {
+                    let Some((proc_rva, proc_offset, proc_end)) = procedure_current else {
+                        continue;
+                    };
+                    if symbol.index().0 >= proc_end.0 {
+                        continue;
+                    }
+                    // the parent is the procedure for depth 1
+                    let depth = site
+                        .parent
+                        .and_then(|parent| site_depths.get(&parent.0))
+                        .copied()
+                        .unwrap_or(0)
+                        + 1;
+                    site_depths.insert(symbol.index().0, depth);
+                    let Some(inlinee) = inlinees.get(&site.inlinee.0) else {
+                        continue;
+                    };
+                    let mut line_map = BTreeMap::new();
+                    let mut lines = inlinee.lines(proc_offset, &site);
+                    while let Some(line_info) = lines.next()? {
+                        if let Some(line_info) =
+                            to_line_info(&program, &address_map, &line_info, module_index)
+                        {
+                            line_map.insert(line_info.rva, line_info);
+                        }
+                    }
+                    if let Some(procedure_info) = functions_map.get_mut(&proc_rva) {
+                        procedure_info.inline_sites.push(InlineSiteInfo {
+                            name: inlinee_name(&id_finder, site.inlinee),
+                            depth,
+                            line_map,
+                        });
+                    }
+                }
+                Ok(_) => {}
+                Err(_e) => {
+                    //warn!("{e} in file: {}", pdb_path.display());
+                }
+            }
+        }
+    }
+
+    // the public symbols are the fallback of the procedures
+    let mut publics_map = BTreeMap::new();
+    let global_symbols = pdb.global_symbols()?;
+    let mut globals = global_symbols.iter();
+    while let Some(symbol) = globals.next()? {
+        if let Ok(SymbolData::Public(public)) = symbol.parse() {
+            if !public.function && !public.code {
+                continue;
+            }
+            if let Some(rva) = public.offset.to_rva(&address_map) {
+                publics_map.insert(rva.0, format!("{}", public.name));
+            }
+        }
+    }
+
+    // for finding the sources of the build paths under the source roots
+    let mut source_links = match pdb.named_stream(b"sourcelink") {
+        Ok(stream) => source_snippet::parse_source_link(stream.as_slice(), file_name_map.values())
+            .unwrap_or_else(|e| {
+                warn!("Failed to parse the source link: {e:#}");
+                vec![]
+            }),
+        Err(_) => vec![],
+    };
+    if let Ok(stream) = pdb.named_stream(b"srcsrv") {
+        let text = String::from_utf8_lossy(stream.as_slice());
+        source_links.extend(source_snippet::parse_srcsrv(&text));
+    }
+
+    Ok(PdbInfo {
+        file_name_map,
+        modules_files_vec,
+        functions_map,
+        publics_map,
+        source_links,
+    })
+}
+
+fn to_line_info(
+    program: &LineProgram,
+    address_map: &AddressMap,
+    line_info: &pdb::LineInfo,
+    module_index: u32,
+) -> Option<LineInfo> {
+    let rva = line_info.offset.to_rva(address_map)?;
+    let file_info = match program.get_file_info(line_info.file_index) {
+        Err(e) => {
+            error!("{e}");
+            return None;
+        }
+        Ok(file_info) => file_info,
+    };
+    Some(LineInfo {
+        rva: rva.0,
+        length: line_info.length,
+        module_index,
+        file_name: file_info.name,
+        line_start: line_info.line_start,
+        line_end: line_info.line_end,
+        column_start: line_info.column_start,
+        column_end: line_info.column_end,
+    })
+}
+
+fn inlinee_name(id_finder: &IdFinder, inlinee: IdIndex) -> String {
+    match id_finder.find(inlinee).and_then(|item| item.parse()) {
+        Ok(IdData::Function(function)) => format!("{}", function.name),
+        Ok(IdData::MemberFunction(function)) => format!("{}", function.name),
+        _ => format!("inlinee {:#x}", inlinee.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open_matched_pdb, InlineSiteInfo, LineInfo, PdbInfo, ProcedureInfo};
+    use pdb::StringRef;
+    use std::{
+        collections::{BTreeMap, HashMap},
+        env, fs,
+    };
+
+    fn line_map(lines: &[(u32, u32, u32)]) -> BTreeMap<u32, LineInfo> {
+        lines
+            .iter()
+            .map(|(rva, length, line)| {
+                (
+                    *rva,
+                    LineInfo {
+                        rva: *rva,
+                        length: Some(*length),
+                        module_index: 0,
+                        file_name: StringRef(*line / 100),
+                        line_start: *line,
+                        line_end: *line,
+                        column_start: None,
+                        column_end: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inline_and_public_frames() {
+        // main calls read at line 12 that calls parse at line 205, both are inlined
+        let mut functions_map = BTreeMap::new();
+        functions_map.insert(
+            0x1000,
+            ProcedureInfo {
+                name: "main".to_string(),
+                rva: 0x1000,
+                len: 0x100,
+                global: true,
+                line_map: line_map(&[(0x1000, 0x10, 10), (0x1010, 0x40, 12), (0x1050, 0x10, 14)]),
+                inline_sites: vec![
+                    InlineSiteInfo {
+                        name: "read".to_string(),
+                        depth: 1,
+                        line_map: line_map(&[(0x1010, 0x20, 200), (0x1030, 0x20, 205)]),
+                    },
+                    InlineSiteInfo {
+                        name: "parse".to_string(),
+                        depth: 2,
+                        line_map: line_map(&[(0x1030, 0x20, 301)]),
+                    },
+                ],
+            },
+        );
+        let mut publics_map = BTreeMap::new();
+        publics_map.insert(0x1000, "main".to_string());
+        publics_map.insert(0x2000, "exported".to_string());
+        let pdb_info = PdbInfo {
+            file_name_map: HashMap::from([
+                (StringRef(0), "main.rs".to_string()),
+                (StringRef(2), "read.rs".to_string()),
+                (StringRef(3), "parse.rs".to_string()),
+            ]),
+            modules_files_vec: vec![],
+            functions_map,
+            publics_map,
+            source_links: vec![],
+        };
+
+        let frames = |offset| pdb_info.get_location_info_by_offset(offset);
+        let frame = |function: &str, line: &str| (function.to_string(), line.to_string());
+        assert_eq!(frames(0x1004), vec![frame("main+0x4", "main.rs: 10")]);
+        assert_eq!(
+            frames(0x1014),
+            vec![
+                frame("read [inline]", "read.rs: 200"),
+                frame("main+0x14", "main.rs: 12")
+            ]
+        );
+        assert_eq!(
+            frames(0x1034),
+            vec![
+                frame("parse [inline]", "parse.rs: 301"),
+                frame("read [inline]", "read.rs: 205"),
+                frame("main+0x34", "main.rs: 12")
+            ]
+        );
+        assert_eq!(frames(0x1054), vec![frame("main+0x54", "main.rs: 14")]);
+        // after main and before the next public
+        assert_eq!(frames(0x1800), vec![frame("", "")]);
+        assert_eq!(frames(0x2010), vec![frame("exported+0x10", "")]);
+    }
+
+    #[test]
+    fn open_candidates() {
+        let dir = env::temp_dir().join("sys_monitor_pdb_candidates");
+        let _ = fs::remove_dir_all(dir.as_path());
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let pdb_paths = [dir.join("a.pdb"), dir.join("b.pdb")];
+        let e = open_matched_pdb(&pdb_paths, None, None).err().unwrap();
+        assert_eq!(e.to_string(), "No pdb in the 2 locations");
+
+        // the candidate that is not a pdb is skipped with the reason
+        fs::write(pdb_paths[1].as_path(), b"not a pdb").unwrap();
+        let e = open_matched_pdb(&pdb_paths, Some(0x5E8C1B2A), None)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(e.starts_with("No matched pdb. "), "{e}");
+        assert!(e.contains("b.pdb: "), "{e}");
+    }
+}
@@ -0,0 +1,74 @@
+//! The little endian values at the offsets of the images and the index files. None if it is out of range,
+//! the offsets are from the files so they are checked instead of trusted
+pub const SECTION_HEADER_SIZE: usize = 40;
+
+pub fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
+pub fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+pub fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+pub fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+/// The zero terminated string at offset
+pub fn c_str(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.iter().position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+/// The file offset of rva by the section that contains it. The section of a overflowing range is skipped
+pub fn rva_to_offset(section_headers: &[u8], rva: u32) -> Option<u64> {
+    section_headers
+        .chunks_exact(SECTION_HEADER_SIZE)
+        .find_map(|section| {
+            let virtual_size = u32_at(section, 8)?;
+            let virtual_address = u32_at(section, 12)?;
+            let size_of_raw_data = u32_at(section, 16)?;
+            let pointer_to_raw_data = u32_at(section, 20)?;
+            let end = virtual_address.checked_add(virtual_size.max(size_of_raw_data))?;
+            (rva >= virtual_address && rva < end)
+                .then(|| (rva - virtual_address) as u64 + pointer_to_raw_data as u64)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(virtual_address: u32, virtual_size: u32, pointer_to_raw_data: u32) -> Vec<u8> {
+        let mut section = vec![0u8; SECTION_HEADER_SIZE];
+        section[8..12].copy_from_slice(&virtual_size.to_le_bytes());
+        section[12..16].copy_from_slice(&virtual_address.to_le_bytes());
+        section[20..24].copy_from_slice(&pointer_to_raw_data.to_le_bytes());
+        section
+    }
+
+    #[test]
+    fn out_of_range() {
+        let data = [1u8, 2, 3, 4, 0];
+        assert_eq!(u32_at(&data, 0), Some(0x04030201));
+        assert_eq!(u32_at(&data, 2), None);
+        assert_eq!(u16_at(&data, usize::MAX), None);
+        assert_eq!(c_str(b"ab\0", 0).as_deref(), Some("ab"));
+        assert_eq!(c_str(b"ab", 0), None);
+    }
+
+    #[test]
+    fn rva_in_sections() {
+        let mut headers = section(0xffff_f000, 0x2000, 0x400);
+        headers.extend(section(0x1000, 0x200, 0xffff_ff00));
+        // the first section overflows
+        assert_eq!(rva_to_offset(&headers, 0xffff_f800), None);
+        assert_eq!(rva_to_offset(&headers, 0x1100), Some(0x1_0000_0000));
+        assert_eq!(rva_to_offset(&headers, 0x1200), None);
+    }
+}
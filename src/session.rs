@@ -0,0 +1,305 @@
+use crate::{
+    event_list::{EventList, Node},
+    event_record_model::EventRecordModel,
+    event_trace::{process_modules, EventRecordDecoded, StackWalk},
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+use tracing::warn;
+
+// a session file is json lines. the first line is the header. the module is always before the events that refer to it
+const SESSION_MAGIC: &str = "sys_monitor_session";
+pub const SESSION_VERSION: u32 = 1;
+// the count of rows is copied out of the list at a time. the list is not locked when writing
+const SAVE_STEP: usize = 4096;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionFilters {
+    pub for_one: String,
+    pub for_pair: String,
+}
+
+pub struct SessionLoaded {
+    pub count: usize,
+    pub filters: SessionFilters,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic: String,
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ModuleStored {
+    id: u32,
+    file_name: String,
+    time_date_stamp: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code_view: Option<process_modules::CodeView>,
+}
+
+#[derive(Serialize)]
+struct EventSaved<'a> {
+    #[serde(flatten)]
+    event: &'a EventRecordDecoded,
+    process_path: &'a str,
+    stack_walk: Option<&'a StackWalk>,
+    stack_walk_2: Option<&'a StackWalk>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    bookmarked: bool,
+}
+
+#[derive(Deserialize)]
+struct EventLoaded {
+    #[serde(flatten)]
+    event: EventRecordDecoded,
+    process_path: String,
+    #[serde(default)]
+    stack_walk: Option<StackWalk>,
+    #[serde(default)]
+    stack_walk_2: Option<StackWalk>,
+    #[serde(default)]
+    bookmarked: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordSaved<'a> {
+    Header(Header),
+    Filters(&'a SessionFilters),
+    Module(ModuleStored),
+    Event(EventSaved<'a>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordLoaded {
+    Header(Header),
+    Filters(SessionFilters),
+    Module(ModuleStored),
+    Event(EventLoaded),
+}
+
+pub fn save(
+    list: &EventList<EventRecordModel>,
+    filters: &SessionFilters,
+    path: &Path,
+) -> Result<usize> {
+    let file =
+        File::create(path).map_err(|e| anyhow!("Failed to create {}: {e}", path.display()))?;
+    save_to(list, filters, BufWriter::new(file))
+}
+
+/// Write the rows in the order of the list. Returns the count of written rows.
+pub fn save_to(
+    list: &EventList<EventRecordModel>,
+    filters: &SessionFilters,
+    mut writer: impl Write,
+) -> Result<usize> {
+    write_record(
+        &mut writer,
+        &RecordSaved::Header(Header {
+            magic: SESSION_MAGIC.to_string(),
+            version: SESSION_VERSION,
+        }),
+    )?;
+    write_record(&mut writer, &RecordSaved::Filters(filters))?;
+
+    let mut module_count = 0u32;
+    let mut count = 0usize;
+    let mut index = Some(0usize);
+    let mut rows = Vec::with_capacity(SAVE_STEP);
+    while let Some(index_from) = index {
+        index = list.traversal_from(index_from, true, SAVE_STEP, |_index, item| {
+            rows.push(item.clone());
+            Ok(false)
+        })?;
+        // the modules map is only appended. so write the new modules before the events that may refer to them
+        for module_info in process_modules::get_modules_info_from(module_count) {
+            write_record(
+                &mut writer,
+                &RecordSaved::Module(ModuleStored {
+                    id: module_count,
+                    file_name: module_info.file_name.clone(),
+                    time_date_stamp: module_info.time_data_stamp,
+                    code_view: module_info.code_view.clone(),
+                }),
+            )?;
+            module_count += 1;
+        }
+        for row in rows.drain(..) {
+            let (stack_walk, stack_walk_2) = row.stack_walk_raw();
+            write_record(
+                &mut writer,
+                &RecordSaved::Event(EventSaved {
+                    event: row.array.as_ref(),
+                    process_path: row.process_path.as_str(),
+                    stack_walk: stack_walk.map(|sw| sw.as_ref()),
+                    stack_walk_2: stack_walk_2.map(|sw| sw.as_ref()),
+                    bookmarked: row.is_bookmarked(),
+                }),
+            )?;
+            count += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+pub fn load(list: &EventList<EventRecordModel>, path: &Path) -> Result<SessionLoaded> {
+    let file = File::open(path).map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))?;
+    load_from(list, BufReader::new(file))
+}
+
+/// Push the events of session to the list. The modules are merged to the modules map, so the stacks can be symbolized offline
+pub fn load_from(
+    list: &EventList<EventRecordModel>,
+    reader: impl BufRead,
+) -> Result<SessionLoaded> {
+    let mut lines = reader.lines().enumerate();
+    let header = match lines.next() {
+        Some((_, line)) => serde_json::from_str::<RecordLoaded>(line?.as_str()).ok(),
+        None => None,
+    };
+    match header {
+        Some(RecordLoaded::Header(header)) if header.magic == SESSION_MAGIC => {
+            if header.version > SESSION_VERSION {
+                return Err(anyhow!(
+                    "The session version {} is newer than supported {SESSION_VERSION}",
+                    header.version
+                ));
+            }
+        }
+        _ => return Err(anyhow!("The file is not a session file")),
+    }
+
+    let mut loaded = SessionLoaded {
+        count: 0,
+        filters: SessionFilters::default(),
+    };
+    // the id of module in file to the id in current modules map
+    let mut modules_id_map = HashMap::<u32, u32>::new();
+    for (line_number, line) in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<RecordLoaded>(line.as_str())
+            .map_err(|e| anyhow!("Failed to parse the line {}: {e}", line_number + 1))?;
+        match record {
+            RecordLoaded::Header(_) => {
+                return Err(anyhow!("Repeated header at the line {}", line_number + 1))
+            }
+            RecordLoaded::Filters(filters) => loaded.filters = filters,
+            RecordLoaded::Module(module) => {
+                let (id, _) = process_modules::module_map_insert(
+                    module.file_name,
+                    module.time_date_stamp,
+                    module.code_view,
+                );
+                modules_id_map.insert(module.id, id as u32);
+            }
+            RecordLoaded::Event(event) => {
+                let row = EventRecordModel::new(event.event, event.process_path);
+                row.set_bookmarked(event.bookmarked);
+                if let Some(sw) = event.stack_walk {
+                    row.set_stack_walk(remap_module_id(sw, &modules_id_map));
+                }
+                if let Some(sw) = event.stack_walk_2 {
+                    row.set_stack_walk_2(remap_module_id(sw, &modules_id_map));
+                }
+                list.push(Arc::new(Node::new(row)));
+                loaded.count += 1;
+            }
+        }
+    }
+    Ok(loaded)
+}
+
+fn write_record(writer: &mut impl Write, record: &RecordSaved) -> Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+fn remap_module_id(mut stack_walk: StackWalk, modules_id_map: &HashMap<u32, u32>) -> StackWalk {
+    for (_, address) in stack_walk.stacks.iter_mut() {
+        if let Some((module_id, offset)) = address.relative {
+            address.relative = match modules_id_map.get(&module_id) {
+                Some(id) => Some((*id, offset)),
+                None => {
+                    warn!("Not found the module: {module_id} in session");
+                    None
+                }
+            };
+        }
+    }
+    stack_walk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SESSION: &str = r#"{"header":{"magic":"sys_monitor_session","version":1}}
+{"filters":{"for_one":"process_id = 4","for_pair":"handle"}}
+{"module":{"id":0,"file_name":"C:\\Windows\\System32\\ntdll.dll","time_date_stamp":1234}}
+{"event":{"provider_id":"9E814AAD-3204-11D2-9A82-006008A86939","event_guid":"3D6FA8D0-FE05-11D0-9DDA-00C04FD7BA7C","event_descriptor":{"Id":0,"Version":2,"Channel":0,"Level":0,"Opcode":1,"Task":0,"Keyword":0},"decoding_source":"DecodingSourceWbem","provider_name":"","level_name":"","channel_name":"","keywords_name":"","event_display_name":null,"event_name":"Thread","opcode_name":"Start","event_message":"","provider_message":"","process_id":4,"thread_id":-1,"timestamp":"133627865451234567","properties":{"TThreadId":"0x10"},"process_path":"System","stack_walk":{"event_timestamp":133627865451234567,"stack_process":4,"stack_thread":16,"stacks":[["0",{"raw":140735000000000,"relative":[0,4096]}],["1",{"raw":140735000000001,"relative":[7,4096]}]]},"stack_walk_2":null}}
+"#;
+
+    #[test]
+    fn load_and_save() {
+        let list = EventList::<EventRecordModel>::new();
+        let loaded = load_from(&list, Cursor::new(SESSION)).unwrap();
+        assert_eq!(loaded.count, 1);
+        assert_eq!(
+            loaded.filters,
+            SessionFilters {
+                for_one: "process_id = 4".to_string(),
+                for_pair: "handle".to_string()
+            }
+        );
+        let row = list.get_by_index(0).unwrap();
+        assert_eq!(row.value.array.thread_id, u32::MAX);
+        assert_eq!(row.value.process_path, "System");
+        let (stack_walk, _) = row.value.stack_walk_raw();
+        let stacks = &stack_walk.unwrap().stacks;
+        let (module_id, offset) = stacks[0].1.relative.unwrap();
+        let module_info = process_modules::get_module_info_by_id(module_id).unwrap();
+        assert_eq!(module_info.time_data_stamp, 1234);
+        assert_eq!(offset, 4096);
+        // the module is not in session
+        assert!(stacks[1].1.relative.is_none());
+        assert!(!row.value.is_bookmarked());
+
+        row.value.set_bookmarked(true);
+        let mut buf = vec![];
+        assert_eq!(save_to(&list, &loaded.filters, &mut buf).unwrap(), 1);
+        let list_1 = EventList::<EventRecordModel>::new();
+        let loaded_1 = load_from(&list_1, Cursor::new(buf)).unwrap();
+        assert_eq!(loaded_1.count, 1);
+        assert_eq!(loaded_1.filters, loaded.filters);
+        assert!(list_1.get_by_index(0).unwrap().value.is_bookmarked());
+    }
+
+    #[test]
+    fn invalid_header() {
+        let list = EventList::<EventRecordModel>::new();
+        assert!(load_from(&list, Cursor::new("{\"filters\":{}}\n")).is_err());
+        assert!(load_from(
+            &list,
+            Cursor::new("{\"header\":{\"magic\":\"sys_monitor_session\",\"version\":100}}\n")
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! The source lines around the line of a symbolized frame. The file in the pdb is the path on the build
+//! machine, it is found locally by
+//! - the path itself
+//! - the remapped prefix. i.e. `C:\build\src=D:\src` maps `C:\build\src\main.cpp` to `D:\src\main.cpp`
+//! - the path in the repository by the Source Link or srcsrv of the pdb, under the source roots
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use parking_lot::FairMutex;
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The syntax is `<build prefix>=<local prefix>` for the remaps and `<dir>` for the local checkouts of the
+/// repositories, separated by `;`. i.e. `C:\build\src=D:\src;D:\checkout`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SourceRoots {
+    pub remaps: Vec<(String, PathBuf)>,
+    pub roots: Vec<PathBuf>,
+}
+
+impl SourceRoots {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut source_roots = Self::default();
+        for entry in s
+            .split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            match entry.split_once('=') {
+                Some((from, to)) => {
+                    let (from, to) = (from.trim(), to.trim());
+                    if from.is_empty() || to.is_empty() {
+                        return Err(anyhow!("The remap needs both prefixes: {entry}"));
+                    }
+                    source_roots
+                        .remaps
+                        .push((from.to_string(), PathBuf::from(to)));
+                }
+                None => source_roots.roots.push(PathBuf::from(entry)),
+            }
+        }
+        Ok(source_roots)
+    }
+
+    /// The local file of the build path. repo_path: the path in the repository by the Source Link or srcsrv
+    pub fn resolve(&self, file_name: &str, repo_path: Option<&str>) -> Option<PathBuf> {
+        let path = Path::new(file_name);
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        for (from, to) in self.remaps.iter() {
+            let matched = file_name
+                .get(..from.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(from));
+            if !matched {
+                continue;
+            }
+            let path = join(to, &file_name[from.len()..]);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+        // the repository path may start with the names of repository and commit, so the leading components
+        // are skipped until the file is found
+        let components: Vec<&str> = repo_path?
+            .split(['/', '\\'])
+            .filter(|component| !component.is_empty())
+            .collect();
+        for root in self.roots.iter() {
+            for skip in 0..components.len() {
+                let path = join(root, components[skip..].join("/").as_str());
+                if path.is_file() {
+                    return Some(path);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for SourceRoots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remaps = self
+            .remaps
+            .iter()
+            .map(|(from, to)| format!("{from}={}", to.display()));
+        let roots = self.roots.iter().map(|root| format!("{}", root.display()));
+        let entries: Vec<String> = remaps.chain(roots).collect();
+        f.write_str(entries.join(";").as_str())
+    }
+}
+
+// the build path is separated by `\` that is not a separator on the other systems
+fn join(dir: &Path, relative: &str) -> PathBuf {
+    relative
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .fold(dir.to_path_buf(), |path, component| path.join(component))
+}
+
+static SOURCE_ROOTS: Lazy<FairMutex<SourceRoots>> =
+    Lazy::new(|| FairMutex::new(SourceRoots::default()));
+
+// the build path in lowercase -> the path in repository
+static SOURCE_LINKS: Lazy<FairMutex<HashMap<String, String>>> =
+    Lazy::new(|| FairMutex::new(HashMap::new()));
+
+/// The local directories must exist
+pub fn source_roots_set(s: &str) -> Result<()> {
+    let source_roots = SourceRoots::parse(s)?;
+    let dirs = source_roots
+        .remaps
+        .iter()
+        .map(|(_, to)| to)
+        .chain(source_roots.roots.iter());
+    for dir in dirs {
+        if !dir.is_dir() {
+            return Err(anyhow!("The directory is not exist: {}", dir.display()));
+        }
+    }
+    *SOURCE_ROOTS.lock() = source_roots;
+    Ok(())
+}
+
+pub fn source_roots_get() -> String {
+    SOURCE_ROOTS.lock().to_string()
+}
+
+/// Record the paths in repository of the build paths, from the pdb that is loaded
+pub fn source_links_add(source_links: impl IntoIterator<Item = (String, String)>) {
+    let mut lock = SOURCE_LINKS.lock();
+    for (file_name, repo_path) in source_links {
+        lock.insert(file_name.to_lowercase(), repo_path);
+    }
+}
+
+/// The lines around the line location of a frame. i.e. `C:\src\main.rs: 12`. The line is marked by `>`
+pub fn snippet(line_location: &str, context: usize) -> Result<String> {
+    let (file_name, line) = line_location
+        .rsplit_once(": ")
+        .ok_or_else(|| anyhow!("No line in {line_location}"))?;
+    let line: usize = line
+        .trim()
+        .parse()
+        .map_err(|e| anyhow!("Invalid line in {line_location}: {e}"))?;
+    let repo_path = SOURCE_LINKS.lock().get(&file_name.to_lowercase()).cloned();
+    let path = SOURCE_ROOTS
+        .lock()
+        .resolve(file_name, repo_path.as_deref())
+        .ok_or_else(|| {
+            anyhow!("The source file is not found, set the source roots in Pdb Config: {file_name}")
+        })?;
+    let bytes =
+        fs::read(path.as_path()).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?;
+    let text = String::from_utf8_lossy(&bytes);
+    snippet_lines(path.as_path(), &text, line, context)
+}
+
+fn snippet_lines(path: &Path, text: &str, line: usize, context: usize) -> Result<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    if line == 0 || line > lines.len() {
+        return Err(anyhow!(
+            "The line {line} is out of the {} lines of {}",
+            lines.len(),
+            path.display()
+        ));
+    }
+    let first = line.saturating_sub(context).max(1);
+    let last = (line + context).min(lines.len());
+    let mut s = format!("{}\n", path.display());
+    for number in first..=last {
+        let marker = if number == line { '>' } else { ' ' };
+        let _ = writeln!(s, "{marker}{number:>6}  {}", lines[number - 1]);
+    }
+    Ok(s)
+}
+
+/// The paths in repository by the `sourcelink` stream of pdb.
+/// i.e. `{"documents": {"C:\\src\\*": "https://raw.githubusercontent.com/org/repo/<commit>/*"}}` maps
+/// `C:\src\lib\a.cpp` to `org/repo/<commit>/lib/a.cpp`. The exact documents without `*` are used as is
+pub fn parse_source_link<'a>(
+    json: &[u8],
+    file_names: impl Iterator<Item = &'a String>,
+) -> Result<Vec<(String, String)>> {
+    let value: serde_json::Value = serde_json::from_slice(json)?;
+    let documents = value
+        .get("documents")
+        .and_then(|documents| documents.as_object())
+        .ok_or_else(|| anyhow!("No documents in the source link"))?;
+    let mut source_links = vec![];
+    for file_name in file_names {
+        for (pattern, url) in documents.iter() {
+            let Some(url) = url.as_str() else {
+                continue;
+            };
+            let repo_path = match (pattern.strip_suffix('*'), url.strip_suffix('*')) {
+                (Some(prefix), Some(url_prefix)) => {
+                    let matched = file_name
+                        .get(..prefix.len())
+                        .is_some_and(|s| s.eq_ignore_ascii_case(prefix));
+                    if !matched {
+                        continue;
+                    }
+                    format!("{}{}", url_path(url_prefix), &file_name[prefix.len()..])
+                }
+                (None, _) if pattern.eq_ignore_ascii_case(file_name) => url_path(url).to_string(),
+                _ => continue,
+            };
+            source_links.push((file_name.clone(), repo_path.replace('\\', "/")));
+            break;
+        }
+    }
+    Ok(source_links)
+}
+
+/// The paths in repository by the `srcsrv` stream of pdb. The files are listed as
+/// `<build path>*<var2>*<var3>...` in the `SRCSRV: source files` section, the last variable that ends with
+/// the file name is the path in repository
+pub fn parse_srcsrv(text: &str) -> Vec<(String, String)> {
+    let mut source_links = vec![];
+    let mut in_files = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("SRCSRV:") {
+            in_files = line.starts_with("SRCSRV: source files");
+            continue;
+        }
+        if !in_files {
+            continue;
+        }
+        let mut vars = line.split('*');
+        let Some(file_name) = vars.next().filter(|file_name| !file_name.is_empty()) else {
+            continue;
+        };
+        let Some(name) = file_name.rsplit(['\\', '/']).next() else {
+            continue;
+        };
+        let repo_path = vars.rfind(|var| {
+            var.rsplit(['\\', '/'])
+                .next()
+                .is_some_and(|var_name| var_name.eq_ignore_ascii_case(name))
+        });
+        if let Some(repo_path) = repo_path {
+            source_links.push((
+                file_name.to_string(),
+                url_path(repo_path).replace('\\', "/"),
+            ));
+        }
+    }
+    source_links
+}
+
+// the path without the scheme and host if it is a url
+fn url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|i| &rest[i + 1..]).unwrap_or_default(),
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_roots() {
+        let source_roots = SourceRoots::parse(r" C:\build\src = D:\src ;; D:\checkout").unwrap();
+        assert_eq!(
+            source_roots,
+            SourceRoots {
+                remaps: vec![(r"C:\build\src".to_string(), PathBuf::from(r"D:\src"))],
+                roots: vec![PathBuf::from(r"D:\checkout")],
+            }
+        );
+        assert_eq!(source_roots.to_string(), r"C:\build\src=D:\src;D:\checkout");
+        assert!(SourceRoots::parse(r"C:\build=").is_err());
+
+        let dir = std::env::temp_dir().join("sys_monitor_source_snippet");
+        let _ = fs::remove_dir_all(dir.as_path());
+        fs::create_dir_all(dir.join("src").join("lib")).unwrap();
+        let file = dir.join("src").join("lib").join("a.cpp");
+        fs::write(file.as_path(), "").unwrap();
+        let source_roots = SourceRoots {
+            remaps: vec![(r"C:\Build\".to_string(), dir.clone())],
+            roots: vec![dir.join("src")],
+        };
+        assert_eq!(
+            source_roots.resolve(r"c:\build\src\lib\a.cpp", None),
+            Some(file.clone())
+        );
+        assert_eq!(
+            source_roots.resolve(r"E:\agent\lib\a.cpp", Some("org/repo/0123abc/lib/a.cpp")),
+            Some(file.clone())
+        );
+        assert_eq!(source_roots.resolve(r"E:\agent\lib\b.cpp", None), None);
+    }
+
+    #[test]
+    fn source_links() {
+        let file_names = [
+            r"C:\src\lib\a.cpp".to_string(),
+            r"C:\src\b.h".to_string(),
+            r"C:\Windows\c.h".to_string(),
+        ];
+        let json = br#"{"documents": {
+            "C:\\src\\*": "https://raw.githubusercontent.com/org/repo/0123abc/*",
+            "C:\\windows\\c.h": "https://example.com/sdk/c.h"
+        }}"#;
+        assert_eq!(
+            parse_source_link(json, file_names.iter()).unwrap(),
+            vec![
+                (
+                    file_names[0].clone(),
+                    "org/repo/0123abc/lib/a.cpp".to_string()
+                ),
+                (file_names[1].clone(), "org/repo/0123abc/b.h".to_string()),
+                (file_names[2].clone(), "sdk/c.h".to_string()),
+            ]
+        );
+        assert!(parse_source_link(b"{}", file_names.iter()).is_err());
+
+        let text = "SRCSRV: ini ------------------------------------------------\r
+VERSION=2\r
+SRCSRV: variables ------------------------------------------\r
+SRCSRVTRG=%targ%\\%var2%\\%fnfile%(%var1%)\r
+SRCSRV: source files ---------------------------------------\r
+c:\\src\\lib\\a.cpp*org/repo/lib/a.cpp*0123abc\r
+c:\\src\\b.h*0123abc\r
+SRCSRV: end ------------------------------------------------\r
+";
+        assert_eq!(
+            parse_srcsrv(text),
+            vec![(
+                r"c:\src\lib\a.cpp".to_string(),
+                "org/repo/lib/a.cpp".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn snippets() {
+        let text = "fn main() {\n    let a = 1;\n    println!(\"{a}\");\n}\n";
+        assert_eq!(
+            snippet_lines(Path::new("main.rs"), text, 2, 1).unwrap(),
+            "main.rs\n      1  fn main() {\n>     2      let a = 1;\n      3      println!(\"{a}\");\n"
+        );
+        assert_eq!(
+            snippet_lines(Path::new("main.rs"), text, 4, 3)
+                .unwrap()
+                .lines()
+                .count(),
+            5
+        );
+        assert!(snippet_lines(Path::new("main.rs"), text, 5, 1).is_err());
+        assert!(super::snippet("main.rs", 1).is_err());
+    }
+}
@@ -0,0 +1,590 @@
+//! The persistent index of the symbols parsed from a pdb. It is memory mapped and looked up without parsing
+//! the pdb again. The pdb path, size and modified time are recorded, the index is invalid if the pdb is changed.
+//!
+//! The layout is little endian:
+//! - header: magic, version, pdb size, pdb modified time, then the (offset, count) of the sections
+//! - functions: (rva, len, name, lines start, lines count, sites start, sites count) sorted by rva
+//! - lines: (rva, length or u32::MAX, file name, line) sorted by rva in every function and site
+//! - sites: (name, depth, lines start, lines count) of the inlined functions
+//! - publics: (rva, name) sorted by rva
+//! - source links: (build path, path in repository) by the Source Link or srcsrv of the pdb
+//! - strings: the utf8 names. a name is (offset, len) in the strings
+use crate::{
+    pdb_info::{LineInfo, PdbInfo},
+    pe_bytes::{u32_at, u64_at},
+};
+use anyhow::{anyhow, Result};
+use memmap2::Mmap;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+const MAGIC: &[u8; 8] = b"SMSYMIDX";
+const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 84;
+const FUNCTION_SIZE: usize = 32;
+const LINE_SIZE: usize = 20;
+const SITE_SIZE: usize = 20;
+const PUBLIC_SIZE: usize = 12;
+const SOURCE_LINK_SIZE: usize = 16;
+const LENGTH_NONE: u32 = u32::MAX;
+
+/// The pdb file that the index is built from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStamp {
+    pub path: PathBuf,
+    pub size: u64,
+    // the nanoseconds since UNIX_EPOCH
+    pub modified: u64,
+}
+
+impl SourceStamp {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let metadata =
+            fs::metadata(path).map_err(|e| anyhow!("Failed to stat {}: {e}", path.display()))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+pub struct SymbolIndex {
+    data: Mmap,
+    source: SourceStamp,
+    functions: Range<usize>,
+    lines: Range<usize>,
+    sites: Range<usize>,
+    publics: Range<usize>,
+    source_links: Range<usize>,
+    strings: Range<usize>,
+}
+
+impl SymbolIndex {
+    /// Map the index file. It fails if the file is broken or the pdb is changed
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).map_err(|e| anyhow!("Failed to open {}: {e}", path.display()))?;
+        // the index file is replaced by renaming instead of modifying
+        let data = unsafe { Mmap::map(&file) }
+            .map_err(|e| anyhow!("Failed to map {}: {e}", path.display()))?;
+        if data.len() < HEADER_SIZE || &data[..8] != MAGIC {
+            return Err(anyhow!("Not a symbol index: {}", path.display()));
+        }
+        if u32_at(&data, 8).unwrap_or_default() != VERSION {
+            return Err(anyhow!("The version of symbol index is changed"));
+        }
+        let section = |pos: usize, size: usize| -> Result<Range<usize>> {
+            let start = u32_at(&data, pos).unwrap_or_default() as usize;
+            let end = start + u32_at(&data, pos + 4).unwrap_or_default() as usize * size;
+            if end > data.len() {
+                return Err(anyhow!("The symbol index is broken: {}", path.display()));
+            }
+            Ok(start..end)
+        };
+        let functions = section(36, FUNCTION_SIZE)?;
+        let lines = section(44, LINE_SIZE)?;
+        let sites = section(52, SITE_SIZE)?;
+        let publics = section(60, PUBLIC_SIZE)?;
+        let source_links = section(68, SOURCE_LINK_SIZE)?;
+        let strings = section(76, 1)?;
+        let source_path = string_at(&data, strings.clone(), 28);
+        let source = SourceStamp {
+            path: PathBuf::from(source_path),
+            size: u64_at(&data, 12).unwrap_or_default(),
+            modified: u64_at(&data, 20).unwrap_or_default(),
+        };
+        let source_now = SourceStamp::from_file(source.path.as_path())?;
+        if source_now != source {
+            return Err(anyhow!("The pdb is changed: {}", source.path.display()));
+        }
+        Ok(Self {
+            data,
+            source,
+            functions,
+            lines,
+            sites,
+            publics,
+            source_links,
+            strings,
+        })
+    }
+
+    /// Write the index to the temporary file then rename it, so the mapped index is not modified
+    pub fn write(path: &Path, pdb_info: &PdbInfo, source: &SourceStamp) -> Result<()> {
+        let data = Self::build(pdb_info, source);
+        let temp = path.with_extension("tmp");
+        fs::write(temp.as_path(), data)
+            .and_then(|_| fs::rename(temp.as_path(), path))
+            .map_err(|e| {
+                let _ = fs::remove_file(temp.as_path());
+                anyhow!("Failed to write {}: {e}", path.display())
+            })
+    }
+
+    pub fn build(pdb_info: &PdbInfo, source: &SourceStamp) -> Vec<u8> {
+        let mut strings = Strings::default();
+        let source_path = strings.add(source.path.to_string_lossy().as_ref());
+        let mut functions = vec![];
+        let mut lines = vec![];
+        let mut sites = vec![];
+        let mut publics = vec![];
+        for procedure_info in pdb_info.functions_map.values() {
+            let name = strings.add(procedure_info.name.as_str());
+            let procedure_lines =
+                push_lines(&mut lines, &mut strings, pdb_info, &procedure_info.line_map);
+            let sites_start = (sites.len() / SITE_SIZE) as u32;
+            for site in procedure_info.inline_sites.iter() {
+                let site_name = strings.add(site.name.as_str());
+                let site_lines = push_lines(&mut lines, &mut strings, pdb_info, &site.line_map);
+                put_u32s(
+                    &mut sites,
+                    &[
+                        site_name.0,
+                        site_name.1,
+                        site.depth,
+                        site_lines.0,
+                        site_lines.1,
+                    ],
+                );
+            }
+            put_u32s(
+                &mut functions,
+                &[
+                    procedure_info.rva,
+                    procedure_info.len,
+                    name.0,
+                    name.1,
+                    procedure_lines.0,
+                    procedure_lines.1,
+                    sites_start,
+                    procedure_info.inline_sites.len() as u32,
+                ],
+            );
+        }
+        for (rva, name) in pdb_info.publics_map.iter() {
+            let name = strings.add(name.as_str());
+            put_u32s(&mut publics, &[*rva, name.0, name.1]);
+        }
+        let mut source_links = vec![];
+        for (file_name, repo_path) in pdb_info.source_links.iter() {
+            let file_name = strings.add(file_name.as_str());
+            let repo_path = strings.add(repo_path.as_str());
+            put_u32s(
+                &mut source_links,
+                &[file_name.0, file_name.1, repo_path.0, repo_path.1],
+            );
+        }
+
+        let mut data = Vec::with_capacity(
+            HEADER_SIZE
+                + functions.len()
+                + lines.len()
+                + sites.len()
+                + publics.len()
+                + source_links.len()
+                + strings.data.len(),
+        );
+        data.extend_from_slice(MAGIC);
+        put_u32s(&mut data, &[VERSION]);
+        data.extend_from_slice(&source.size.to_le_bytes());
+        data.extend_from_slice(&source.modified.to_le_bytes());
+        put_u32s(&mut data, &[source_path.0, source_path.1]);
+        let mut offset = HEADER_SIZE;
+        for (section, size) in [
+            (&functions, FUNCTION_SIZE),
+            (&lines, LINE_SIZE),
+            (&sites, SITE_SIZE),
+            (&publics, PUBLIC_SIZE),
+            (&source_links, SOURCE_LINK_SIZE),
+            (&strings.data, 1),
+        ] {
+            put_u32s(&mut data, &[offset as u32, (section.len() / size) as u32]);
+            offset += section.len();
+        }
+        for section in [
+            &functions,
+            &lines,
+            &sites,
+            &publics,
+            &source_links,
+            &strings.data,
+        ] {
+            data.extend_from_slice(section);
+        }
+        data
+    }
+
+    pub fn source(&self) -> &SourceStamp {
+        &self.source
+    }
+
+    /// (build path, path in repository) of the source files
+    pub fn source_links(&self) -> Vec<(String, String)> {
+        (0..self.source_links.len() / SOURCE_LINK_SIZE)
+            .map(|i| {
+                (
+                    self.string(&self.source_links, SOURCE_LINK_SIZE, i, 0)
+                        .to_string(),
+                    self.string(&self.source_links, SOURCE_LINK_SIZE, i, 2)
+                        .to_string(),
+                )
+            })
+            .collect()
+    }
+
+    /// The same as PdbInfo::get_location_info_by_offset
+    pub fn get_location_info_by_offset(
+        &self,
+        offset: u32,
+    ) -> Vec<(
+        /*function_location*/ String,
+        /*line_location*/ String,
+    )> {
+        let functions_count = self.functions.len() / FUNCTION_SIZE;
+        let index = partition_point(functions_count, |i| self.function(i, 0) <= offset);
+        if let Some(function) = index.checked_sub(1) {
+            let rva = self.function(function, 0);
+            // the overflowed range contains nothing, the same as the pe_bytes::rva_to_offset
+            let end = rva.checked_add(self.function(function, 1));
+            if end.is_some_and(|end| offset < end) {
+                let sites_start = self.function(function, 6) as usize;
+                let mut sites: Vec<(u32, String, String)> = (sites_start
+                    ..sites_start + self.function(function, 7) as usize)
+                    .filter_map(|site| {
+                        let lines = self.field(&self.sites, SITE_SIZE, site, 3);
+                        let lines_count = self.field(&self.sites, SITE_SIZE, site, 4);
+                        // the range of site is unknown without the length
+                        let line = self.line_at(lines, lines_count, offset)?;
+                        if self.field(&self.lines, LINE_SIZE, line, 1) == LENGTH_NONE {
+                            return None;
+                        }
+                        Some((
+                            self.field(&self.sites, SITE_SIZE, site, 2),
+                            self.string(&self.sites, SITE_SIZE, site, 0).to_string(),
+                            self.line_location(line),
+                        ))
+                    })
+                    .collect();
+                sites.sort_by_key(|(depth, ..)| Reverse(*depth));
+                let mut frames: Vec<(String, String)> = sites
+                    .into_iter()
+                    .map(|(_, name, line)| (format!("{name} [inline]"), line))
+                    .collect();
+                let line = self
+                    .line_at(
+                        self.function(function, 4),
+                        self.function(function, 5),
+                        offset,
+                    )
+                    .map(|line| self.line_location(line))
+                    .unwrap_or_default();
+                frames.push((
+                    format!(
+                        "{}+{:#x}",
+                        self.string(&self.functions, FUNCTION_SIZE, function, 2),
+                        offset - rva
+                    ),
+                    line,
+                ));
+                return frames;
+            }
+        }
+
+        // the public symbol has no length, it is not used after a procedure at or after it
+        let publics_count = self.publics.len() / PUBLIC_SIZE;
+        let index = partition_point(publics_count, |i| {
+            self.field(&self.publics, PUBLIC_SIZE, i, 0) <= offset
+        });
+        if let Some(public) = index.checked_sub(1) {
+            let rva = self.field(&self.publics, PUBLIC_SIZE, public, 0);
+            let function = partition_point(functions_count, |i| self.function(i, 0) < rva);
+            let procedure_between =
+                function < functions_count && self.function(function, 0) <= offset;
+            if !procedure_between {
+                let name = self.string(&self.publics, PUBLIC_SIZE, public, 1);
+                return vec![(format!("{name}+{:#x}", offset - rva), String::new())];
+            }
+        }
+        vec![(String::new(), String::new())]
+    }
+
+    fn function(&self, index: usize, field: usize) -> u32 {
+        self.field(&self.functions, FUNCTION_SIZE, index, field)
+    }
+
+    fn field(&self, section: &Range<usize>, size: usize, index: usize, field: usize) -> u32 {
+        let pos = section.start + index * size + field * 4;
+        if pos + 4 > section.end {
+            return 0;
+        }
+        u32_at(&self.data, pos).unwrap_or_default()
+    }
+
+    // the string of (offset, len) at the field
+    fn string(&self, section: &Range<usize>, size: usize, index: usize, field: usize) -> &str {
+        string_at(
+            &self.data,
+            self.strings.clone(),
+            section.start + index * size + field * 4,
+        )
+    }
+
+    // the index of line that contains the offset in the lines [start, start + count)
+    fn line_at(&self, start: u32, count: u32, offset: u32) -> Option<usize> {
+        let start = start as usize;
+        let index = partition_point(count as usize, |i| {
+            self.field(&self.lines, LINE_SIZE, start + i, 0) <= offset
+        });
+        let line = start + index.checked_sub(1)?;
+        let rva = self.field(&self.lines, LINE_SIZE, line, 0);
+        let length = self.field(&self.lines, LINE_SIZE, line, 1);
+        if length != LENGTH_NONE && !rva.checked_add(length).is_some_and(|end| offset < end) {
+            return None;
+        }
+        Some(line)
+    }
+
+    // i.e. `C:\src\main.rs: 12`
+    fn line_location(&self, line: usize) -> String {
+        format!(
+            "{}: {}",
+            self.string(&self.lines, LINE_SIZE, line, 2),
+            self.field(&self.lines, LINE_SIZE, line, 4)
+        )
+    }
+}
+
+#[derive(Default)]
+struct Strings {
+    data: Vec<u8>,
+    // the same names are stored once
+    map: HashMap<String, (u32, u32)>,
+}
+
+impl Strings {
+    fn add(&mut self, s: &str) -> (u32, u32) {
+        if let Some(position) = self.map.get(s) {
+            return *position;
+        }
+        let position = (self.data.len() as u32, s.len() as u32);
+        self.data.extend_from_slice(s.as_bytes());
+        self.map.insert(s.to_string(), position);
+        position
+    }
+}
+
+// append the lines, return (start, count) of them
+fn push_lines(
+    lines: &mut Vec<u8>,
+    strings: &mut Strings,
+    pdb_info: &PdbInfo,
+    line_map: &BTreeMap<u32, LineInfo>,
+) -> (u32, u32) {
+    let start = (lines.len() / LINE_SIZE) as u32;
+    for line_info in line_map.values() {
+        let file_name = pdb_info
+            .file_name_map
+            .get(&line_info.file_name)
+            .map(|name| name.as_str())
+            .unwrap_or_default();
+        let file_name = strings.add(file_name);
+        put_u32s(
+            lines,
+            &[
+                line_info.rva,
+                line_info.length.unwrap_or(LENGTH_NONE),
+                file_name.0,
+                file_name.1,
+                line_info.line_start,
+            ],
+        );
+    }
+    (start, line_map.len() as u32)
+}
+
+// the first index in [0, count) that f returns false
+fn partition_point(count: usize, f: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if f(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+fn put_u32s(data: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+// the string of (offset, len) at pos. empty if it is broken
+fn string_at(data: &[u8], strings: Range<usize>, pos: usize) -> &str {
+    let start = strings.start + u32_at(data, pos).unwrap_or_default() as usize;
+    let end = start + u32_at(data, pos + 4).unwrap_or_default() as usize;
+    if end > strings.end {
+        return "";
+    }
+    std::str::from_utf8(&data[start..end]).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdb_info::{InlineSiteInfo, ProcedureInfo};
+    use pdb::StringRef;
+
+    fn line_info(rva: u32, length: Option<u32>, file: u32, line: u32) -> (u32, LineInfo) {
+        (
+            rva,
+            LineInfo {
+                rva,
+                length,
+                module_index: 0,
+                file_name: StringRef(file),
+                line_start: line,
+                line_end: line,
+                column_start: None,
+                column_end: None,
+            },
+        )
+    }
+
+    #[test]
+    fn index_same_as_pdb() {
+        let mut functions_map = BTreeMap::new();
+        for (rva, name) in [(0x1000, "main"), (0x1200, "?Foo@Bar@@QEAAXH@Z")] {
+            functions_map.insert(
+                rva,
+                ProcedureInfo {
+                    name: name.to_string(),
+                    rva,
+                    len: 0x100,
+                    global: true,
+                    line_map: BTreeMap::from([
+                        line_info(rva, Some(0x10), 0, 10),
+                        line_info(rva + 0x10, Some(0x40), 0, 12),
+                        line_info(rva + 0x50, None, 1, 14),
+                    ]),
+                    inline_sites: vec![],
+                },
+            );
+        }
+        functions_map
+            .get_mut(&0x1000)
+            .unwrap()
+            .inline_sites
+            .extend([
+                InlineSiteInfo {
+                    name: "read".to_string(),
+                    depth: 1,
+                    line_map: BTreeMap::from([
+                        line_info(0x1010, Some(0x20), 1, 200),
+                        line_info(0x1030, Some(0x20), 1, 205),
+                    ]),
+                },
+                InlineSiteInfo {
+                    name: "parse".to_string(),
+                    depth: 2,
+                    line_map: BTreeMap::from([line_info(0x1030, Some(0x20), 0, 301)]),
+                },
+            ]);
+        let pdb_info = PdbInfo {
+            file_name_map: HashMap::from([
+                (StringRef(0), "main.rs".to_string()),
+                (StringRef(1), "read.rs".to_string()),
+            ]),
+            modules_files_vec: vec![],
+            functions_map,
+            publics_map: BTreeMap::from([
+                (0x1000, "main".to_string()),
+                (0x2000, "exported".to_string()),
+            ]),
+            source_links: vec![("main.rs".to_string(), "org/repo/main.rs".to_string())],
+        };
+
+        let dir = std::env::temp_dir().join("sys_monitor_symbol_index");
+        let _ = fs::remove_dir_all(dir.as_path());
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let pdb_path = dir.join("a.pdb");
+        fs::write(pdb_path.as_path(), b"pdb").unwrap();
+        let index_path = dir.join("a.idx");
+        let source = SourceStamp::from_file(pdb_path.as_path()).unwrap();
+        SymbolIndex::write(index_path.as_path(), &pdb_info, &source).unwrap();
+
+        let index = SymbolIndex::open(index_path.as_path()).unwrap();
+        assert_eq!(index.source(), &source);
+        assert_eq!(index.source_links(), pdb_info.source_links);
+        for offset in [
+            0x0, 0x1004, 0x1014, 0x1034, 0x1054, 0x10ff, 0x1100, 0x1204, 0x1260, 0x1800, 0x2010,
+        ] {
+            assert_eq!(
+                index.get_location_info_by_offset(offset),
+                pdb_info.get_location_info_by_offset(offset),
+                "{offset:#x}"
+            );
+        }
+        drop(index);
+
+        // the pdb is changed
+        fs::write(pdb_path.as_path(), b"pdb changed").unwrap();
+        assert!(SymbolIndex::open(index_path.as_path()).is_err());
+        fs::write(dir.join("b.idx"), b"SMSYMIDX").unwrap();
+        assert!(SymbolIndex::open(dir.join("b.idx").as_path()).is_err());
+    }
+
+    #[test]
+    fn overflowed_ranges() {
+        let rva = 0xffff_ff00;
+        let pdb_info = PdbInfo {
+            file_name_map: HashMap::from([(StringRef(0), "main.rs".to_string())]),
+            modules_files_vec: vec![],
+            functions_map: BTreeMap::from([(
+                rva,
+                ProcedureInfo {
+                    name: "tail".to_string(),
+                    rva,
+                    len: 0x200,
+                    global: true,
+                    line_map: BTreeMap::from([line_info(rva, Some(0x200), 0, 10)]),
+                    inline_sites: vec![],
+                },
+            )]),
+            publics_map: BTreeMap::new(),
+            source_links: vec![],
+        };
+
+        let dir = std::env::temp_dir().join("sys_monitor_symbol_index_overflowed");
+        let _ = fs::remove_dir_all(dir.as_path());
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let pdb_path = dir.join("a.pdb");
+        fs::write(pdb_path.as_path(), b"pdb").unwrap();
+        let index_path = dir.join("a.idx");
+        let source = SourceStamp::from_file(pdb_path.as_path()).unwrap();
+        SymbolIndex::write(index_path.as_path(), &pdb_info, &source).unwrap();
+
+        // the range over u32::MAX contains nothing
+        let index = SymbolIndex::open(index_path.as_path()).unwrap();
+        assert_eq!(
+            index.get_location_info_by_offset(rva + 0x10),
+            vec![(String::new(), String::new())]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! The symbol path in the syntax of `_NT_SYMBOL_PATH`. i.e. `dir1;dir2;cache*dir;srv*cache*url`
+//! The pdb is searched in the flat layout `<dir>/<pdbname>` and the symstore layout `<dir>/<pdbname>/<GUID><age>/<pdbname>`
+use anyhow::{anyhow, Result};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolPathEntry {
+    Directory(PathBuf),
+    // the downstream store of the symbol servers. it is also searched as a directory
+    Cache(PathBuf),
+    // srv*cache*url. the pdbs are downloaded to the cache that is also searched as a directory
+    Server { cache: PathBuf, url: String },
+}
+
+/// The pdb that the module is linked with. i.e. by the CodeView record of module
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdbSignature {
+    // the path when the module is linked. i.e. D:\build\ntkrnlmp.pdb
+    pub pdb_path: String,
+    // the directory name of symstore. i.e. `6A399AE04BC64DE9870B3657F8947E7E1` for the guid with age 1
+    pub key: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SymbolPath {
+    pub entries: Vec<SymbolPathEntry>,
+}
+
+// the downstream store of `srv*url` when no cache is given. it is `sym` like the default of dbghelp
+pub const DEFAULT_CACHE: &str = "sym";
+
+impl SymbolPath {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut symbol_path = Self::default();
+        for entry in split(s) {
+            let entry = symbol_path.parse_entry(entry)?;
+            symbol_path.entries.push(entry);
+        }
+        Ok(symbol_path)
+    }
+
+    /// Parse a entry that follows the entries. `srv*url` uses the last `cache*dir`, otherwise the DEFAULT_CACHE
+    pub fn parse_entry(&self, entry: &str) -> Result<SymbolPathEntry> {
+        let lower = entry.to_ascii_lowercase();
+        if lower.starts_with("cache*") {
+            let dir = entry["cache*".len()..].trim();
+            if dir.is_empty() {
+                return Err(anyhow!("No directory for the cache: {entry}"));
+            }
+            Ok(SymbolPathEntry::Cache(PathBuf::from(dir)))
+        } else if lower.starts_with("srv*") {
+            let parts: Vec<&str> = entry.split('*').map(str::trim).collect();
+            let url = parts[parts.len() - 1];
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(anyhow!("The symbol server is not http: {entry}"));
+            }
+            let cache = match parts
+                .get(1..parts.len() - 1)
+                .and_then(|caches| caches.first())
+            {
+                Some(cache) if !cache.is_empty() => PathBuf::from(cache),
+                _ => self
+                    .entries
+                    .iter()
+                    .rev()
+                    .find_map(|entry| match entry {
+                        SymbolPathEntry::Cache(dir) => Some(dir.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE)),
+            };
+            Ok(SymbolPathEntry::Server {
+                cache,
+                url: url.trim_end_matches('/').to_string(),
+            })
+        } else if lower.starts_with("symsrv*") {
+            Err(anyhow!("The symsrv dll is not supported: {entry}"))
+        } else {
+            Ok(SymbolPathEntry::Directory(PathBuf::from(entry)))
+        }
+    }
+
+    /// The paths that may be the pdb of module, in the order of searching.
+    /// module_path: the full path of module file. i.e. C:\Windows\System32\ntdll.dll
+    pub fn pdb_candidates(
+        &self,
+        module_path: &Path,
+        pdb_signature: Option<&PdbSignature>,
+    ) -> Vec<PathBuf> {
+        let module_pdb_name = module_path
+            .file_name()
+            .map(|name| Path::new(name).with_extension("pdb"));
+        // the pdb name of code view is first. i.e. ntkrnlmp.pdb for ntoskrnl.exe
+        let mut pdb_names: Vec<PathBuf> = vec![];
+        if let Some(name) =
+            pdb_signature.and_then(|pdb_signature| pdb_file_name(&pdb_signature.pdb_path))
+        {
+            pdb_names.push(PathBuf::from(name));
+        }
+        if let Some(name) = module_pdb_name {
+            if !pdb_names.contains(&name) {
+                pdb_names.push(name);
+            }
+        }
+
+        let mut candidates = vec![];
+        let mut push = |path: PathBuf| {
+            if !candidates.contains(&path) {
+                candidates.push(path);
+            }
+        };
+        for entry in self.entries.iter() {
+            let (SymbolPathEntry::Directory(dir)
+            | SymbolPathEntry::Cache(dir)
+            | SymbolPathEntry::Server { cache: dir, .. }) = entry;
+            for pdb_name in pdb_names.iter() {
+                if let Some(pdb_signature) = pdb_signature {
+                    push(
+                        dir.join(pdb_name)
+                            .join(pdb_signature.key.as_str())
+                            .join(pdb_name),
+                    );
+                }
+                push(dir.join(pdb_name));
+            }
+        }
+        // the path when the module is linked
+        if let Some(pdb_signature) = pdb_signature {
+            if Path::new(pdb_signature.pdb_path.as_str()).is_absolute() {
+                push(PathBuf::from(pdb_signature.pdb_path.as_str()));
+            }
+        }
+        if let Some(module_dir) = module_path.parent() {
+            for pdb_name in pdb_names.iter() {
+                push(module_dir.join(pdb_name));
+            }
+        }
+        candidates
+    }
+}
+
+impl fmt::Display for SymbolPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            match entry {
+                SymbolPathEntry::Directory(dir) => write!(f, "{}", dir.display())?,
+                SymbolPathEntry::Cache(dir) => write!(f, "cache*{}", dir.display())?,
+                SymbolPathEntry::Server { cache, url } => {
+                    write!(f, "srv*{}*{url}", cache.display())?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The non-empty entries of a symbol path
+pub fn split(s: &str) -> impl Iterator<Item = &str> {
+    s.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+/// The file name of pdb path in the code view. The path is recorded by the linker of other machine
+pub fn pdb_file_name(pdb_path: &str) -> Option<&str> {
+    pdb_path
+        .rsplit(['\\', '/'])
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn parse() {
+        let symbol_path = SymbolPath::parse(r"C:\pdb; ;cache*D:\cache;").unwrap();
+        assert_eq!(
+            symbol_path.entries,
+            vec![
+                SymbolPathEntry::Directory(PathBuf::from(r"C:\pdb")),
+                SymbolPathEntry::Cache(PathBuf::from(r"D:\cache")),
+            ]
+        );
+        assert_eq!(symbol_path.to_string(), r"C:\pdb;cache*D:\cache");
+        assert!(SymbolPath::parse("cache*").is_err());
+
+        let symbol_path = SymbolPath::parse(
+            r"srv*E:\sym*https://msdl.microsoft.com/download/symbols/;cache*D:\cache;srv*http://127.0.0.1",
+        )
+        .unwrap();
+        assert_eq!(
+            symbol_path.entries,
+            vec![
+                SymbolPathEntry::Server {
+                    cache: PathBuf::from(r"E:\sym"),
+                    url: "https://msdl.microsoft.com/download/symbols".to_string()
+                },
+                SymbolPathEntry::Cache(PathBuf::from(r"D:\cache")),
+                SymbolPathEntry::Server {
+                    cache: PathBuf::from(r"D:\cache"),
+                    url: "http://127.0.0.1".to_string()
+                },
+            ]
+        );
+        // the default downstream store without a cache
+        assert_eq!(
+            SymbolPath::parse("srv*https://msdl.microsoft.com/download/symbols")
+                .unwrap()
+                .entries,
+            vec![SymbolPathEntry::Server {
+                cache: PathBuf::from(DEFAULT_CACHE),
+                url: "https://msdl.microsoft.com/download/symbols".to_string()
+            }]
+        );
+        assert!(SymbolPath::parse("srv*ftp://127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn pdb_candidates() {
+        // the absolute paths of the platform, the separators of `C:\pdb` are not of Linux
+        let root = env::temp_dir();
+        let pdb_dir = root.join("pdb");
+        let build_pdb = root.join("build").join("ntkrnlmp.pdb");
+        let module_dir = root.join("Windows").join("System32");
+        let symbol_path = SymbolPath::parse(pdb_dir.to_string_lossy().as_ref()).unwrap();
+        let key = "6A399AE04BC64DE9870B3657F8947E7E1A";
+        let pdb_signature = PdbSignature {
+            pdb_path: build_pdb.to_string_lossy().into_owned(),
+            key: key.to_string(),
+        };
+        let candidates =
+            symbol_path.pdb_candidates(&module_dir.join("ntoskrnl.exe"), Some(&pdb_signature));
+        assert_eq!(
+            candidates,
+            vec![
+                pdb_dir.join("ntkrnlmp.pdb").join(key).join("ntkrnlmp.pdb"),
+                pdb_dir.join("ntkrnlmp.pdb"),
+                pdb_dir.join("ntoskrnl.pdb").join(key).join("ntoskrnl.pdb"),
+                pdb_dir.join("ntoskrnl.pdb"),
+                build_pdb,
+                module_dir.join("ntkrnlmp.pdb"),
+                module_dir.join("ntoskrnl.pdb"),
+            ]
+        );
+    }
+}
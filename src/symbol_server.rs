@@ -0,0 +1,285 @@
+//! Download the pdbs from the http symbol server to the cache in the symstore layout.
+//! i.e. `<url>/<pdbname>/<GUID><age>/<pdbname>`, then the compressed `<pdbname>.pd_` and the redirect `file.ptr`
+use crate::symbol_path::{self, PdbSignature, SymbolPath, SymbolPathEntry};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use parking_lot::FairMutex;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread,
+};
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadRequest {
+    pub url: String,
+    pub cache: PathBuf,
+    pub pdb_name: String,
+    // <GUID><age>
+    pub key: String,
+}
+
+impl DownloadRequest {
+    /// The path in the cache. i.e. `<cache>/<pdbname>/<GUID><age>/<pdbname>`
+    pub fn target(&self) -> PathBuf {
+        self.cache
+            .join(self.pdb_name.as_str())
+            .join(self.key.as_str())
+            .join(self.pdb_name.as_str())
+    }
+}
+
+struct Downloader {
+    sender: FairMutex<Sender<DownloadRequest>>,
+    // the targets that are downloading or failed. the failed is not retried until the symbol path is changed
+    requested: FairMutex<HashSet<PathBuf>>,
+    // increased by every downloaded pdb. the modules without pdb are searched again if it is changed
+    downloaded: AtomicUsize,
+}
+
+static DOWNLOADER: Lazy<Downloader> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<DownloadRequest>();
+    thread::spawn(move || {
+        while let Ok(request) = receiver.recv() {
+            match download(&request) {
+                Ok(path) => {
+                    info!("Downloaded {}", path.display());
+                    DOWNLOADER.downloaded.fetch_add(1, Ordering::Release);
+                }
+                Err(e) => warn!(
+                    "Failed to download {} from {}: {e:#}",
+                    request.pdb_name, request.url
+                ),
+            }
+        }
+    });
+    Downloader {
+        sender: FairMutex::new(sender),
+        requested: FairMutex::new(HashSet::new()),
+        downloaded: AtomicUsize::new(0),
+    }
+});
+
+/// Download on the background worker. Returns false if it has been requested
+pub fn download_in_background(request: DownloadRequest) -> bool {
+    if !DOWNLOADER.requested.lock().insert(request.target()) {
+        return false;
+    }
+    if let Err(e) = DOWNLOADER.sender.lock().send(request) {
+        error!("Failed to send the download request: {e}");
+    }
+    true
+}
+
+/// The count of downloaded pdbs
+pub fn downloaded_count() -> usize {
+    DOWNLOADER.downloaded.load(Ordering::Acquire)
+}
+
+/// The failed downloads are retried after this
+pub fn download_requested_clear() {
+    DOWNLOADER.requested.lock().clear();
+}
+
+/// The requests of the `srv*` entries in the symbol path
+pub fn download_requests(
+    symbol_path: &SymbolPath,
+    pdb_signature: &PdbSignature,
+) -> Vec<DownloadRequest> {
+    let Some(pdb_name) = symbol_path::pdb_file_name(pdb_signature.pdb_path.as_str()) else {
+        return vec![];
+    };
+    symbol_path
+        .entries
+        .iter()
+        .filter_map(|entry| match entry {
+            SymbolPathEntry::Server { cache, url } => Some(DownloadRequest {
+                url: url.clone(),
+                cache: cache.clone(),
+                pdb_name: pdb_name.to_string(),
+                key: pdb_signature.key.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn download(request: &DownloadRequest) -> Result<PathBuf> {
+    let target = request.target();
+    let dir = target
+        .parent()
+        .ok_or_else(|| anyhow!("No parent of {}", target.display()))?;
+    let url_dir = format!("{}/{}/{}", request.url, request.pdb_name, request.key);
+
+    if let Some(data) = http_get(format!("{url_dir}/{}", request.pdb_name).as_str())? {
+        write_file(dir, &target, &data)?;
+        return Ok(target);
+    }
+    // the last letter is replaced by `_` for the cab file. i.e. ntkrnlmp.pd_
+    let mut compressed_name = request.pdb_name.clone();
+    compressed_name.pop();
+    compressed_name.push('_');
+    if let Some(data) = http_get(format!("{url_dir}/{compressed_name}").as_str())? {
+        let data = extract_cab(&data)?;
+        write_file(dir, &target, &data)?;
+        return Ok(target);
+    }
+    // `PATH:<path>` or `MSG:<message>`
+    if let Some(data) = http_get(format!("{url_dir}/file.ptr").as_str())? {
+        let file_ptr = String::from_utf8_lossy(&data);
+        let file_ptr = file_ptr.trim();
+        if let Some(path) = file_ptr.strip_prefix("PATH:") {
+            let data = fs::read(path).map_err(|e| anyhow!("Failed to read {path}: {e}"))?;
+            write_file(dir, &target, &data)?;
+            return Ok(target);
+        }
+        return Err(anyhow!("The file.ptr is not a path: {file_ptr}"));
+    }
+    Err(anyhow!("Not found in {url_dir}"))
+}
+
+// None if it is not found
+fn http_get(url: &str) -> Result<Option<Vec<u8>>> {
+    let response = match ureq::get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(anyhow!("Failed to get {url}: {e}")),
+    };
+    let mut data = vec![];
+    response
+        .into_reader()
+        .read_to_end(&mut data)
+        .map_err(|e| anyhow!("Failed to read {url}: {e}"))?;
+    Ok(Some(data))
+}
+
+// the first file of the cab
+fn extract_cab(data: &[u8]) -> Result<Vec<u8>> {
+    let mut cabinet = cab::Cabinet::new(Cursor::new(data))?;
+    let name = cabinet
+        .folder_entries()
+        .flat_map(|folder| folder.file_entries())
+        .map(|file| file.name().to_string())
+        .next()
+        .ok_or_else(|| anyhow!("No file in the cab"))?;
+    let mut data = vec![];
+    cabinet.read_file(name.as_str())?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// the partial file is not left in the cache when failing
+fn write_file(dir: &Path, target: &Path, data: &[u8]) -> Result<()> {
+    fs::create_dir_all(dir).map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+    let temp = target.with_extension("download");
+    File::create(temp.as_path())
+        .and_then(|mut file| file.write_all(data))
+        .and_then(|_| fs::rename(temp.as_path(), target))
+        .map_err(|e| {
+            let _ = fs::remove_file(temp.as_path());
+            anyhow!("Failed to write {}: {e}", target.display())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader},
+        net::TcpListener,
+    };
+
+    // a http server that responds the bodies by the paths, others are 404
+    fn serve(bodies: HashMap<String, Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let _ = match bodies.get(path) {
+                    Some(body) => stream
+                        .write_all(
+                            format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                body.len()
+                            )
+                            .as_bytes(),
+                        )
+                        .and_then(|_| stream.write_all(body)),
+                    None => stream.write_all(
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    ),
+                };
+            }
+        });
+        url
+    }
+
+    fn request(url: &str, cache: &Path, pdb_name: &str) -> DownloadRequest {
+        DownloadRequest {
+            url: url.to_string(),
+            cache: cache.to_path_buf(),
+            pdb_name: pdb_name.to_string(),
+            key: "6A399AE04BC64DE9870B3657F8947E7E1".to_string(),
+        }
+    }
+
+    #[test]
+    fn download_from_server() {
+        let cache = std::env::temp_dir().join("sys_monitor_symbol_server");
+        let _ = fs::remove_dir_all(cache.as_path());
+        let redirected = std::env::temp_dir().join("sys_monitor_symbol_server_redirected.pdb");
+        fs::write(redirected.as_path(), b"redirected").unwrap();
+
+        let mut builder = cab::CabinetBuilder::new();
+        builder
+            .add_folder(cab::CompressionType::None)
+            .add_file("b.pdb");
+        let mut writer = builder.build(Cursor::new(Vec::new())).unwrap();
+        while let Some(mut file_writer) = writer.next_file().unwrap() {
+            file_writer.write_all(b"compressed").unwrap();
+        }
+        let cab_data = writer.finish().unwrap().into_inner();
+
+        let key = "6A399AE04BC64DE9870B3657F8947E7E1";
+        let mut bodies = HashMap::new();
+        bodies.insert(format!("/a.pdb/{key}/a.pdb"), b"plain".to_vec());
+        bodies.insert(format!("/b.pdb/{key}/b.pd_"), cab_data);
+        bodies.insert(
+            format!("/c.pdb/{key}/file.ptr"),
+            format!("PATH:{}", redirected.display()).into_bytes(),
+        );
+        let url = serve(bodies);
+
+        for (pdb_name, content) in [
+            ("a.pdb", b"plain".as_slice()),
+            ("b.pdb", b"compressed".as_slice()),
+            ("c.pdb", b"redirected".as_slice()),
+        ] {
+            let path = download(&request(url.as_str(), cache.as_path(), pdb_name)).unwrap();
+            assert_eq!(path, cache.join(pdb_name).join(key).join(pdb_name));
+            assert_eq!(fs::read(path).unwrap(), content);
+        }
+        assert!(download(&request(url.as_str(), cache.as_path(), "d.pdb")).is_err());
+        assert!(!cache.join("d.pdb").exists());
+    }
+}
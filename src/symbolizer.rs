@@ -0,0 +1,220 @@
+//! Symbolize the frames of stacks on the worker threads. A batch is split by modules, so a pdb is parsed
+//! by one worker while the frames of other modules are symbolized by the others.
+use crate::{event_trace::process_modules::CodeView, pdb};
+use linked_hash_map::LinkedHashMap;
+use once_cell::sync::Lazy;
+use parking_lot::FairMutex;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+use tracing::{error, info};
+
+const WORKERS_MAX: usize = 4;
+
+pub type Locations = Vec<(
+    /*function_location*/ String,
+    /*line_location*/ String,
+)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameRequest {
+    // the full path of module. i.e. C:\Windows\System32\ntdll.dll
+    pub module_path: PathBuf,
+    pub time_date_stamp: u32,
+    pub code_view: Option<CodeView>,
+    pub offset: u32,
+}
+
+impl FrameRequest {
+    /// The locations if the pdb of module has been parsed, or the module is known to have no pdb
+    pub fn locations_loaded(&self) -> Option<Locations> {
+        let r = pdb::get_location_info_loaded(
+            self.module_path.as_path(),
+            self.time_date_stamp,
+            self.offset,
+        )?;
+        Some(r.unwrap_or_else(|_| vec![(String::new(), String::new())]))
+    }
+
+    /// Symbolize in the current thread. It may parse the pdb
+    pub fn locations(&self) -> Locations {
+        pdb::get_location_info(
+            self.module_path.as_path(),
+            self.time_date_stamp,
+            self.code_view.as_ref(),
+            self.offset,
+        )
+        .unwrap_or_else(|e| {
+            info!("{e:#}");
+            vec![(String::new(), String::new())]
+        })
+    }
+}
+
+struct Batch {
+    results: FairMutex<Vec<Locations>>,
+    jobs_remaining: AtomicUsize,
+    on_done: FairMutex<Option<Box<dyn FnOnce(Vec<Locations>) + Send>>>,
+}
+
+// the frames of a module in a batch
+struct Job {
+    frames: Vec<(/*index in batch*/ usize, FrameRequest)>,
+    batch: Arc<Batch>,
+}
+
+static WORKERS: Lazy<FairMutex<Sender<Job>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(FairMutex::new(receiver));
+    let count = thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+        .min(WORKERS_MAX);
+    for _ in 0..count {
+        let receiver = receiver.clone();
+        thread::spawn(move || work(receiver));
+    }
+    FairMutex::new(sender)
+});
+
+fn work(receiver: Arc<FairMutex<Receiver<Job>>>) {
+    loop {
+        let job = receiver.lock().recv();
+        let Ok(job) = job else {
+            return;
+        };
+        // a panic of the pdb parsing fails the frames of the job only. the worker and the batch go on
+        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+            job.frames
+                .iter()
+                .map(|(index, frame)| (*index, frame.locations()))
+                .collect::<Vec<(usize, Locations)>>()
+        }));
+        match r {
+            Ok(locations) => job.done(locations),
+            Err(_) => {
+                error!(
+                    "Panicked when symbolizing the frames of {}",
+                    job.frames
+                        .first()
+                        .map(|(_, frame)| frame.module_path.display().to_string())
+                        .unwrap_or_default()
+                );
+                job.fail();
+            }
+        }
+    }
+}
+
+impl Job {
+    fn done(self, locations: Vec<(usize, Locations)>) {
+        {
+            let mut results = self.batch.results.lock();
+            for (index, locations) in locations {
+                results[index] = locations;
+            }
+        }
+        if self.batch.jobs_remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let results = std::mem::take(&mut *self.batch.results.lock());
+            if let Some(on_done) = self.batch.on_done.lock().take() {
+                on_done(results);
+            }
+        }
+    }
+
+    // the frames are not symbolized, the batch is still completed
+    fn fail(self) {
+        let locations = self
+            .frames
+            .iter()
+            .map(|(index, _)| (*index, vec![(String::new(), String::new())]))
+            .collect();
+        self.done(locations);
+    }
+}
+
+/// Symbolize the frames of one or more stacks on the workers. on_done is called on a worker with the
+/// locations in the order of frames
+pub fn symbolize(frames: Vec<FrameRequest>, on_done: impl FnOnce(Vec<Locations>) + Send + 'static) {
+    if frames.is_empty() {
+        on_done(vec![]);
+        return;
+    }
+    // (module path, TimeDateStamp) -> frames
+    let mut modules = LinkedHashMap::new();
+    let frames_count = frames.len();
+    for (index, frame) in frames.into_iter().enumerate() {
+        modules
+            .entry((frame.module_path.clone(), frame.time_date_stamp))
+            .or_insert_with(Vec::new)
+            .push((index, frame));
+    }
+    let batch = Arc::new(Batch {
+        results: FairMutex::new(vec![vec![]; frames_count]),
+        jobs_remaining: AtomicUsize::new(modules.len()),
+        on_done: FairMutex::new(Some(Box::new(on_done))),
+    });
+    let sender = WORKERS.lock();
+    for (_, frames) in modules {
+        let job = Job {
+            frames,
+            batch: batch.clone(),
+        };
+        if let Err(mpsc::SendError(job)) = sender.send(job) {
+            error!("Failed to send the symbolizing job, no worker");
+            job.fail();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn symbolize_batch() {
+        let frames: Vec<FrameRequest> = [("a.dll", 0x10), ("b.dll", 0x20), ("a.dll", 0x30)]
+            .into_iter()
+            .map(|(module, offset)| FrameRequest {
+                module_path: PathBuf::from(module),
+                time_date_stamp: 1,
+                code_view: None,
+                offset,
+            })
+            .collect();
+        let (sender, receiver) = mpsc::channel();
+        symbolize(frames.clone(), move |locations| {
+            let _ = sender.send(locations);
+        });
+        let locations = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        // the modules are not exist, so they are not symbolized
+        assert_eq!(locations.len(), frames.len());
+        assert!(locations
+            .iter()
+            .all(|locations| locations == &vec![(String::new(), String::new())]));
+        // the module without pdb is not searched again
+        assert_eq!(
+            frames[0].locations_loaded(),
+            Some(vec![(String::new(), String::new())])
+        );
+        let frame = FrameRequest {
+            module_path: PathBuf::from("c.dll"),
+            ..frames[0].clone()
+        };
+        assert_eq!(frame.locations_loaded(), None);
+
+        let (sender, receiver) = mpsc::channel();
+        symbolize(vec![], move |locations| {
+            let _ = sender.send(locations);
+        });
+        assert!(receiver.recv().unwrap().is_empty());
+    }
+}
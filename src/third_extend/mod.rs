@@ -8,10 +8,10 @@ pub mod bytemuck {
     }
 }
 
-use serde::{Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use windows::core::GUID;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Guid(pub GUID);
 
 impl Serialize for Guid {
@@ -22,3 +22,38 @@ impl Serialize for Guid {
         serializer.serialize_str(format!("{:?}", self.0).as_str())
     }
 }
+
+// accept the format of serializing. i.e. `6A399AE0-4BC6-4DE9-870B-3657F8947E7E`
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let hex: String = s
+            .chars()
+            .filter(|c| !matches!(c, '-' | '{' | '}'))
+            .collect();
+        if hex.len() != 32 {
+            return Err(de::Error::custom(format!("invalid guid: {s}")));
+        }
+        u128::from_str_radix(hex.as_str(), 16)
+            .map(|num| Guid(GUID::from_u128(num)))
+            .map_err(|e| de::Error::custom(format!("invalid guid: {s} {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Guid;
+    use windows::core::GUID;
+
+    #[test]
+    fn guid_serde() {
+        let guid = Guid(GUID::from_u128(0x6a399ae0_4bc6_4de9_870b_3657f8947e7e));
+        let s = serde_json::to_string(&guid).unwrap();
+        assert_eq!(s, "\"6A399AE0-4BC6-4DE9-870B-3657F8947E7E\"");
+        let guid_1: Guid = serde_json::from_str(s.as_str()).unwrap();
+        assert_eq!(guid_1.0, guid.0);
+    }
+}
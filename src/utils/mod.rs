@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Result};
 use chrono::*;
-use serde::{Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::{env, ops::Sub, convert::From};
 use windows::Win32::Foundation::FILETIME;
 use windows::Win32::System::SystemInformation::GetSystemTimeAsFileTime;
@@ -74,6 +74,20 @@ impl Serialize for TimeStamp {
     }
 }
 
+// accept the detail string of serializing. i.e. `133627865451234567(2024-06-13 ...)`
+impl<'de> Deserialize<'de> for TimeStamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let num = s.split('(').next().unwrap_or_default().trim();
+        num.parse::<i64>()
+            .map(TimeStamp)
+            .map_err(|e| de::Error::custom(format!("invalid TimeStamp: {s} {e}")))
+    }
+}
+
 impl From<FILETIME> for TimeStamp {
     fn from(value: FILETIME) -> Self {
         let mut int = value.dwHighDateTime as u64;
@@ -157,6 +171,14 @@ mod tests {
         assert_eq!(s, String::from(r"C:\Program Files\Git\cmd\git.exe"));
     }
 
+    #[test]
+    fn time_stamp_serde() {
+        let ts = super::TimeStamp(133627865451234567);
+        let s = serde_json::to_string(&ts).unwrap();
+        let ts_1: super::TimeStamp = serde_json::from_str(s.as_str()).unwrap();
+        assert_eq!(ts_1.0, ts.0);
+    }
+
     #[test]
     fn get_exe_dir() {
         let r = super::get_exe_dir();